@@ -0,0 +1,121 @@
+//! Derive macros for the `pjrt` crate.
+//!
+//! Use the re-exports in `pjrt` rather than depending on this crate
+//! directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index, Member};
+
+/// Derives `pjrt::PjrtTree` for a struct whose fields all implement it.
+///
+/// The struct becomes a `TreeDef::Struct` node named after the struct, with
+/// one child per field in declaration order. Tuple struct fields are named
+/// `0`, `1`, ... and unit structs have no children.
+#[proc_macro_derive(PjrtTree)]
+pub fn derive_pjrt_tree(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => data.fields.clone(),
+        Data::Enum(data) => {
+            return Err(syn::Error::new_spanned(
+                data.enum_token,
+                "PjrtTree can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "PjrtTree can only be derived for structs",
+            ))
+        }
+    };
+
+    let members: Vec<Member> = match &fields {
+        Fields::Named(named) => named
+            .named
+            .iter()
+            .map(|f| Member::Named(f.ident.clone().unwrap()))
+            .collect(),
+        Fields::Unnamed(unnamed) => (0..unnamed.unnamed.len())
+            .map(|i| Member::Unnamed(Index::from(i)))
+            .collect(),
+        Fields::Unit => vec![],
+    };
+    let names: Vec<String> = members
+        .iter()
+        .map(|member| match member {
+            Member::Named(ident) => ident.to_string(),
+            Member::Unnamed(index) => index.index.to_string(),
+        })
+        .collect();
+    let types: Vec<_> = fields.iter().map(|f| f.ty.clone()).collect();
+
+    let where_clause = input.generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: ::pjrt::PjrtTree));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let children: Vec<_> = (0..members.len())
+        .map(|i| format_ident!("__child{}", i))
+        .collect();
+    let construct = match &fields {
+        Fields::Named(_) => quote! { Self { #(#members: #children),* } },
+        Fields::Unnamed(_) => quote! { Self(#(#children),*) },
+        Fields::Unit => quote! { Self },
+    };
+    let indices = 0..members.len();
+
+    Ok(quote! {
+        impl #impl_generics ::pjrt::PjrtTree for #ident #ty_generics #where_clause {
+            fn tree_def(&self) -> ::pjrt::TreeDef {
+                ::pjrt::TreeDef::Struct {
+                    name: #name.to_string(),
+                    fields: ::std::vec![
+                        #((#names.to_string(), ::pjrt::PjrtTree::tree_def(&self.#members))),*
+                    ],
+                }
+            }
+
+            fn flatten_into(self, __leaves: &mut ::std::vec::Vec<::pjrt::Buffer>) {
+                #(::pjrt::PjrtTree::flatten_into(self.#members, __leaves);)*
+            }
+
+            fn unflatten_from(
+                def: &::pjrt::TreeDef,
+                __leaves: &mut ::std::vec::IntoIter<::pjrt::Buffer>,
+            ) -> ::pjrt::Result<Self> {
+                let __fields = def.struct_fields(#name, &[#(#names),*])?;
+                #(
+                    let #children = <#types as ::pjrt::PjrtTree>::unflatten_from(
+                        &__fields[#indices].1,
+                        __leaves,
+                    )?;
+                )*
+                ::std::result::Result::Ok(#construct)
+            }
+
+            fn static_tree_def() -> ::std::option::Option<::pjrt::TreeDef> {
+                ::std::option::Option::Some(::pjrt::TreeDef::Struct {
+                    name: #name.to_string(),
+                    fields: ::std::vec![
+                        #((#names.to_string(), <#types as ::pjrt::PjrtTree>::static_tree_def()?)),*
+                    ],
+                })
+            }
+        }
+    })
+}
@@ -1,9 +1,10 @@
 //! Key-Value Store Example
 //!
 //! This example demonstrates how to use the PJRT KeyValueStore trait:
-//! 1. Implementing a custom KeyValueStore
+//! 1. Using the built-in in-memory and file-backed stores
 //! 2. Using KeyValueStore with Client builder for distributed coordination
 //! 3. Understanding get, put, and try_get operations
+//! 4. Wrapping a store to add custom behaviour
 //!
 //! The KeyValueStore is essential for distributed/multi-node PJRT setups
 //! where processes need to coordinate through a shared key-value store.
@@ -14,76 +15,7 @@
 //! cargo run --example kv_store
 //! ```
 
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-
-use pjrt::{self, KeyValueStore, Result};
-
-/// A simple in-memory KeyValueStore implementation for demonstration
-#[derive(Debug, Clone)]
-struct InMemoryKeyValueStore {
-    data: Arc<Mutex<HashMap<String, Vec<u8>>>>,
-}
-
-impl InMemoryKeyValueStore {
-    fn new() -> Self {
-        Self {
-            data: Arc::new(Mutex::new(HashMap::new())),
-        }
-    }
-}
-
-impl KeyValueStore for InMemoryKeyValueStore {
-    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
-        println!("   KV Store: GET '{}' (timeout={}ms)", key, timeout_in_ms);
-
-        let data = self.data.lock().unwrap();
-        match data.get(key) {
-            Some(value) => {
-                println!("   KV Store: Found value for '{}'", key);
-                Ok(value.clone())
-            }
-            None => {
-                println!(
-                    "   KV Store: Key '{}' not found (would wait in real impl)",
-                    key
-                );
-                Err(pjrt::Error::InvalidArgument(format!(
-                    "Key not found: {}",
-                    key
-                )))
-            }
-        }
-    }
-
-    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
-        println!(
-            "   KV Store: PUT '{}' = '{}'",
-            key,
-            String::from_utf8_lossy(value)
-        );
-
-        let mut data = self.data.lock().unwrap();
-        data.insert(key.to_string(), value.to_vec());
-        Ok(())
-    }
-
-    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
-        println!("   KV Store: TRY_GET '{}'", key);
-
-        let data = self.data.lock().unwrap();
-        match data.get(key) {
-            Some(value) => {
-                println!("   KV Store: Found value for '{}'", key);
-                Ok(Some(value.clone()))
-            }
-            None => {
-                println!("   KV Store: Key '{}' not found (non-blocking)", key);
-                Ok(None)
-            }
-        }
-    }
-}
+use pjrt::{self, FileKeyValueStore, InMemoryKeyValueStore, KeyValueStore, Result};
 
 fn main() -> Result<()> {
     println!("Key-Value Store Example");
@@ -139,6 +71,29 @@ fn demonstrate_kv_store_trait() -> Result<()> {
 
     println!();
 
+    // A blocking GET times out with a DEADLINE_EXCEEDED error
+    match store.get("cluster/late", 100) {
+        Ok(_) => println!("   Unexpected value for cluster/late"),
+        Err(err) => println!("   GET timed out as expected: {} ({:?})", err, err.code()),
+    }
+
+    println!();
+
+    // The file-backed store shares keys through a directory
+    println!("   File-backed store:");
+    let dir = std::env::temp_dir().join("pjrt-kv-store-example");
+    let file_store = FileKeyValueStore::new(&dir)?;
+    file_store.put("cluster/size", b"4")?;
+    let size = FileKeyValueStore::new(&dir)?.get("cluster/size", 5000)?;
+    println!(
+        "   Cluster size from {}: {}",
+        dir.display(),
+        String::from_utf8_lossy(&size)
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+
+    println!();
+
     Ok(())
 }
 
@@ -152,10 +107,10 @@ fn demonstrate_client_integration() -> Result<()> {
 
     println!("   Example usage pattern:");
     println!();
-    println!("   let kv_store = MyKeyValueStore::new(redis_connection);");
+    println!("   let kv_store = Arc::new(MyKeyValueStore::new(redis_connection));");
     println!();
     println!("   let client = Client::builder(&api)");
-    println!("       .key_value_store(&kv_store)  // Provide the KV store");
+    println!("       .kv_store(kv_store)            // Provide the KV store");
     println!("       .process_index(0)              // This process index");
     println!("       .process_count(4)              // Total processes");
     println!("       .build()?;");
@@ -165,7 +120,8 @@ fn demonstrate_client_integration() -> Result<()> {
     println!("   - Redis: For distributed coordination");
     println!("   - etcd: For Kubernetes-native deployments");
     println!("   - Consul: For service discovery");
-    println!("   - Custom: In-memory (testing), filesystem, etc.\n");
+    println!("   - InMemoryKeyValueStore: Simulated processes in one test");
+    println!("   - FileKeyValueStore: A directory shared between processes\n");
 
     println!("   Typical keys used by PJRT:");
     println!("   - Process metadata and heartbeats");
@@ -0,0 +1,377 @@
+//! Ahead-of-Time Compilation
+//!
+//! This module provides [`AotArtifact`], which supports compiling for a
+//! machine other than the one doing the compilation and shipping the result:
+//!
+//! 1. Describe the target with a [`TopologyDescription`], either deserialized
+//!    from a topology serialized on the target with
+//!    [`TopologyDescription::deserialize`], or created from a plugin-specific
+//!    topology name with [`Api::create_topology`].
+//! 2. Compile with [`AotArtifact::compile`], which bundles the serialized
+//!    executable with the serialized topology, the compile options and the
+//!    platform it was compiled for.
+//! 3. Write the artifact to a file with [`AotArtifact::write`].
+//! 4. On the target host, [`AotArtifact::read`] it back and load it with
+//!    [`AotArtifact::load`], which checks that the client matches the
+//!    artifact's platform and topology before deserializing the executable.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! // On the build machine:
+//! let api = plugin("pjrt_c_api_tpu_plugin.so").load()?;
+//! let topology = api.create_topology("v5e:2x2", vec![])?;
+//! let artifact = AotArtifact::compile(&api, &program, &topology, CompileOptions::new())?;
+//! artifact.write("model.pjrt")?;
+//!
+//! // On the target host:
+//! let artifact = AotArtifact::read("model.pjrt")?;
+//! let executable = artifact.load(&client)?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use prost::Message;
+
+use crate::{
+    Api, Client, CompileOptions, CompileToExecutable, Error, Executable, LoadedExecutable, Result,
+    TopologyDescription,
+};
+
+/// Magic bytes at the start of every artifact file.
+const MAGIC: &[u8; 8] = b"PJRTAOT\0";
+
+/// Version of the artifact encoding.
+const FORMAT_VERSION: u32 = 1;
+
+/// The encoded form of an [`AotArtifact`], following [`MAGIC`].
+#[derive(Clone, PartialEq, Message)]
+struct AotArtifactProto {
+    #[prost(uint32, tag = "1")]
+    format_version: u32,
+    #[prost(string, tag = "2")]
+    platform_name: String,
+    #[prost(string, tag = "3")]
+    platform_version: String,
+    #[prost(int32, tag = "4")]
+    api_major_version: i32,
+    #[prost(int32, tag = "5")]
+    api_minor_version: i32,
+    #[prost(bytes = "vec", tag = "6")]
+    topology: Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    compile_options: Vec<u8>,
+    #[prost(bytes = "vec", tag = "8")]
+    executable: Vec<u8>,
+}
+
+/// An executable compiled ahead of time, bundled with the target topology,
+/// the compile options and the platform it was compiled for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AotArtifact {
+    platform_name: String,
+    platform_version: String,
+    api_version: (i32, i32),
+    topology: Vec<u8>,
+    compile_options: Vec<u8>,
+    executable: Vec<u8>,
+}
+
+impl AotArtifact {
+    /// Compiles `program` for `topology` without a client and bundles the
+    /// result.
+    pub fn compile<T>(
+        api: &Api,
+        program: &T,
+        topology: &TopologyDescription,
+        options: CompileOptions,
+    ) -> Result<Self>
+    where
+        Api: CompileToExecutable<T>,
+    {
+        let executable = api.compile(program, topology, options.clone(), None)?;
+        Self::new(api, &executable, topology, &options)
+    }
+
+    /// Bundles an `executable` compiled for `topology` with `options`.
+    pub fn new(
+        api: &Api,
+        executable: &Executable,
+        topology: &TopologyDescription,
+        options: &CompileOptions,
+    ) -> Result<Self> {
+        let version = api.version();
+        Ok(Self {
+            platform_name: topology.platform_name()?.into_owned(),
+            platform_version: topology.platform_version()?.into_owned(),
+            api_version: (version.major_version, version.minor_version),
+            topology: topology.serialize()?.bytes().to_vec(),
+            compile_options: options.encode(),
+            executable: executable.serialize()?.bytes().to_vec(),
+        })
+    }
+
+    /// Returns the name of the platform the artifact was compiled for.
+    pub fn platform_name(&self) -> &str {
+        &self.platform_name
+    }
+
+    /// Returns the version of the platform the artifact was compiled for.
+    pub fn platform_version(&self) -> &str {
+        &self.platform_version
+    }
+
+    /// Returns the PJRT C API version of the compiling plugin as
+    /// `(major, minor)`.
+    pub fn api_version(&self) -> (i32, i32) {
+        self.api_version
+    }
+
+    /// Returns the serialized target topology.
+    pub fn topology_bytes(&self) -> &[u8] {
+        &self.topology
+    }
+
+    /// Deserializes the target topology.
+    pub fn topology(&self, api: &Api) -> Result<TopologyDescription> {
+        TopologyDescription::deserialize(api, &self.topology)
+    }
+
+    /// Returns the options the artifact was compiled with.
+    pub fn compile_options(&self) -> Result<CompileOptions> {
+        CompileOptions::decode(&self.compile_options)
+    }
+
+    /// Returns the serialized executable.
+    pub fn executable_bytes(&self) -> &[u8] {
+        &self.executable
+    }
+
+    /// Returns the number of devices the executable runs on, from the number
+    /// of replicas and partitions it was compiled for.
+    pub fn num_devices(&self) -> Result<usize> {
+        let options = self.compile_options()?;
+        let Some(build) = &options.proto().executable_build_options else {
+            return Ok(1);
+        };
+        let num_replicas = build.num_replicas.max(1) as usize;
+        let num_partitions = build.num_partitions.max(1) as usize;
+        Ok(num_replicas * num_partitions)
+    }
+
+    /// Checks that the artifact can be loaded on `client`.
+    ///
+    /// The client must run the same platform and platform version, a PJRT C
+    /// API with the same major version, and have the topology the artifact
+    /// was compiled for: the same number of devices of each kind.
+    pub fn check_compatible(&self, client: &Client) -> Result<()> {
+        let platform = format!("platform {} {}", self.platform_name, self.platform_version);
+        let client_platform = format!(
+            "platform {} {}",
+            client.platform_name()?,
+            client.platform_version()?
+        );
+        if platform != client_platform {
+            return Err(Error::IncompatibleArtifact {
+                expected: platform,
+                actual: client_platform,
+            });
+        }
+
+        let major_version = client.api().version().major_version;
+        if self.api_version.0 != major_version {
+            return Err(Error::IncompatibleArtifact {
+                expected: format!("PJRT C API {}.{}", self.api_version.0, self.api_version.1),
+                actual: format!(
+                    "PJRT C API {}.{}",
+                    major_version,
+                    client.api().version().minor_version
+                ),
+            });
+        }
+
+        let topology = describe_topology(&self.topology(client.api())?)?;
+        let client_topology = describe_topology(&client.topology()?)?;
+        if topology != client_topology {
+            return Err(Error::IncompatibleArtifact {
+                expected: topology,
+                actual: client_topology,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks compatibility with `client` and loads the executable on it.
+    pub fn load(&self, client: &Client) -> Result<LoadedExecutable> {
+        self.check_compatible(client)?;
+        client.load_executable(&self.executable)
+    }
+
+    /// Encodes the artifact into bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let proto = AotArtifactProto {
+            format_version: FORMAT_VERSION,
+            platform_name: self.platform_name.clone(),
+            platform_version: self.platform_version.clone(),
+            api_major_version: self.api_version.0,
+            api_minor_version: self.api_version.1,
+            topology: self.topology.clone(),
+            compile_options: self.compile_options.clone(),
+            executable: self.executable.clone(),
+        };
+        let mut bytes = Vec::with_capacity(MAGIC.len() + proto.encoded_len());
+        bytes.extend_from_slice(MAGIC);
+        proto.encode(&mut bytes).expect("vec has enough capacity");
+        bytes
+    }
+
+    /// Decodes an artifact produced by [`AotArtifact::encode`].
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let Some(body) = bytes.strip_prefix(MAGIC) else {
+            return Err(Error::InvalidArgument(
+                "not a PJRT ahead-of-time artifact".to_string(),
+            ));
+        };
+        let proto = AotArtifactProto::decode(body)?;
+        if proto.format_version != FORMAT_VERSION {
+            return Err(Error::InvalidArgument(format!(
+                "unsupported artifact format version {}, expected {}",
+                proto.format_version, FORMAT_VERSION
+            )));
+        }
+        Ok(Self {
+            platform_name: proto.platform_name,
+            platform_version: proto.platform_version,
+            api_version: (proto.api_major_version, proto.api_minor_version),
+            topology: proto.topology,
+            compile_options: proto.compile_options,
+            executable: proto.executable,
+        })
+    }
+
+    /// Writes the encoded artifact to the file at `path`.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.encode())?;
+        Ok(())
+    }
+
+    /// Reads an artifact from the file at `path`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+}
+
+/// Describes a topology by the number of devices of each kind, e.g.
+/// `topology of 4 TPU v5 lite devices`.
+fn describe_topology(topology: &TopologyDescription) -> Result<String> {
+    let kinds = topology
+        .device_descriptions()?
+        .iter()
+        .map(|description| Ok(description.kind()?.into_owned()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(describe_device_kinds(kinds))
+}
+
+fn describe_device_kinds(kinds: impl IntoIterator<Item = String>) -> String {
+    let mut counts = BTreeMap::new();
+    for kind in kinds {
+        *counts.entry(kind).or_insert(0) += 1;
+    }
+    if counts.is_empty() {
+        return "topology of 0 devices".to_string();
+    }
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(kind, count)| format!("{count} {kind}"))
+        .collect();
+    format!("topology of {} devices", counts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExecutableBuildOptions;
+
+    fn artifact() -> AotArtifact {
+        let options = CompileOptions::new().executable_build_options(
+            ExecutableBuildOptions::new()
+                .num_replicas(2)
+                .num_partitions(4),
+        );
+        AotArtifact {
+            platform_name: "cpu".to_string(),
+            platform_version: "1.0".to_string(),
+            api_version: (0, 70),
+            topology: vec![1, 2, 3],
+            compile_options: options.encode(),
+            executable: vec![4, 5, 6, 7],
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let artifact = artifact();
+        let bytes = artifact.encode();
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(AotArtifact::decode(&bytes).unwrap(), artifact);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let err = AotArtifact::decode(b"not an artifact").unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+
+        let mut bytes = MAGIC.to_vec();
+        AotArtifactProto {
+            format_version: FORMAT_VERSION + 1,
+            ..Default::default()
+        }
+        .encode(&mut bytes)
+        .unwrap();
+        let err = AotArtifact::decode(&bytes).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(0xff);
+        let err = AotArtifact::decode(&bytes).unwrap_err();
+        assert!(matches!(err, Error::ProtoDecodeError(_)));
+    }
+
+    #[test]
+    fn test_write_read() {
+        let path = std::env::temp_dir().join(format!("pjrt-aot-{}.pjrt", std::process::id()));
+        let artifact = artifact();
+        artifact.write(&path).unwrap();
+        let read = AotArtifact::read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), artifact);
+    }
+
+    #[test]
+    fn test_compile_options() {
+        let artifact = artifact();
+        let options = artifact.compile_options().unwrap();
+        let build = options.proto().executable_build_options.as_ref().unwrap();
+        assert_eq!(build.num_replicas, 2);
+        assert_eq!(build.num_partitions, 4);
+        assert_eq!(artifact.num_devices().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_describe_device_kinds() {
+        let kinds = |kinds: &[&str]| describe_device_kinds(kinds.iter().map(|k| k.to_string()));
+        assert_eq!(
+            kinds(&["TPU v5 lite"; 4]),
+            "topology of 4 TPU v5 lite devices"
+        );
+        assert_ne!(kinds(&["TPU v5 lite"; 4]), kinds(&["TPU v5 lite"; 8]));
+        assert_eq!(kinds(&["gpu", "cpu", "gpu"]), kinds(&["gpu", "gpu", "cpu"]));
+        assert_eq!(
+            kinds(&["gpu", "cpu", "gpu"]),
+            "topology of 1 cpu, 2 gpu devices"
+        );
+        assert_eq!(kinds(&[]), "topology of 0 devices");
+    }
+}
@@ -64,6 +64,7 @@
 //! ```
 
 use std::backtrace::Backtrace;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 
 use pjrt_sys::{
@@ -74,11 +75,13 @@ use pjrt_sys::{
 };
 
 use crate::extension::Extension;
-use crate::kv_store::{kv_get_callback, kv_put_callback, kv_try_get_callback};
+use crate::kv_store::{kv_get_callback, kv_put_callback, kv_try_get_callback, CallbackStore};
 use crate::named_value::NamedValueMap;
+use crate::stablehlo::{check_version, supported_range};
 use crate::{
     utils, Client, CompileOptions, CompileToExecutable, Error, ErrorCode, Executable,
-    ExecuteContext, KeyValueStore, NamedValue, Program, Result, TopologyDescription,
+    ExecuteContext, KeyValueStore, NamedValue, Program, Result, StablehloVersion,
+    TopologyDescription,
 };
 
 /// The main entry point for interacting with a PJRT plugin.
@@ -227,6 +230,46 @@ impl Api {
         utils::to_named_value_map(args.attributes, args.num_attributes)
     }
 
+    /// Returns the range of StableHLO versions the plugin can deserialize.
+    ///
+    /// Returns `None` if the plugin does not advertise
+    /// `stablehlo_current_version`.
+    pub fn stablehlo_version_range(&self) -> Result<Option<RangeInclusive<StablehloVersion>>> {
+        Ok(supported_range(&self.plugin_attributes()?))
+    }
+
+    /// Checks that the plugin can deserialize `program`.
+    ///
+    /// Returns `Error::IncompatibleStablehloVersion` if the program is
+    /// portable bytecode whose producer records a StableHLO version outside
+    /// the plugin's supported range. Other programs, and plugins that do not
+    /// advertise a range, always pass.
+    pub fn check_stablehlo_compatibility(&self, program: &Program) -> Result<()> {
+        self.check_stablehlo_version(program.stablehlo_version())
+    }
+
+    /// Checks the `stablehlo.version` module attribute of a textual MLIR
+    /// program against the plugin's supported range.
+    ///
+    /// Unlike [`Api::check_stablehlo_compatibility`], this is not done when
+    /// compiling: the attribute is an annotation, and textual MLIR is parsed
+    /// by the plugin's own StableHLO version.
+    pub fn check_stablehlo_attribute_compatibility(&self, program: &Program) -> Result<()> {
+        self.check_stablehlo_version(program.stablehlo_version_attribute())
+    }
+
+    fn check_stablehlo_version(&self, version: Option<StablehloVersion>) -> Result<()> {
+        let Some(version) = version else {
+            return Ok(());
+        };
+        // Older plugins may not implement PJRT_Plugin_Attributes; let the
+        // compiler report any problem in that case.
+        let Ok(Some(supported)) = self.stablehlo_version_range() else {
+            return Ok(());
+        };
+        check_version(version, &supported)
+    }
+
     pub fn create_execute_context(&self) -> Result<ExecuteContext> {
         let mut args = PJRT_ExecuteContext_Create_Args::new();
         args = self.PJRT_ExecuteContext_Create(args)?;
@@ -249,25 +292,30 @@ impl Api {
         Ok(TopologyDescription::wrap(self, args.topology, None))
     }
 
+    /// Creates a client.
+    ///
+    /// The client keeps `kv_store` alive, as PJRT may call it from any thread
+    /// for as long as the client exists.
     pub fn create_client(
         &self,
         options: Vec<NamedValue>,
-        kv_store: Option<&dyn KeyValueStore>,
+        kv_store: Option<Arc<dyn KeyValueStore + Send + Sync>>,
     ) -> Result<Client> {
+        let kv_store = kv_store.map(CallbackStore::new);
         let create_options: Vec<PJRT_NamedValue> = options.iter().map(Into::into).collect();
         let mut args = PJRT_Client_Create_Args::new();
         args.create_options = create_options.as_ptr();
         args.num_options = create_options.len();
-        if let Some(kv_store) = kv_store {
+        if let Some(kv_store) = &kv_store {
             args.kv_get_callback = Some(kv_get_callback);
-            args.kv_get_user_arg = &kv_store as *const &dyn KeyValueStore as *mut _;
+            args.kv_get_user_arg = kv_store.user_arg();
             args.kv_put_callback = Some(kv_put_callback);
-            args.kv_put_user_arg = &kv_store as *const &dyn KeyValueStore as *mut _;
+            args.kv_put_user_arg = kv_store.user_arg();
             args.kv_try_get_callback = Some(kv_try_get_callback);
-            args.kv_try_get_user_arg = &kv_store as *const &dyn KeyValueStore as *mut _;
+            args.kv_try_get_user_arg = kv_store.user_arg();
         }
         args = self.PJRT_Client_Create(args)?;
-        Ok(Client::wrap(self, args.client))
+        Ok(Client::wrap_with_store(self, args.client, kv_store))
     }
 
     pub fn compile<T>(
@@ -352,6 +400,7 @@ impl CompileToExecutable<Program> for Api {
         options: &CompileOptions,
         client: Option<&Client>,
     ) -> Result<Executable> {
+        self.check_stablehlo_compatibility(program)?;
         let options_encoded = options.encode();
         let mut args = PJRT_Compile_Args::new();
         args.topology = topology.ptr;
@@ -0,0 +1,422 @@
+//! Dynamic Batching
+//!
+//! This module provides `Batcher`, which serves single-example requests with
+//! executables compiled for fixed batch sizes. Requests are queued until
+//! either `max_batch_size` of them are waiting or the oldest has waited
+//! `max_latency`. The queued requests are then concatenated along a new
+//! leading batch dimension, padded with zeros up to the smallest compiled
+//! batch size that fits, executed, and the outputs are split back per
+//! request.
+//!
+//! Each argument of a request has the shape of the corresponding executable
+//! parameter without its leading batch dimension. Every output of the
+//! executables must have the batch dimension as its leading dimension.
+//!
+//! Like the rest of the crate, a `Batcher` stays on the thread of its client:
+//! drive [`Batcher::serve`] and the callers of [`Batcher::infer`] from the
+//! same thread, e.g. on a `tokio::task::LocalSet`.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let batcher = Rc::new(
+//!     Batcher::new([(1, exe_1), (4, exe_4), (16, exe_16)])?
+//!         .max_latency(Duration::from_millis(2)),
+//! );
+//!
+//! let server = batcher.clone();
+//! tokio::task::spawn_local(async move { server.serve().await });
+//!
+//! // From any number of local tasks:
+//! let outputs = batcher.infer(vec![example]).await?;
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{oneshot, Notify};
+
+use crate::{
+    utils, Buffer, Error, ExecuteOptions, HostBuffer, LoadedExecutable, MemoryLayout,
+    PrimitiveType, Result,
+};
+
+/// The default time the oldest request may wait for a batch to fill.
+const DEFAULT_MAX_LATENCY: Duration = Duration::from_millis(5);
+
+/// Batches single-example requests onto executables compiled for fixed batch
+/// sizes.
+///
+/// # Thread Safety
+///
+/// `Batcher` is `!Send + !Sync` because it owns [`LoadedExecutable`]s.
+pub struct Batcher {
+    /// Executables by batch size, in ascending order.
+    executables: Vec<(usize, LoadedExecutable)>,
+    max_batch_size: usize,
+    max_latency: Duration,
+    queue: RefCell<VecDeque<Request>>,
+    notify: Notify,
+    closed: Cell<bool>,
+    /// The number of arguments of every request, from the signature of the
+    /// executables or, if it is unavailable, the first accepted request.
+    num_args: Cell<Option<usize>>,
+}
+
+struct Request {
+    inputs: Vec<HostBuffer>,
+    arrived: Instant,
+    reply: oneshot::Sender<Result<Vec<HostBuffer>>>,
+}
+
+impl Batcher {
+    /// Creates a batcher from executables and the batch size each was
+    /// compiled for.
+    ///
+    /// Returns an error if no executable is given, a batch size is zero, or
+    /// two executables share a batch size.
+    pub fn new<I>(executables: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (usize, LoadedExecutable)>,
+    {
+        let mut executables: Vec<_> = executables.into_iter().collect();
+        executables.sort_by_key(|(batch_size, _)| *batch_size);
+        if executables.is_empty() {
+            return Err(Error::InvalidArgument(
+                "batcher requires at least one executable".to_string(),
+            ));
+        }
+        if executables[0].0 == 0 {
+            return Err(Error::InvalidArgument(
+                "batch sizes must be positive".to_string(),
+            ));
+        }
+        if let Some(pair) = executables.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate executable for batch size {}",
+                pair[0].0
+            )));
+        }
+        let max_batch_size = executables[executables.len() - 1].0;
+        Ok(Self {
+            executables,
+            max_batch_size,
+            max_latency: DEFAULT_MAX_LATENCY,
+            queue: RefCell::new(VecDeque::new()),
+            notify: Notify::new(),
+            closed: Cell::new(false),
+            num_args: Cell::new(None),
+        })
+    }
+
+    /// Sets the maximum number of requests executed together.
+    ///
+    /// Defaults to the largest compiled batch size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_batch_size` is zero or exceeds the largest compiled
+    /// batch size.
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        let largest = self.executables[self.executables.len() - 1].0;
+        assert!(
+            (1..=largest).contains(&max_batch_size),
+            "max_batch_size must be between 1 and {largest}"
+        );
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    /// Sets how long the oldest queued request may wait for more requests
+    /// before a partial batch is executed.
+    ///
+    /// Defaults to 5 ms.
+    pub fn max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = max_latency;
+        self
+    }
+
+    /// Returns the maximum number of requests executed together.
+    pub fn get_max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    /// Returns the maximum time a request waits for its batch to fill.
+    pub fn get_max_latency(&self) -> Duration {
+        self.max_latency
+    }
+
+    /// Returns the compiled batch sizes in ascending order.
+    pub fn batch_sizes(&self) -> Vec<usize> {
+        self.executables.iter().map(|(size, _)| *size).collect()
+    }
+
+    /// Returns the number of requests waiting to be batched.
+    pub fn queued(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    /// Queues a single-example request and waits for its outputs.
+    ///
+    /// Returns `Error::InputMismatch` if the arguments do not match the
+    /// executables' parameters without their batch dimension,
+    /// `Error::BatchFailed` if the batch could not be executed, and
+    /// `Error::BatcherClosed` if the batcher was closed or dropped before
+    /// serving the request.
+    ///
+    /// If the executables' signature is unavailable, the number of arguments
+    /// of the first accepted request is required of every later request.
+    pub async fn infer(&self, inputs: Vec<HostBuffer>) -> Result<Vec<HostBuffer>> {
+        if self.closed.get() {
+            return Err(Error::BatcherClosed);
+        }
+        self.check_request(&inputs)?;
+        let (reply, outputs) = oneshot::channel();
+        self.queue.borrow_mut().push_back(Request {
+            inputs,
+            arrived: Instant::now(),
+            reply,
+        });
+        self.notify.notify_one();
+        outputs.await.unwrap_or(Err(Error::BatcherClosed))
+    }
+
+    /// Stops accepting requests.
+    ///
+    /// [`Batcher::serve`] returns once the queued requests are served.
+    pub fn close(&self) {
+        self.closed.set(true);
+        self.notify.notify_one();
+    }
+
+    /// Forms and executes batches until the batcher is closed.
+    ///
+    /// Failures are reported to the requests of the affected batch, so this
+    /// only returns once [`Batcher::close`] was called and the queue is empty.
+    pub async fn serve(&self) {
+        loop {
+            let first_arrival = loop {
+                if let Some(request) = self.queue.borrow().front() {
+                    break request.arrived;
+                }
+                if self.closed.get() {
+                    return;
+                }
+                self.notify.notified().await;
+            };
+            let deadline = first_arrival + self.max_latency;
+            while self.queued() < self.max_batch_size && !self.closed.get() {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline.into()) => break,
+                    _ = self.notify.notified() => {}
+                }
+            }
+            let batch: Vec<Request> = {
+                let mut queue = self.queue.borrow_mut();
+                let n = queue.len().min(self.max_batch_size);
+                queue.drain(..n).collect()
+            };
+            self.dispatch(batch).await;
+        }
+    }
+
+    /// Executes `batch` and replies to each of its requests.
+    async fn dispatch(&self, batch: Vec<Request>) {
+        match self.execute(&batch).await {
+            Ok(outputs) => {
+                for (request, outputs) in batch.into_iter().zip(outputs) {
+                    let _ = request.reply.send(Ok(outputs));
+                }
+            }
+            Err(err) => {
+                let (code, msg) = (err.code(), err.to_string());
+                for request in batch {
+                    let _ = request.reply.send(Err(Error::BatchFailed {
+                        code,
+                        msg: msg.clone(),
+                    }));
+                }
+            }
+        }
+    }
+
+    async fn execute(&self, batch: &[Request]) -> Result<Vec<Vec<HostBuffer>>> {
+        let (batch_size, executable) = self
+            .executables
+            .iter()
+            .find(|(size, _)| *size >= batch.len())
+            .ok_or_else(|| Error::InvalidArgument(format!("no executable fits {}", batch.len())))?;
+        let device = executable
+            .addressable_devices()?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoAddressableDevice)?;
+        let num_args = batch[0].inputs.len();
+        let mut args = Vec::with_capacity(num_args);
+        for arg_index in 0..num_args {
+            let examples = batch.iter().map(|request| &request.inputs[arg_index]);
+            let host = concat_examples(arg_index, examples, *batch_size)?;
+            args.push(host.to(&device).await?);
+        }
+        let outputs = executable.execute(args, &ExecuteOptions::new()).await?;
+        let mut per_request: Vec<Vec<HostBuffer>> = batch.iter().map(|_| Vec::new()).collect();
+        for buffer in outputs.into_iter().next().unwrap_or_default() {
+            let examples = split_batch(&buffer, *batch_size).await?;
+            for (request_outputs, example) in per_request.iter_mut().zip(examples) {
+                request_outputs.push(example);
+            }
+        }
+        Ok(per_request)
+    }
+
+    /// Checks a request against the parameters of the smallest executable.
+    fn check_request(&self, inputs: &[HostBuffer]) -> Result<()> {
+        let signature = self.executables[0].1.cached_signature();
+        let num_args = signature
+            .map(|signature| signature.parameters().len())
+            .or(self.num_args.get())
+            .unwrap_or(inputs.len());
+        if inputs.len() != num_args {
+            return Err(Error::InputMismatch {
+                arg_index: None,
+                expected: format!("{num_args} arguments"),
+                actual: format!("{} arguments", inputs.len()),
+            });
+        }
+        self.num_args.set(Some(num_args));
+        let Some(signature) = signature else {
+            return Ok(());
+        };
+        let parameters = signature.parameters();
+        for (arg_index, (input, parameter)) in inputs.iter().zip(parameters).enumerate() {
+            let shape = &parameter.shape;
+            let example_dims = shape.dims.get(1..).unwrap_or_default();
+            if input.primitive_type() != shape.primitive_type || input.dims() != example_dims {
+                return Err(Error::InputMismatch {
+                    arg_index: Some(arg_index),
+                    expected: format!("{:?}{:?}", shape.primitive_type, example_dims),
+                    actual: format!("{:?}{:?}", input.primitive_type(), input.dims()),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Batcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Batcher")
+            .field("batch_sizes", &self.batch_sizes())
+            .field("max_batch_size", &self.max_batch_size)
+            .field("max_latency", &self.max_latency)
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+/// Stacks `examples` along a new leading dimension of size `batch_size`,
+/// zero-padding the missing examples.
+fn concat_examples<'b>(
+    arg_index: usize,
+    examples: impl IntoIterator<Item = &'b HostBuffer>,
+    batch_size: usize,
+) -> Result<HostBuffer> {
+    let mut examples = examples.into_iter().peekable();
+    let first = examples
+        .peek()
+        .ok_or_else(|| Error::InvalidArgument("cannot concatenate an empty batch".to_string()))?;
+    let ty = first.primitive_type();
+    let dims = first.dims().to_vec();
+    let elem_size = element_size(ty)?;
+    let example_size = first.as_bytes().len();
+    let mut bytes = Vec::with_capacity(example_size * batch_size);
+    for example in examples {
+        if example.primitive_type() != ty || example.dims() != dims {
+            return Err(Error::InputMismatch {
+                arg_index: Some(arg_index),
+                expected: format!("{ty:?}{dims:?}"),
+                actual: format!("{:?}{:?}", example.primitive_type(), example.dims()),
+            });
+        }
+        if !utils::is_row_major(example.layout(), &dims, elem_size) {
+            return Err(Error::InvalidArgument(format!(
+                "argument {arg_index} must have a dense row-major layout to be batched"
+            )));
+        }
+        bytes.extend_from_slice(example.as_bytes());
+    }
+    bytes.resize(example_size * batch_size, 0);
+    let mut batch_dims = Vec::with_capacity(dims.len() + 1);
+    batch_dims.push(batch_size as i64);
+    batch_dims.extend_from_slice(&dims);
+    HostBuffer::from_bytes(bytes, ty, Some(batch_dims), None)
+}
+
+/// Copies `buffer` to the host and splits it along its leading dimension of
+/// size `batch_size`.
+async fn split_batch(buffer: &Buffer, batch_size: usize) -> Result<Vec<HostBuffer>> {
+    let ty = buffer.primitive_type()?;
+    let dims = buffer.dims()?;
+    if dims.first() != Some(&(batch_size as i64)) {
+        return Err(Error::InvalidArgument(format!(
+            "output with dimensions {dims:?} has no leading batch dimension of size {batch_size}"
+        )));
+    }
+    let layout = MemoryLayout::from_strides(utils::byte_strides(&dims, element_size(ty)?));
+    let host = buffer.to_host(Some(layout)).await?;
+    let bytes = host.as_bytes();
+    let example_size = bytes.len() / batch_size;
+    (0..batch_size)
+        .map(|i| {
+            let example = bytes[i * example_size..(i + 1) * example_size].to_vec();
+            HostBuffer::from_bytes(example, ty, Some(dims[1..].to_vec()), None)
+        })
+        .collect()
+}
+
+fn element_size(ty: PrimitiveType) -> Result<usize> {
+    Ok(ty.try_into_dtype()?.size())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_examples_pads_batch() {
+        let a = HostBuffer::from_data(vec![1.0f32, 2.0], None, None);
+        let b = HostBuffer::from_data(vec![3.0f32, 4.0], None, None);
+        let batch = concat_examples(0, [&a, &b], 4).unwrap();
+        assert_eq!(batch.dims(), &[4, 2]);
+        assert_eq!(
+            batch.read_f32().unwrap(),
+            &[1.0, 2.0, 3.0, 4.0, 0.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_concat_examples_shape_mismatch() {
+        let a = HostBuffer::from_data(vec![1.0f32, 2.0], None, None);
+        let b = HostBuffer::from_data(vec![3.0f32], None, None);
+        let err = concat_examples(1, [&a, &b], 2).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InputMismatch {
+                arg_index: Some(1),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_concat_examples_rejects_strided_layout() {
+        let a = HostBuffer::from_data(
+            vec![1i32, 2, 3, 4],
+            Some(vec![2, 2]),
+            Some(MemoryLayout::from_strides(vec![4, 8])),
+        );
+        let err = concat_examples(0, [&a], 1).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+    }
+}
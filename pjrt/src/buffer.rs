@@ -60,6 +60,7 @@
 //! let copied_buffer = device_buffer.copy_to_device(other_device)?;
 //! ```
 
+use std::cell::Cell;
 use std::ffi::c_void;
 use std::future::Future;
 use std::pin::Pin;
@@ -77,7 +78,9 @@ use pjrt_sys::{
 };
 
 use crate::event::Event;
-use crate::{Client, Device, ErrorCode, HostBuffer, Memory, MemoryLayout, PrimitiveType, Result};
+use crate::{
+    Client, Device, Error, ErrorCode, HostBuffer, Memory, MemoryLayout, PrimitiveType, Result,
+};
 
 /// A buffer holding data on a PJRT device.
 ///
@@ -90,6 +93,8 @@ use crate::{Client, Device, ErrorCode, HostBuffer, Memory, MemoryLayout, Primiti
 pub struct Buffer {
     client: Client,
     pub(crate) ptr: *mut PJRT_Buffer,
+    /// Set once the buffer was donated to an execution.
+    donated: Cell<bool>,
 }
 
 impl Drop for Buffer {
@@ -111,6 +116,7 @@ impl std::fmt::Debug for Buffer {
             )
             .field("is_on_cpu", &self.is_on_cpu().unwrap_or(false))
             .field("is_deleted", &self.is_deleted().unwrap_or(false))
+            .field("is_donated", &self.is_donated())
             .finish()
     }
 }
@@ -122,6 +128,7 @@ impl Buffer {
         Self {
             client: client.clone(),
             ptr,
+            donated: Cell::new(false),
         }
     }
 
@@ -129,6 +136,27 @@ impl Buffer {
         &self.client
     }
 
+    /// Returns whether this buffer was donated to an execution.
+    ///
+    /// A donated buffer's memory belongs to the execution's outputs. Its
+    /// shape can still be queried, but operations on its data fail with
+    /// `Error::BufferDonated`.
+    pub fn is_donated(&self) -> bool {
+        self.donated.get()
+    }
+
+    pub(crate) fn mark_donated(&self) {
+        self.donated.set(true);
+    }
+
+    /// Returns `Error::BufferDonated` if this buffer was donated.
+    pub(crate) fn check_not_donated(&self) -> Result<()> {
+        if self.is_donated() {
+            return Err(Error::BufferDonated);
+        }
+        Ok(())
+    }
+
     pub fn primitive_type(&self) -> Result<PrimitiveType> {
         let mut args = PJRT_Buffer_ElementType_Args::new();
         args.buffer = self.ptr;
@@ -175,6 +203,7 @@ impl Buffer {
     }
 
     pub fn on_device_size(&self) -> Result<usize> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_OnDeviceSizeInBytes_Args::new();
         args.buffer = self.ptr;
         args = self.client.api().PJRT_Buffer_OnDeviceSizeInBytes(args)?;
@@ -203,6 +232,7 @@ impl Buffer {
     }
 
     pub fn delete(self) -> Result<()> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_Delete_Args::new();
         args.buffer = self.ptr;
         self.client.api().PJRT_Buffer_Delete(args)?;
@@ -217,6 +247,7 @@ impl Buffer {
     }
 
     pub(crate) fn ready_event(&self) -> Result<Event> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_ReadyEvent_Args::new();
         args.buffer = self.ptr;
         args = self.client.api().PJRT_Buffer_ReadyEvent(args)?;
@@ -224,6 +255,7 @@ impl Buffer {
     }
 
     fn call_copy_to_device(&self, device: &Device) -> Result<PJRT_Buffer_CopyToDevice_Args> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_CopyToDevice_Args::new();
         args.buffer = self.ptr;
         args.dst_device = device.ptr;
@@ -249,6 +281,7 @@ impl Buffer {
     }
 
     fn call_copy_to_memory(&self, memory: &Memory) -> Result<PJRT_Buffer_CopyToMemory_Args> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_CopyToMemory_Args::new();
         args.buffer = self.ptr;
         args.dst_memory = memory.ptr;
@@ -277,6 +310,7 @@ impl Buffer {
         &self,
         host_layout: Option<&MemoryLayout>,
     ) -> Result<(PJRT_Buffer_ToHostBuffer_Args, Vec<u8>)> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_ToHostBuffer_Args::new();
         args.src = self.ptr;
         let mut layout_c = host_layout.map(PJRT_Buffer_MemoryLayout::from);
@@ -329,6 +363,7 @@ impl Buffer {
         offset: usize,
         transfer_size: usize,
     ) -> Result<()> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_CopyRawToHost_Args::new();
         args.buffer = self.ptr;
         args.dst = dst.as_mut_ptr() as *mut _;
@@ -347,6 +382,7 @@ impl Buffer {
         offset: usize,
         transfer_size: usize,
     ) -> Result<()> {
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_CopyRawToHost_Args::new();
         args.buffer = self.ptr;
         args.dst = dst.as_mut_ptr() as *mut _;
@@ -371,6 +407,7 @@ impl Buffer {
     ) -> Result<CopyRawToHostFuture> {
         use pjrt_sys::PJRT_Buffer_CopyRawToHostFuture_Args;
 
+        self.check_not_donated()?;
         let mut args = PJRT_Buffer_CopyRawToHostFuture_Args::new();
         args.buffer = self.ptr;
         args.offset = offset as i64;
@@ -396,6 +433,7 @@ impl Buffer {
     /// Returns the donated buffer along with a callback that must be invoked
     /// before the dependency is considered ready.
     pub fn donate_with_control_dependency(&self) -> Result<DonateWithControlDependency> {
+        self.check_not_donated()?;
         use pjrt_sys::PJRT_Buffer_DonateWithControlDependency_Args;
 
         let mut args = PJRT_Buffer_DonateWithControlDependency_Args::new();
@@ -672,6 +710,7 @@ impl Buffer {
     /// // external_ref is dropped here, releasing the reference
     /// ```
     pub fn external_ref(&self) -> Result<ExternalBufferRef<'_>> {
+        self.check_not_donated()?;
         unsafe { self.increase_external_ref_count()? };
         Ok(ExternalBufferRef { buffer: self })
     }
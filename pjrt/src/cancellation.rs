@@ -0,0 +1,115 @@
+//! Execution Cancellation
+//!
+//! This module provides `CancellationToken`, a cloneable handle used to
+//! cancel in-flight executions from another task or thread. Executions
+//! configured with `ExecuteOptions::cancellation_token` poison their launch
+//! on every participating device once the token is cancelled and fail with
+//! `Error::Cancelled`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use pjrt::CancellationToken;
+//!
+//! let token = CancellationToken::new();
+//! let handle = token.clone();
+//! assert!(!token.is_cancelled());
+//!
+//! handle.cancel();
+//! assert!(token.is_cancelled());
+//! ```
+
+use std::future::poll_fn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+/// A cloneable, thread-safe cancellation flag.
+///
+/// All clones share the same state; cancelling any of them cancels all.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token and wakes every task waiting in
+    /// [`CancellationToken::cancelled`].
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.lock_wakers());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled.
+    pub async fn cancelled(&self) {
+        poll_fn(|cx| {
+            if self.is_cancelled() {
+                return Poll::Ready(());
+            }
+            let mut wakers = self.lock_wakers();
+            // Re-check under the lock so a concurrent `cancel` is not missed.
+            if self.is_cancelled() {
+                return Poll::Ready(());
+            }
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn lock_wakers(&self) -> std::sync::MutexGuard<'_, Vec<Waker>> {
+        self.inner.wakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token_shared_between_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancellation_token_wakes_waiter() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        let token = CancellationToken::new();
+        let waiter = token.clone();
+        let task = runtime.spawn(async move { waiter.cancelled().await });
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        token.cancel();
+        runtime.block_on(task).unwrap();
+    }
+
+    #[test]
+    fn test_cancellation_token_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CancellationToken>();
+    }
+}
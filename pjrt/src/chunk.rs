@@ -1,4 +1,5 @@
 use std::os::raw::c_void;
+use std::slice;
 
 use pjrt_sys::PJRT_Chunk;
 
@@ -16,6 +17,33 @@ impl Chunk {
     pub fn new(data: Vec<u8>) -> Self {
         Self { data }
     }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Copies a chunk handed over by the runtime and releases it.
+    ///
+    /// # Safety
+    ///
+    /// `chunk` must point to a valid `PJRT_Chunk` whose ownership is passed
+    /// to the caller; it must not be used afterwards.
+    pub(crate) unsafe fn from_raw(chunk: *mut PJRT_Chunk) -> Self {
+        let chunk = &*chunk;
+        let data = if chunk.data.is_null() || chunk.size == 0 {
+            vec![]
+        } else {
+            slice::from_raw_parts(chunk.data as *const u8, chunk.size).to_vec()
+        };
+        if let Some(deleter) = chunk.deleter {
+            deleter(chunk.data, chunk.deleter_arg);
+        }
+        Self { data }
+    }
 }
 
 impl From<Chunk> for PJRT_Chunk {
@@ -98,6 +126,14 @@ mod tests {
         assert_eq!(set.len(), 2);
     }
 
+    #[test]
+    fn test_chunk_raw_round_trip() {
+        let mut raw: PJRT_Chunk = Chunk::new(vec![1, 2, 3]).into();
+        let chunk = unsafe { Chunk::from_raw(&mut raw) };
+        assert_eq!(chunk.data(), &[1, 2, 3]);
+        assert_eq!(chunk.into_vec(), vec![1, 2, 3]);
+    }
+
     #[test]
     fn test_chunk_large_data() {
         let data: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
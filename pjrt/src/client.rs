@@ -58,9 +58,11 @@
 //! ```
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::ffi::c_void;
 use std::rc::Rc;
 use std::slice;
+use std::sync::Arc;
 
 use bon::bon;
 use pjrt_sys::{
@@ -77,6 +79,7 @@ use pjrt_sys::{
     PJRT_ShapeSpec,
 };
 
+use crate::kv_store::CallbackStore;
 use crate::{
     utils, Api, AsyncHostToDeviceTransferManager, Buffer, BufferShape, CallbackExtension,
     CompileOptions, CompileToLoadedExecutable, Device, DeviceAssignment, ErrorCode, Extension,
@@ -87,6 +90,9 @@ use crate::{
 struct ClientRaw {
     api: Api,
     ptr: *mut PJRT_Client,
+    /// The store behind the key-value callbacks, dropped after the client is
+    /// destroyed.
+    _kv_store: Option<CallbackStore>,
 }
 
 impl Drop for ClientRaw {
@@ -145,11 +151,20 @@ impl std::fmt::Debug for Client {
 #[bon]
 impl Client {
     pub(crate) fn wrap(api: &Api, ptr: *mut PJRT_Client) -> Self {
+        Self::wrap_with_store(api, ptr, None)
+    }
+
+    pub(crate) fn wrap_with_store(
+        api: &Api,
+        ptr: *mut PJRT_Client,
+        kv_store: Option<CallbackStore>,
+    ) -> Self {
         assert!(!ptr.is_null());
         Self {
             raw: Rc::new(ClientRaw {
                 api: api.clone(),
                 ptr,
+                _kv_store: kv_store,
             }),
         }
     }
@@ -158,7 +173,7 @@ impl Client {
     pub fn builder(
         #[builder(start_fn)] api: &Api,
         #[builder(default = bon::vec![], into)] options: Vec<NamedValue>,
-        kv_store: Option<&dyn KeyValueStore>,
+        kv_store: Option<Arc<dyn KeyValueStore + Send + Sync>>,
     ) -> Result<Self> {
         api.create_client(options, kv_store)
     }
@@ -238,6 +253,33 @@ impl Client {
             .collect())
     }
 
+    /// Returns the devices attached to this process, i.e. the global devices
+    /// whose process index is [`Client::process_index`].
+    ///
+    /// Unlike [`Client::addressable_devices`], the devices are listed in the
+    /// order of [`Client::devices`].
+    pub fn local_devices(&self) -> Result<Vec<Device>> {
+        let process_index = self.process_index()?;
+        let mut local = vec![];
+        for device in self.devices()? {
+            if device.description()?.process_index()? == process_index {
+                local.push(device);
+            }
+        }
+        Ok(local)
+    }
+
+    /// Returns the global devices grouped by the index of the process they
+    /// are attached to.
+    pub fn devices_by_process(&self) -> Result<BTreeMap<i32, Vec<Device>>> {
+        let mut grouped: BTreeMap<i32, Vec<Device>> = BTreeMap::new();
+        for device in self.devices()? {
+            let process_index = device.description()?.process_index()?;
+            grouped.entry(process_index).or_default().push(device);
+        }
+        Ok(grouped)
+    }
+
     pub fn lookup_device(&self, global_device_id: GlobalDeviceId) -> Result<Device> {
         let mut args = PJRT_Client_LookupDevice_Args::new();
         args.client = self.ptr();
@@ -522,6 +564,7 @@ impl Client {
 
 impl CompileToLoadedExecutable<Program> for Client {
     fn compile(&self, program: &Program, options: &CompileOptions) -> Result<LoadedExecutable> {
+        self.api().check_stablehlo_compatibility(program)?;
         let options_encoded = options.encode();
         let mut args = PJRT_Client_Compile_Args::new();
         args.client = self.ptr();
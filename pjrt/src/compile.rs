@@ -66,11 +66,14 @@
 //! ```
 
 use pjrt_sys::protos::xla::{
-    CompilationEnvironmentsProto, CompileOptionsProto, ExecutableBuildOptionsProto,
+    CompilationEnvironmentsProto, CompileOptionsProto, DeviceAssignmentProto,
+    ExecutableBuildOptionsProto,
 };
 use prost::Message;
 
-use crate::{Client, Executable, LoadedExecutable, Result, TopologyDescription};
+use crate::{
+    Client, DeviceAssignment, Executable, LoadedExecutable, Mesh, Result, TopologyDescription,
+};
 
 /// Trait for types that can compile programs to executables.
 ///
@@ -137,6 +140,13 @@ impl CompileOptions {
     pub fn encode(&self) -> Vec<u8> {
         self.proto.encode_to_vec()
     }
+
+    /// Decodes options previously produced by [`CompileOptions::encode`].
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(Self {
+            proto: CompileOptionsProto::decode(bytes)?,
+        })
+    }
 }
 
 /// Device-specific options for building executables.
@@ -191,6 +201,17 @@ impl ExecutableBuildOptions {
         self
     }
 
+    /// The device assignment of the computation, if known at compile time.
+    pub fn device_assignment(
+        mut self,
+        device_assignment: impl Into<Option<DeviceAssignment>>,
+    ) -> Self {
+        self.proto.device_assignment = device_assignment
+            .into()
+            .map(|v| DeviceAssignmentProto::from(&v));
+        self
+    }
+
     /// Indicates whether to use SPMD (true) or MPMD (false) partitioning when
     /// num_partitions > 1 and XLA is requested to partition the input program.
     pub fn use_spmd_partitioning(mut self, use_spmd_partitioning: bool) -> Self {
@@ -291,6 +312,20 @@ impl ExecutableBuildOptions {
         self
     }
 
+    /// Partitions the computation over every device of `mesh` with SPMD.
+    ///
+    /// Sets one replica, one partition per mesh device, and the auto
+    /// sharding mesh shape and ids. Compile with the device assignment
+    /// returned by `mesh.to_device_assignment(&[])` so that partitions map
+    /// to devices in mesh order.
+    pub fn mesh(self, mesh: &Mesh) -> Self {
+        self.num_replicas(1)
+            .num_partitions(mesh.size() as i64)
+            .use_spmd_partitioning(true)
+            .auto_spmd_partitioning_mesh_shape(mesh.mesh_shape())
+            .auto_spmd_partitioning_mesh_ids(mesh.mesh_ids())
+    }
+
     /// Use Shardy, a new partitioner, to replace the existing
     /// ShardingPropagation and SpmdPartitioner.
     pub fn use_shardy_partitioner(mut self, use_shardy_partitioner: bool) -> Self {
@@ -409,6 +444,16 @@ mod tests {
         // Should be valid protobuf
     }
 
+    #[test]
+    fn test_compile_options_decode() {
+        let options = CompileOptions::new()
+            .executable_build_options(ExecutableBuildOptions::new().num_partitions(4));
+        let decoded = CompileOptions::decode(&options.encode()).unwrap();
+        assert_eq!(decoded.proto(), options.proto());
+
+        assert!(CompileOptions::decode(&[0xff]).is_err());
+    }
+
     #[test]
     fn test_compile_options_executable_build_options() {
         let build_options = ExecutableBuildOptions::new()
@@ -513,6 +558,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_executable_build_options_mesh() {
+        let mesh = Mesh::new(&[("data", 2), ("model", 2)], vec![3, 2, 1, 0]).unwrap();
+        let opts = ExecutableBuildOptions::new().num_replicas(4).mesh(&mesh);
+        assert_eq!(opts.proto().num_replicas, 1);
+        assert_eq!(opts.proto().num_partitions, 4);
+        assert!(opts.proto().use_spmd_partitioning);
+        assert_eq!(opts.proto().auto_spmd_partitioning_mesh_shape, vec![2, 2]);
+        assert_eq!(
+            opts.proto().auto_spmd_partitioning_mesh_ids,
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_executable_build_options_device_assignment() {
+        let assignment = DeviceAssignment::new(1, 2, vec![1, 0]).unwrap();
+        let opts = ExecutableBuildOptions::new().device_assignment(assignment.clone());
+        let proto = opts.proto().device_assignment.as_ref().unwrap();
+        assert_eq!(DeviceAssignment::try_from(proto).unwrap(), assignment);
+
+        let opts = opts.device_assignment(None);
+        assert!(opts.proto().device_assignment.is_none());
+    }
+
     #[test]
     fn test_executable_build_options_auto_spmd_mesh() {
         let opts = ExecutableBuildOptions::new()
@@ -0,0 +1,1360 @@
+//! Distributed Coordination
+//!
+//! This module provides a small coordination service for multi-process PJRT
+//! jobs, similar to the one JAX starts in `jax.distributed.initialize`:
+//!
+//! - `CoordinationService`: a TCP server, usually run by process 0, holding a
+//!   shared key-value store and the state of every task.
+//! - `CoordinationClient`: a connection to the service from one task. It
+//!   implements `KeyValueStore`, so it can be passed to
+//!   `Api::create_client`, and provides barriers, error reporting and
+//!   process state for `Client::update_global_process_info`.
+//!
+//! Clients send heartbeats from a background thread. A task that misses
+//! heartbeats for the service's heartbeat timeout, or reports an error, is
+//! marked `ProcessState::Error`; barriers then fail for every task instead of
+//! waiting for it.
+//!
+//! The protocol is a simple length-prefixed request/response exchange over
+//! TCP, unauthenticated and unencrypted; run it on a trusted network.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! // On process 0:
+//! let service = CoordinationService::builder(num_processes)
+//!     .address("0.0.0.0:8476")
+//!     .start()?;
+//!
+//! // On every process:
+//! let coordinator = Arc::new(CoordinationClient::builder("host0:8476", process_id).connect()?);
+//! let client = Client::builder(&api)
+//!     .kv_store(coordinator.clone())
+//!     .build()?;
+//! coordinator.barrier("client_created", Duration::from_secs(60))?;
+//! coordinator.update_process_info(&client)?;
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bon::bon;
+use pjrt_sys::PJRT_Error_Code;
+
+use crate::kv_store::{insert_entry, list_entries, KeyValueEntry};
+use crate::{Client, Error, ErrorCode, KeyValueStore, ProcessInfo, ProcessState, Result};
+
+/// The default time after which a task without heartbeats is marked failed.
+const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default interval between heartbeats sent by a client.
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The default time a client keeps retrying to reach the service.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound on the size of a single message.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// Memory reserved up front for a received message; larger bodies grow as
+/// their bytes arrive, so a bogus length cannot force a large allocation.
+const READ_CHUNK_SIZE: usize = 64 << 10;
+
+// ---------------------------------------------------------------------------
+// Wire format
+// ---------------------------------------------------------------------------
+//
+// Every message is framed as
+//
+//   [ u32 body length ][ u8 op ][ u16 field count ]([ u32 length ][ bytes ])*
+//
+// with integers in little endian. Requests carry one of the `OP_*` codes and
+// responses either `RESPONSE_OK` with result fields or `RESPONSE_ERROR` with
+// an error code and message.
+
+const OP_REGISTER: u8 = 1;
+const OP_HEARTBEAT: u8 = 2;
+const OP_DISCONNECT: u8 = 3;
+const OP_PUT: u8 = 4;
+const OP_GET: u8 = 5;
+const OP_TRY_GET: u8 = 6;
+const OP_BARRIER: u8 = 7;
+const OP_REPORT_ERROR: u8 = 8;
+const OP_PROCESS_INFOS: u8 = 9;
+const OP_DELETE: u8 = 10;
+const OP_LIST_PREFIX: u8 = 11;
+const OP_PUT_WITH_TTL: u8 = 12;
+
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_ERROR: u8 = 255;
+
+/// Number of fields encoding one `ProcessInfo`.
+const PROCESS_INFO_FIELDS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Message {
+    op: u8,
+    fields: Vec<Vec<u8>>,
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+impl Message {
+    fn new(op: u8) -> Self {
+        Self { op, fields: vec![] }
+    }
+
+    fn bytes(mut self, field: &[u8]) -> Self {
+        self.fields.push(field.to_vec());
+        self
+    }
+
+    fn str(self, field: &str) -> Self {
+        self.bytes(field.as_bytes())
+    }
+
+    fn i32(self, field: i32) -> Self {
+        self.bytes(&field.to_le_bytes())
+    }
+
+    fn u64(self, field: u64) -> Self {
+        self.bytes(&field.to_le_bytes())
+    }
+
+    fn get_bytes(&self, index: usize) -> io::Result<&[u8]> {
+        self.fields
+            .get(index)
+            .map(Vec::as_slice)
+            .ok_or_else(|| invalid_data(format!("missing field {index}")))
+    }
+
+    fn get_str(&self, index: usize) -> io::Result<&str> {
+        std::str::from_utf8(self.get_bytes(index)?)
+            .map_err(|_| invalid_data(format!("field {index} is not UTF-8")))
+    }
+
+    fn get_i32(&self, index: usize) -> io::Result<i32> {
+        let bytes = self.get_bytes(index)?;
+        bytes
+            .try_into()
+            .map(i32::from_le_bytes)
+            .map_err(|_| invalid_data(format!("field {index} is not an i32")))
+    }
+
+    fn get_u64(&self, index: usize) -> io::Result<u64> {
+        let bytes = self.get_bytes(index)?;
+        bytes
+            .try_into()
+            .map(u64::from_le_bytes)
+            .map_err(|_| invalid_data(format!("field {index} is not a u64")))
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut body =
+            Vec::with_capacity(3 + self.fields.iter().map(|f| 4 + f.len()).sum::<usize>());
+        body.push(self.op);
+        let count = u16::try_from(self.fields.len())
+            .map_err(|_| invalid_data("too many fields in message"))?;
+        body.extend_from_slice(&count.to_le_bytes());
+        for field in &self.fields {
+            let len =
+                u32::try_from(field.len()).map_err(|_| invalid_data("message field too large"))?;
+            body.extend_from_slice(&len.to_le_bytes());
+            body.extend_from_slice(field);
+        }
+        if body.len() > MAX_MESSAGE_SIZE {
+            return Err(invalid_data("message too large"));
+        }
+        writer.write_all(&(body.len() as u32).to_le_bytes())?;
+        writer.write_all(&body)?;
+        writer.flush()
+    }
+
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if !(3..=MAX_MESSAGE_SIZE).contains(&len) {
+            return Err(invalid_data(format!("invalid message length {len}")));
+        }
+        let mut body = Vec::with_capacity(len.min(READ_CHUNK_SIZE));
+        reader.take(len as u64).read_to_end(&mut body)?;
+        if body.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let op = body[0];
+        let count = u16::from_le_bytes([body[1], body[2]]) as usize;
+        let mut rest = &body[3..];
+        let mut fields = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (len, tail) = rest
+                .split_first_chunk::<4>()
+                .ok_or_else(|| invalid_data("truncated message"))?;
+            let len = u32::from_le_bytes(*len) as usize;
+            if tail.len() < len {
+                return Err(invalid_data("truncated message"));
+            }
+            let (field, tail) = tail.split_at(len);
+            fields.push(field.to_vec());
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(invalid_data("trailing bytes in message"));
+        }
+        Ok(Self { op, fields })
+    }
+}
+
+/// An error reported over the wire.
+type Status = (ErrorCode, String);
+
+fn status(code: ErrorCode, msg: impl Into<String>) -> Status {
+    (code, msg.into())
+}
+
+fn error_code_from_i32(code: i32) -> ErrorCode {
+    ErrorCode::try_from(code as PJRT_Error_Code).unwrap_or(ErrorCode::Unknown)
+}
+
+// ---------------------------------------------------------------------------
+// Service
+// ---------------------------------------------------------------------------
+
+/// A coordination service for the tasks of a distributed job.
+///
+/// The service runs on background threads until it is shut down or dropped.
+///
+/// # Thread Safety
+///
+/// `CoordinationService` is `Send + Sync`.
+pub struct CoordinationService {
+    address: SocketAddr,
+    state: Arc<ServiceState>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+struct ServiceState {
+    num_tasks: usize,
+    heartbeat_timeout: Duration,
+    inner: Mutex<ServiceInner>,
+    /// Notified whenever keys, barriers or task states change.
+    changed: Condvar,
+    shutdown: AtomicBool,
+}
+
+struct ServiceInner {
+    kv: HashMap<String, KeyValueEntry>,
+    tasks: Vec<TaskStatus>,
+    barriers: HashMap<String, HashSet<i32>>,
+    /// Open connections, shut down when the service stops.
+    connections: HashMap<u64, TcpStream>,
+}
+
+#[derive(Debug, Clone)]
+struct TaskStatus {
+    incarnation_id: u64,
+    state: ProcessState,
+    last_heartbeat: Instant,
+    error: Option<Status>,
+}
+
+impl TaskStatus {
+    fn to_process_info(&self, task_id: i32) -> ProcessInfo {
+        let info = ProcessInfo::new(task_id, self.state).with_incarnation(self.incarnation_id);
+        match &self.error {
+            Some((code, msg)) => info.with_error(*code as i32, msg.clone()),
+            None => info,
+        }
+    }
+}
+
+#[bon]
+impl CoordinationService {
+    /// Starts a service for `num_tasks` tasks.
+    ///
+    /// The service listens on `address`, by default an ephemeral port on
+    /// localhost; use [`CoordinationService::address`] to find the port.
+    #[builder(finish_fn = start)]
+    pub fn builder(
+        #[builder(start_fn)] num_tasks: usize,
+        #[builder(default = "127.0.0.1:0".to_string(), into)] address: String,
+        #[builder(default = DEFAULT_HEARTBEAT_TIMEOUT)] heartbeat_timeout: Duration,
+    ) -> Result<Self> {
+        if num_tasks == 0 || i32::try_from(num_tasks).is_err() {
+            return Err(Error::InvalidArgument(format!(
+                "invalid number of tasks: {num_tasks}"
+            )));
+        }
+        let listener = TcpListener::bind(address.as_str())?;
+        let address = listener.local_addr()?;
+        let now = Instant::now();
+        let state = Arc::new(ServiceState {
+            num_tasks,
+            heartbeat_timeout,
+            inner: Mutex::new(ServiceInner {
+                kv: HashMap::new(),
+                tasks: vec![
+                    TaskStatus {
+                        incarnation_id: 0,
+                        state: ProcessState::Uninitialized,
+                        last_heartbeat: now,
+                        error: None,
+                    };
+                    num_tasks
+                ],
+                barriers: HashMap::new(),
+                connections: HashMap::new(),
+            }),
+            changed: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let accept = {
+            let state = state.clone();
+            thread::Builder::new()
+                .name("pjrt-coordination-accept".to_string())
+                .spawn(move || state.accept(listener))?
+        };
+        let detector = {
+            let state = state.clone();
+            thread::Builder::new()
+                .name("pjrt-coordination-failure-detector".to_string())
+                .spawn(move || state.detect_failures())?
+        };
+        Ok(Self {
+            address,
+            state,
+            threads: vec![accept, detector],
+        })
+    }
+
+    /// Returns the address the service listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn num_tasks(&self) -> usize {
+        self.state.num_tasks
+    }
+
+    pub fn heartbeat_timeout(&self) -> Duration {
+        self.state.heartbeat_timeout
+    }
+
+    /// Returns the current state of every task, indexed by task id.
+    pub fn process_infos(&self) -> Vec<ProcessInfo> {
+        self.state.process_infos()
+    }
+
+    /// Stops the service and closes every connection.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if self.state.shutdown.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for connection in self.state.lock().connections.values() {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+        self.state.changed.notify_all();
+        // Wake the accept loop.
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect(address);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for CoordinationService {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl std::fmt::Debug for CoordinationService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoordinationService")
+            .field("address", &self.address)
+            .field("num_tasks", &self.state.num_tasks)
+            .field("heartbeat_timeout", &self.state.heartbeat_timeout)
+            .finish()
+    }
+}
+
+impl ServiceState {
+    fn lock(&self) -> MutexGuard<'_, ServiceInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn process_infos(&self) -> Vec<ProcessInfo> {
+        self.lock()
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(task_id, status)| status.to_process_info(task_id as i32))
+            .collect()
+    }
+
+    fn accept(self: Arc<Self>, listener: TcpListener) {
+        static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+        for stream in listener.incoming() {
+            if self.is_shutdown() {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let _ = stream.set_nodelay(true);
+            let Ok(clone) = stream.try_clone() else {
+                continue;
+            };
+            let id = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+            self.lock().connections.insert(id, clone);
+            let state = self.clone();
+            let spawned = thread::Builder::new()
+                .name("pjrt-coordination-connection".to_string())
+                .spawn(move || {
+                    state.serve(stream);
+                    state.lock().connections.remove(&id);
+                });
+            if spawned.is_err() {
+                self.lock().connections.remove(&id);
+            }
+        }
+    }
+
+    fn serve(&self, mut stream: TcpStream) {
+        while !self.is_shutdown() {
+            let Ok(request) = Message::read_from(&mut stream) else {
+                return;
+            };
+            let response = match self.handle(&request) {
+                Ok(fields) => Message {
+                    op: RESPONSE_OK,
+                    fields,
+                },
+                Err((code, msg)) => Message::new(RESPONSE_ERROR).i32(code as i32).str(&msg),
+            };
+            if response.write_to(&mut stream).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Marks tasks that stopped sending heartbeats as failed.
+    fn detect_failures(&self) {
+        let interval = (self.heartbeat_timeout / 4).max(Duration::from_millis(1));
+        let mut inner = self.lock();
+        while !self.is_shutdown() {
+            let mut changed = false;
+            for (task_id, status) in inner.tasks.iter_mut().enumerate() {
+                if status.state == ProcessState::Connected
+                    && status.last_heartbeat.elapsed() > self.heartbeat_timeout
+                {
+                    status.state = ProcessState::Error;
+                    status.error = Some(heartbeat_timeout_status(task_id, self.heartbeat_timeout));
+                    changed = true;
+                }
+            }
+            if changed {
+                self.changed.notify_all();
+            }
+            inner = self
+                .changed
+                .wait_timeout(inner, interval)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn handle(&self, request: &Message) -> std::result::Result<Vec<Vec<u8>>, Status> {
+        let bad_request = |err: io::Error| status(ErrorCode::InvalidArgument, err.to_string());
+        match request.op {
+            OP_REGISTER => {
+                let task_id = request.get_i32(0).map_err(bad_request)?;
+                let incarnation_id = request.get_u64(1).map_err(bad_request)?;
+                let mut inner = self.lock();
+                let task = self.task(&mut inner, task_id)?;
+                *task = TaskStatus {
+                    incarnation_id,
+                    state: ProcessState::Connected,
+                    last_heartbeat: Instant::now(),
+                    error: None,
+                };
+                self.changed.notify_all();
+                Ok(vec![])
+            }
+            OP_HEARTBEAT => {
+                let task_id = request.get_i32(0).map_err(bad_request)?;
+                let incarnation_id = request.get_u64(1).map_err(bad_request)?;
+                let mut inner = self.lock();
+                let task = self.task(&mut inner, task_id)?;
+                if task.incarnation_id != incarnation_id {
+                    return Err(status(
+                        ErrorCode::Aborted,
+                        format!("task {task_id} was restarted with a new incarnation"),
+                    ));
+                }
+                match task.state {
+                    ProcessState::Connected => {
+                        task.last_heartbeat = Instant::now();
+                        Ok(vec![])
+                    }
+                    ProcessState::Error => Err(task.error.clone().unwrap_or_else(|| {
+                        status(ErrorCode::Aborted, format!("task {task_id} has failed"))
+                    })),
+                    _ => Err(status(
+                        ErrorCode::FailedPrecondition,
+                        format!("task {task_id} is not connected"),
+                    )),
+                }
+            }
+            OP_DISCONNECT => {
+                let task_id = request.get_i32(0).map_err(bad_request)?;
+                let mut inner = self.lock();
+                let task = self.task(&mut inner, task_id)?;
+                if task.state == ProcessState::Connected {
+                    task.state = ProcessState::Disconnected;
+                }
+                self.changed.notify_all();
+                Ok(vec![])
+            }
+            OP_REPORT_ERROR => {
+                let task_id = request.get_i32(0).map_err(bad_request)?;
+                let code = error_code_from_i32(request.get_i32(1).map_err(bad_request)?);
+                let msg = request.get_str(2).map_err(bad_request)?;
+                let mut inner = self.lock();
+                let task = self.task(&mut inner, task_id)?;
+                task.state = ProcessState::Error;
+                task.error = Some(status(code, msg));
+                self.changed.notify_all();
+                Ok(vec![])
+            }
+            OP_PUT => {
+                let key = request.get_str(0).map_err(bad_request)?;
+                let value = request.get_bytes(1).map_err(bad_request)?;
+                insert_entry(&mut self.lock().kv, key, KeyValueEntry::new(value, None));
+                self.changed.notify_all();
+                Ok(vec![])
+            }
+            OP_PUT_WITH_TTL => {
+                let key = request.get_str(0).map_err(bad_request)?;
+                let value = request.get_bytes(1).map_err(bad_request)?;
+                let ttl = Duration::from_millis(request.get_u64(2).map_err(bad_request)?);
+                insert_entry(
+                    &mut self.lock().kv,
+                    key,
+                    KeyValueEntry::new(value, Some(ttl)),
+                );
+                self.changed.notify_all();
+                Ok(vec![])
+            }
+            OP_GET => {
+                let key = request.get_str(0).map_err(bad_request)?;
+                let timeout_in_ms = request.get_i32(1).map_err(bad_request)?;
+                let deadline = deadline_after(timeout_in_ms);
+                let mut inner = self.lock();
+                loop {
+                    if let Some(value) = inner.kv.get(key).and_then(KeyValueEntry::value) {
+                        return Ok(vec![value.clone()]);
+                    }
+                    inner = self.wait_until(inner, deadline).ok_or_else(|| {
+                        status(
+                            ErrorCode::DeadlineExceeded,
+                            format!("timed out after {timeout_in_ms}ms waiting for key: {key}"),
+                        )
+                    })??;
+                }
+            }
+            OP_TRY_GET => {
+                let key = request.get_str(0).map_err(bad_request)?;
+                match self.lock().kv.get(key).and_then(KeyValueEntry::value) {
+                    Some(value) => Ok(vec![value.clone()]),
+                    None => Err(status(ErrorCode::NotFound, format!("key not found: {key}"))),
+                }
+            }
+            OP_DELETE => {
+                let key = request.get_str(0).map_err(bad_request)?;
+                let deleted = self
+                    .lock()
+                    .kv
+                    .remove(key)
+                    .is_some_and(|entry| entry.value().is_some());
+                Ok(vec![vec![deleted as u8]])
+            }
+            OP_LIST_PREFIX => {
+                let prefix = request.get_str(0).map_err(bad_request)?;
+                Ok(list_entries(&self.lock().kv, prefix)
+                    .into_iter()
+                    .flat_map(|(key, value)| [key.into_bytes(), value])
+                    .collect())
+            }
+            OP_BARRIER => {
+                let id = request.get_str(0).map_err(bad_request)?;
+                let task_id = request.get_i32(1).map_err(bad_request)?;
+                let timeout_in_ms = request.get_i32(2).map_err(bad_request)?;
+                let deadline = deadline_after(timeout_in_ms);
+                let mut inner = self.lock();
+                self.task(&mut inner, task_id)?;
+                inner
+                    .barriers
+                    .entry(id.to_string())
+                    .or_default()
+                    .insert(task_id);
+                self.changed.notify_all();
+                loop {
+                    let arrived = inner.barriers[id].len();
+                    if arrived == self.num_tasks {
+                        return Ok(vec![]);
+                    }
+                    if let Some((failed, task)) = inner
+                        .tasks
+                        .iter()
+                        .enumerate()
+                        .find(|(_, task)| task.state == ProcessState::Error)
+                    {
+                        let msg = task.error.as_ref().map_or("", |(_, msg)| msg.as_str());
+                        return Err(status(
+                            ErrorCode::Aborted,
+                            format!("barrier {id} aborted: task {failed} failed: {msg}"),
+                        ));
+                    }
+                    inner = self.wait_until(inner, deadline).ok_or_else(|| {
+                        status(
+                            ErrorCode::DeadlineExceeded,
+                            format!(
+                                "barrier {id} timed out with {arrived} of {} tasks",
+                                self.num_tasks
+                            ),
+                        )
+                    })??;
+                }
+            }
+            OP_PROCESS_INFOS => {
+                let mut fields = vec![];
+                for info in self.process_infos() {
+                    fields.push(info.task_id.to_le_bytes().to_vec());
+                    fields.push(info.incarnation_id.to_le_bytes().to_vec());
+                    fields.push((info.state as i32).to_le_bytes().to_vec());
+                    fields.push(info.error_code.unwrap_or(0).to_le_bytes().to_vec());
+                    fields.push(info.error_message.unwrap_or_default().into_bytes());
+                }
+                Ok(fields)
+            }
+            op => Err(status(
+                ErrorCode::Unimplemented,
+                format!("unknown request {op}"),
+            )),
+        }
+    }
+
+    fn task<'a>(
+        &self,
+        inner: &'a mut ServiceInner,
+        task_id: i32,
+    ) -> std::result::Result<&'a mut TaskStatus, Status> {
+        usize::try_from(task_id)
+            .ok()
+            .and_then(|index| inner.tasks.get_mut(index))
+            .ok_or_else(|| {
+                status(
+                    ErrorCode::InvalidArgument,
+                    format!("invalid task id {task_id} for {} tasks", self.num_tasks),
+                )
+            })
+    }
+
+    /// Waits for a change until `deadline`.
+    ///
+    /// Returns `None` once the deadline has passed, and an error if the
+    /// service is shutting down.
+    fn wait_until<'a>(
+        &self,
+        inner: MutexGuard<'a, ServiceInner>,
+        deadline: Instant,
+    ) -> Option<std::result::Result<MutexGuard<'a, ServiceInner>, Status>> {
+        if self.is_shutdown() {
+            return Some(Err(status(
+                ErrorCode::Unavailable,
+                "coordination service is shutting down",
+            )));
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+        let inner = self
+            .changed
+            .wait_timeout(inner, remaining)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+        Some(Ok(inner))
+    }
+}
+
+fn heartbeat_timeout_status(task_id: usize, timeout: Duration) -> Status {
+    status(
+        ErrorCode::Unavailable,
+        format!("task {task_id} sent no heartbeat for {timeout:?}"),
+    )
+}
+
+/// Returns the deadline of a wait of `timeout_in_ms`. Non-positive timeouts
+/// check once.
+fn deadline_after(timeout_in_ms: i32) -> Instant {
+    Instant::now() + Duration::from_millis(timeout_in_ms.max(0) as u64)
+}
+
+fn timeout_in_ms(timeout: Duration) -> i32 {
+    i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+}
+
+// ---------------------------------------------------------------------------
+// Client
+// ---------------------------------------------------------------------------
+
+/// A task's connection to a [`CoordinationService`].
+///
+/// Registers the task on connect, sends heartbeats from a background thread
+/// and disconnects when dropped. Requests from several threads run
+/// concurrently over separate connections.
+///
+/// # Thread Safety
+///
+/// `CoordinationClient` is `Send + Sync`.
+pub struct CoordinationClient {
+    address: SocketAddr,
+    task_id: i32,
+    incarnation_id: u64,
+    /// Connections not currently used by a request.
+    idle: Mutex<Vec<TcpStream>>,
+    heartbeat: Option<HeartbeatThread>,
+}
+
+struct HeartbeatThread {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    /// The error of the last failed heartbeat, cleared by a successful one.
+    error: Arc<Mutex<Option<Status>>>,
+    thread: JoinHandle<()>,
+}
+
+#[bon]
+impl CoordinationClient {
+    /// Connects task `task_id` to the service at `address` and registers it.
+    ///
+    /// Connection attempts are retried until `connect_timeout`, so tasks may
+    /// start before the service. The incarnation id identifies this run of
+    /// the task and defaults to a value derived from the clock and process
+    /// id.
+    #[builder(finish_fn = connect)]
+    pub fn builder(
+        #[builder(start_fn, into)] address: String,
+        #[builder(start_fn)] task_id: i32,
+        incarnation_id: Option<u64>,
+        #[builder(default = DEFAULT_HEARTBEAT_INTERVAL)] heartbeat_interval: Duration,
+        #[builder(default = DEFAULT_CONNECT_TIMEOUT)] connect_timeout: Duration,
+    ) -> Result<Self> {
+        let deadline = Instant::now() + connect_timeout;
+        let (address, stream) = loop {
+            match connect(&address) {
+                Ok(connected) => break connected,
+                Err(err) if Instant::now() >= deadline => return Err(err.into()),
+                Err(_) => thread::sleep(Duration::from_millis(100)),
+            }
+        };
+        let incarnation_id = incarnation_id.unwrap_or_else(default_incarnation_id);
+        let mut client = Self {
+            address,
+            task_id,
+            incarnation_id,
+            idle: Mutex::new(vec![stream]),
+            heartbeat: None,
+        };
+        client.request(Message::new(OP_REGISTER).i32(task_id).u64(incarnation_id))?;
+        client.heartbeat = Some(client.start_heartbeat(heartbeat_interval)?);
+        Ok(client)
+    }
+
+    pub fn task_id(&self) -> i32 {
+        self.task_id
+    }
+
+    pub fn incarnation_id(&self) -> u64 {
+        self.incarnation_id
+    }
+
+    /// Returns the address of the service.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the error of the last heartbeat, if it failed.
+    ///
+    /// Heartbeats fail when the service is unreachable or has marked this
+    /// task as failed.
+    pub fn heartbeat_error(&self) -> Option<Error> {
+        let heartbeat = self.heartbeat.as_ref()?;
+        let error = heartbeat.error.lock().unwrap_or_else(|e| e.into_inner());
+        error
+            .clone()
+            .map(|(code, msg)| Error::Coordination { code, msg })
+    }
+
+    /// Waits until every task has reached the barrier `id`.
+    ///
+    /// Fails with `ErrorCode::DeadlineExceeded` after `timeout`, and with
+    /// `ErrorCode::Aborted` if any task fails while waiting.
+    pub fn barrier(&self, id: &str, timeout: Duration) -> Result<()> {
+        self.request(
+            Message::new(OP_BARRIER)
+                .str(id)
+                .i32(self.task_id)
+                .i32(timeout_in_ms(timeout)),
+        )
+        .map(|_| ())
+    }
+
+    /// Marks this task as failed, aborting pending and future barriers.
+    pub fn report_error(&self, code: ErrorCode, message: &str) -> Result<()> {
+        self.request(
+            Message::new(OP_REPORT_ERROR)
+                .i32(self.task_id)
+                .i32(code as i32)
+                .str(message),
+        )
+        .map(|_| ())
+    }
+
+    /// Returns the state of every task, indexed by task id.
+    pub fn process_infos(&self) -> Result<Vec<ProcessInfo>> {
+        let response = self.request(Message::new(OP_PROCESS_INFOS))?;
+        (0..response.fields.len())
+            .step_by(PROCESS_INFO_FIELDS)
+            .map(|offset| decode_process_info(&response, offset))
+            .collect::<io::Result<_>>()
+            .map_err(Error::from)
+    }
+
+    /// Passes the state of every task to `client`.
+    ///
+    /// See [`Client::update_global_process_info`].
+    pub fn update_process_info(&self, client: &Client) -> Result<()> {
+        client.update_global_process_info(&self.process_infos()?)
+    }
+
+    /// Stops heartbeats and tells the service this task has disconnected.
+    pub fn disconnect(mut self) -> Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let Some(heartbeat) = self.heartbeat.take() else {
+            return Ok(());
+        };
+        {
+            let (stopped, wake) = &*heartbeat.stop;
+            *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+            wake.notify_all();
+        }
+        let _ = heartbeat.thread.join();
+        self.request(Message::new(OP_DISCONNECT).i32(self.task_id))
+            .map(|_| ())
+    }
+
+    fn start_heartbeat(&self, interval: Duration) -> Result<HeartbeatThread> {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let error = Arc::new(Mutex::new(None));
+        let request = Message::new(OP_HEARTBEAT)
+            .i32(self.task_id)
+            .u64(self.incarnation_id);
+        let address = self.address;
+        let thread = {
+            let stop = stop.clone();
+            let error = error.clone();
+            thread::Builder::new()
+                .name("pjrt-coordination-heartbeat".to_string())
+                .spawn(move || {
+                    // A dedicated connection, so heartbeats are not queued
+                    // behind blocking requests.
+                    let mut stream = None;
+                    let (stopped, wake) = &*stop;
+                    let mut guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    while !*guard {
+                        let result = send_heartbeat(&mut stream, address, &request);
+                        *error.lock().unwrap_or_else(|e| e.into_inner()) = result.err();
+                        guard = wake
+                            .wait_timeout(guard, interval)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0;
+                    }
+                })?
+        };
+        Ok(HeartbeatThread {
+            stop,
+            error,
+            thread,
+        })
+    }
+
+    /// Sends `request` on an idle connection and returns the response.
+    fn request(&self, request: Message) -> Result<Message> {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let mut stream = match idle {
+            Some(stream) => stream,
+            None => connect_to(self.address)?,
+        };
+        // Connections are only reused after a complete exchange.
+        request.write_to(&mut stream)?;
+        let response = Message::read_from(&mut stream)?;
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(stream);
+        match response.op {
+            RESPONSE_OK => Ok(response),
+            RESPONSE_ERROR => {
+                let (code, msg) = decode_status(&response)?;
+                Err(Error::Coordination { code, msg })
+            }
+            op => Err(invalid_data(format!("unexpected response {op}")).into()),
+        }
+    }
+}
+
+impl Drop for CoordinationClient {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl std::fmt::Debug for CoordinationClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoordinationClient")
+            .field("address", &self.address)
+            .field("task_id", &self.task_id)
+            .field("incarnation_id", &self.incarnation_id)
+            .finish()
+    }
+}
+
+impl KeyValueStore for CoordinationClient {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        let result = self.request(Message::new(OP_GET).str(key).i32(timeout_in_ms));
+        match result {
+            Ok(mut response) if response.fields.len() == 1 => Ok(response.fields.remove(0)),
+            Ok(_) => Err(invalid_data("malformed get response").into()),
+            Err(Error::Coordination {
+                code: ErrorCode::DeadlineExceeded,
+                ..
+            }) => Err(Error::KeyValueTimeout {
+                key: key.to_string(),
+                timeout_in_ms,
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.request(Message::new(OP_PUT).str(key).bytes(value))
+            .map(|_| ())
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.request(Message::new(OP_TRY_GET).str(key)) {
+            Ok(mut response) if response.fields.len() == 1 => Ok(Some(response.fields.remove(0))),
+            Ok(_) => Err(invalid_data("malformed try_get response").into()),
+            Err(Error::Coordination {
+                code: ErrorCode::NotFound,
+                ..
+            }) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let response = self.request(Message::new(OP_DELETE).str(key))?;
+        match response.get_bytes(0)? {
+            [deleted] => Ok(*deleted != 0),
+            _ => Err(invalid_data("malformed delete response").into()),
+        }
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let response = self.request(Message::new(OP_LIST_PREFIX).str(prefix))?;
+        if response.fields.len() % 2 != 0 {
+            return Err(invalid_data("malformed list_prefix response").into());
+        }
+        (0..response.fields.len())
+            .step_by(2)
+            .map(|i| {
+                Ok((
+                    response.get_str(i)?.to_string(),
+                    response.get_bytes(i + 1)?.to_vec(),
+                ))
+            })
+            .collect()
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let ttl_in_ms = ttl.as_millis().try_into().unwrap_or(u64::MAX);
+        self.request(
+            Message::new(OP_PUT_WITH_TTL)
+                .str(key)
+                .bytes(value)
+                .u64(ttl_in_ms),
+        )
+        .map(|_| ())
+    }
+}
+
+fn connect(address: &str) -> io::Result<(SocketAddr, TcpStream)> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match connect_to(address) {
+            Ok(stream) => return Ok((address, stream)),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address resolved to nothing")))
+}
+
+fn connect_to(address: SocketAddr) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+fn send_heartbeat(
+    stream: &mut Option<TcpStream>,
+    address: SocketAddr,
+    request: &Message,
+) -> std::result::Result<(), Status> {
+    let unavailable = |err: io::Error| status(ErrorCode::Unavailable, err.to_string());
+    let connection = match stream {
+        Some(connection) => connection,
+        None => stream.insert(connect_to(address).map_err(unavailable)?),
+    };
+    let response = request
+        .write_to(connection)
+        .and_then(|_| Message::read_from(connection));
+    match response {
+        Ok(response) if response.op == RESPONSE_OK => Ok(()),
+        Ok(response) => Err(decode_status(&response).map_err(unavailable)?),
+        Err(err) => {
+            *stream = None;
+            Err(unavailable(err))
+        }
+    }
+}
+
+fn decode_status(response: &Message) -> io::Result<Status> {
+    Ok((
+        error_code_from_i32(response.get_i32(0)?),
+        response.get_str(1)?.to_string(),
+    ))
+}
+
+fn decode_process_info(response: &Message, offset: usize) -> io::Result<ProcessInfo> {
+    let task_id = response.get_i32(offset)?;
+    let incarnation_id = response.get_u64(offset + 1)?;
+    let state = match response.get_i32(offset + 2)? {
+        1 => ProcessState::Uninitialized,
+        2 => ProcessState::Disconnected,
+        3 => ProcessState::Connected,
+        4 => ProcessState::Error,
+        _ => ProcessState::Unspecified,
+    };
+    let info = ProcessInfo::new(task_id, state).with_incarnation(incarnation_id);
+    match response.get_i32(offset + 3)? {
+        0 => Ok(info),
+        code => Ok(info.with_error(code, response.get_str(offset + 4)?)),
+    }
+}
+
+fn default_incarnation_id() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    nanos ^ ((std::process::id() as u64) << 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(num_tasks: usize) -> CoordinationService {
+        CoordinationService::builder(num_tasks)
+            .heartbeat_timeout(Duration::from_secs(30))
+            .start()
+            .unwrap()
+    }
+
+    fn client(service: &CoordinationService, task_id: i32) -> CoordinationClient {
+        CoordinationClient::builder(service.address().to_string(), task_id)
+            .connect()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let message = Message::new(OP_PUT)
+            .str("key")
+            .bytes(b"")
+            .i32(-7)
+            .u64(u64::MAX);
+        let mut bytes = vec![];
+        message.write_to(&mut bytes).unwrap();
+        let decoded = Message::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(decoded.get_str(0).unwrap(), "key");
+        assert_eq!(decoded.get_i32(2).unwrap(), -7);
+        assert_eq!(decoded.get_u64(3).unwrap(), u64::MAX);
+        assert!(decoded.get_i32(0).is_err());
+        assert!(decoded.get_bytes(4).is_err());
+    }
+
+    #[test]
+    fn test_message_rejects_malformed_input() {
+        let mut bytes = vec![];
+        Message::new(OP_PUT)
+            .str("key")
+            .write_to(&mut bytes)
+            .unwrap();
+        assert!(Message::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+
+        // A field length running past the end of the body.
+        let mut bytes = vec![];
+        Message::new(OP_PUT)
+            .str("key")
+            .write_to(&mut bytes)
+            .unwrap();
+        bytes[7] = 200;
+        assert!(Message::read_from(&mut bytes.as_slice()).is_err());
+
+        let too_large = (MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes();
+        assert!(Message::read_from(&mut too_large.as_slice()).is_err());
+
+        // A length larger than the bytes that follow is not trusted.
+        let mut truncated = (MAX_MESSAGE_SIZE as u32).to_le_bytes().to_vec();
+        truncated.extend_from_slice(&[OP_PUT, 0, 0]);
+        let err = Message::read_from(&mut truncated.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_invalid_task_count() {
+        assert!(CoordinationService::builder(0).start().is_err());
+    }
+
+    #[test]
+    fn test_key_value_store() {
+        let service = service(2);
+        let a = client(&service, 0);
+        let b = client(&service, 1);
+
+        assert_eq!(b.try_get("key").unwrap(), None);
+        a.put("key", b"\0value").unwrap();
+        assert_eq!(b.try_get("key").unwrap().unwrap(), b"\0value");
+        assert_eq!(b.get("key", 0).unwrap(), b"\0value");
+
+        let err = b.get("missing", 20).unwrap_err();
+        assert!(matches!(err, Error::KeyValueTimeout { .. }));
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                a.put("late", b"value").unwrap();
+            });
+            assert_eq!(b.get("late", 10_000).unwrap(), b"value");
+        });
+
+        a.put("list/2", b"2").unwrap();
+        a.put("list/1", b"").unwrap();
+        assert_eq!(
+            b.list_prefix("list/").unwrap(),
+            vec![
+                ("list/1".to_string(), vec![]),
+                ("list/2".to_string(), b"2".to_vec()),
+            ]
+        );
+        assert!(b.delete("list/1").unwrap());
+        assert!(!a.delete("list/1").unwrap());
+        assert_eq!(a.try_get("list/1").unwrap(), None);
+        assert_eq!(a.list_prefix("list/").unwrap().len(), 1);
+        assert_eq!(a.list_prefix("none").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_key_value_ttl() {
+        let service = service(2);
+        let a = client(&service, 0);
+        let b = client(&service, 1);
+
+        a.put_with_ttl("long", b"1", Duration::from_secs(600))
+            .unwrap();
+        a.put_with_ttl("short", b"2", Duration::from_millis(50))
+            .unwrap();
+        assert_eq!(b.try_get("short").unwrap().unwrap(), b"2");
+        assert_eq!(b.list_prefix("").unwrap().len(), 2);
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(b.try_get("short").unwrap(), None);
+        assert!(matches!(
+            b.get("short", 0),
+            Err(Error::KeyValueTimeout { .. })
+        ));
+        assert_eq!(
+            b.list_prefix("").unwrap(),
+            vec![("long".to_string(), b"1".to_vec())]
+        );
+        assert!(!b.delete("short").unwrap());
+
+        // A plain put replaces the value and its expiry.
+        a.put_with_ttl("short", b"3", Duration::from_millis(50))
+            .unwrap();
+        a.put("short", b"4").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(b.get("short", 0).unwrap(), b"4");
+    }
+
+    #[test]
+    fn test_barrier() {
+        let service = service(3);
+        let clients: Vec<_> = (0..3).map(|i| client(&service, i)).collect();
+        thread::scope(|s| {
+            for client in &clients {
+                s.spawn(|| client.barrier("start", Duration::from_secs(10)).unwrap());
+            }
+        });
+        // A completed barrier stays passed.
+        clients[0]
+            .barrier("start", Duration::from_millis(0))
+            .unwrap();
+
+        let err = clients[0]
+            .barrier("partial", Duration::from_millis(20))
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+    }
+
+    #[test]
+    fn test_barrier_aborted_by_failed_task() {
+        let service = service(2);
+        let a = client(&service, 0);
+        let b = client(&service, 1);
+        thread::scope(|s| {
+            let waiter = s.spawn(|| a.barrier("sync", Duration::from_secs(10)));
+            thread::sleep(Duration::from_millis(20));
+            b.report_error(ErrorCode::Internal, "out of memory")
+                .unwrap();
+            let err = waiter.join().unwrap().unwrap_err();
+            assert_eq!(err.code(), ErrorCode::Aborted);
+            assert!(err.to_string().contains("out of memory"));
+        });
+
+        let infos = a.process_infos().unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].state, ProcessState::Connected);
+        assert_eq!(infos[0].incarnation_id, a.incarnation_id());
+        assert_eq!(infos[1].state, ProcessState::Error);
+        assert_eq!(infos[1].error_code, Some(ErrorCode::Internal as i32));
+        assert_eq!(infos[1].error_message.as_deref(), Some("out of memory"));
+    }
+
+    #[test]
+    fn test_process_states() {
+        let service = service(3);
+        let a = client(&service, 0);
+        let b = client(&service, 1);
+        b.disconnect().unwrap();
+
+        let states: Vec<_> = a
+            .process_infos()
+            .unwrap()
+            .into_iter()
+            .map(|info| info.state)
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ProcessState::Connected,
+                ProcessState::Disconnected,
+                ProcessState::Uninitialized
+            ]
+        );
+        assert_eq!(service.process_infos()[1].state, ProcessState::Disconnected);
+    }
+
+    #[test]
+    fn test_heartbeat_timeout_marks_task_failed() {
+        let service = CoordinationService::builder(2)
+            .heartbeat_timeout(Duration::from_millis(100))
+            .start()
+            .unwrap();
+        let alive = CoordinationClient::builder(service.address().to_string(), 0)
+            .heartbeat_interval(Duration::from_millis(10))
+            .connect()
+            .unwrap();
+        // Heartbeats far less often than the timeout.
+        let silent = CoordinationClient::builder(service.address().to_string(), 1)
+            .heartbeat_interval(Duration::from_secs(60))
+            .connect()
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while service.process_infos()[1].state != ProcessState::Error {
+            assert!(Instant::now() < deadline, "task was not marked failed");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let infos = alive.process_infos().unwrap();
+        assert_eq!(infos[0].state, ProcessState::Connected);
+        assert_eq!(infos[1].error_code, Some(ErrorCode::Unavailable as i32));
+        assert!(alive.heartbeat_error().is_none());
+
+        let err = alive
+            .barrier("after_failure", Duration::from_secs(10))
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Aborted);
+        drop(silent);
+    }
+
+    #[test]
+    fn test_invalid_task_id() {
+        let service = service(1);
+        let err = CoordinationClient::builder(service.address().to_string(), 5)
+            .connect()
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CoordinationService>();
+        assert_send_sync::<CoordinationClient>();
+    }
+}
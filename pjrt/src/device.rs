@@ -204,6 +204,27 @@ impl Device {
             .PJRT_Device_PoisonExecution(args)
             .map(|_| ())
     }
+
+    /// Poisons the earliest unfinished execution with `launch_id` on this
+    /// device.
+    ///
+    /// The execution's output buffers become error buffers carrying the given
+    /// error code and message. Returns whether an execution was poisoned.
+    pub fn poison_launch(
+        &self,
+        launch_id: i32,
+        error_code: ErrorCode,
+        error_message: &str,
+    ) -> Result<bool> {
+        let mut args = PJRT_Device_PoisonExecution_Args::new();
+        args.device = self.ptr;
+        args.launch_id = launch_id;
+        args.error_code = error_code as pjrt_sys::PJRT_Error_Code;
+        args.error_message = error_message.as_ptr() as *const i8;
+        args.error_message_size = error_message.len();
+        let args = self.client.api().PJRT_Device_PoisonExecution(args)?;
+        Ok(args.poisoned)
+    }
 }
 
 /// An async tracking event for monitoring device operations.
@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::{Error, GlobalDeviceId, Result};
+use pjrt_sys::protos::xla::device_assignment_proto::ComputationDevice;
+use pjrt_sys::protos::xla::DeviceAssignmentProto;
+
+use crate::{Client, Device, Error, GlobalDeviceId, Result};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LogicalId {
@@ -79,6 +82,131 @@ impl DeviceAssignment {
         }
         map
     }
+
+    /// Returns the device ids, indexed by replica and then by partition.
+    pub fn device_ids(&self) -> &[Vec<GlobalDeviceId>] {
+        &self.assignments
+    }
+
+    /// Checks that the assignment refers to distinct devices of `client`.
+    pub fn validate(&self, client: &Client) -> Result<()> {
+        let known = client
+            .devices()?
+            .iter()
+            .map(|device| device.description()?.id())
+            .collect::<Result<HashSet<_>>>()?;
+        let mut seen = HashSet::new();
+        for id in self.assignments.iter().flatten() {
+            if !known.contains(id) {
+                return Err(Error::InvalidArgument(format!(
+                    "device assignment refers to unknown device {}",
+                    id
+                )));
+            }
+            if !seen.insert(*id) {
+                return Err(Error::InvalidArgument(format!(
+                    "device {} appears more than once in the device assignment",
+                    id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the devices of `client`, indexed by replica and then by
+    /// partition.
+    pub fn devices(&self, client: &Client) -> Result<Vec<Vec<Device>>> {
+        self.assignments
+            .iter()
+            .map(|replica| replica.iter().map(|id| client.lookup_device(*id)).collect())
+            .collect()
+    }
+}
+
+impl From<&DeviceAssignment> for DeviceAssignmentProto {
+    fn from(assignment: &DeviceAssignment) -> Self {
+        let computation_devices = (0..assignment.num_partitions)
+            .map(|partition| ComputationDevice {
+                replica_device_ids: assignment
+                    .assignments
+                    .iter()
+                    .map(|replica| replica[partition] as i64)
+                    .collect(),
+            })
+            .collect();
+        DeviceAssignmentProto {
+            replica_count: assignment.num_replicas as i32,
+            computation_count: assignment.num_partitions as i32,
+            computation_devices,
+        }
+    }
+}
+
+impl From<DeviceAssignment> for DeviceAssignmentProto {
+    fn from(assignment: DeviceAssignment) -> Self {
+        Self::from(&assignment)
+    }
+}
+
+impl TryFrom<&DeviceAssignmentProto> for DeviceAssignment {
+    type Error = Error;
+
+    fn try_from(proto: &DeviceAssignmentProto) -> Result<Self> {
+        let num_replicas = usize::try_from(proto.replica_count).map_err(|_| {
+            Error::InvalidArgument(format!("invalid replica count {}", proto.replica_count))
+        })?;
+        let num_partitions = usize::try_from(proto.computation_count).map_err(|_| {
+            Error::InvalidArgument(format!(
+                "invalid computation count {}",
+                proto.computation_count
+            ))
+        })?;
+        if num_replicas == 0 || num_partitions == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "device assignment needs at least one replica and one computation, got {}x{}",
+                num_replicas, num_partitions
+            )));
+        }
+        if proto.computation_devices.len() != num_partitions {
+            return Err(Error::InvalidArgument(format!(
+                "expected {} computation devices, got {}",
+                num_partitions,
+                proto.computation_devices.len()
+            )));
+        }
+        if let Some(computation) = proto
+            .computation_devices
+            .iter()
+            .position(|c| c.replica_device_ids.len() != num_replicas)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "computation {} has {} replica devices, expected {}",
+                computation,
+                proto.computation_devices[computation]
+                    .replica_device_ids
+                    .len(),
+                num_replicas
+            )));
+        }
+        let mut assignments = Vec::with_capacity(num_replicas * num_partitions);
+        for replica in 0..num_replicas {
+            for computation in &proto.computation_devices {
+                let id = computation.replica_device_ids[replica];
+                let id = GlobalDeviceId::try_from(id)
+                    .map_err(|_| Error::InvalidArgument(format!("invalid device id {}", id)))?;
+                assignments.push(id);
+            }
+        }
+        Self::new(num_replicas, num_partitions, assignments)
+    }
+}
+
+impl TryFrom<DeviceAssignmentProto> for DeviceAssignment {
+    type Error = Error;
+
+    fn try_from(proto: DeviceAssignmentProto) -> Result<Self> {
+        Self::try_from(&proto)
+    }
 }
 
 #[cfg(test)]
@@ -325,6 +453,71 @@ mod tests {
         assert!(debug.contains("2")); // num_partitions
     }
 
+    #[test]
+    fn test_device_assignment_device_ids() {
+        let da = DeviceAssignment::new(2, 2, vec![0, 1, 2, 3]).unwrap();
+        assert_eq!(da.device_ids(), &[vec![0, 1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_device_assignment_to_proto() {
+        // Replica 0: [device 0, device 1], replica 1: [device 2, device 3]
+        let da = DeviceAssignment::new(2, 2, vec![0, 1, 2, 3]).unwrap();
+        let proto = DeviceAssignmentProto::from(&da);
+
+        assert_eq!(proto.replica_count, 2);
+        assert_eq!(proto.computation_count, 2);
+        assert_eq!(proto.computation_devices[0].replica_device_ids, [0, 2]);
+        assert_eq!(proto.computation_devices[1].replica_device_ids, [1, 3]);
+    }
+
+    #[test]
+    fn test_device_assignment_proto_round_trip() {
+        let da = DeviceAssignment::new(3, 2, vec![5, 4, 3, 2, 1, 0]).unwrap();
+        let proto = DeviceAssignmentProto::from(&da);
+        assert_eq!(DeviceAssignment::try_from(proto).unwrap(), da);
+    }
+
+    #[test]
+    fn test_device_assignment_from_invalid_proto() {
+        let computation = |ids: Vec<i64>| ComputationDevice {
+            replica_device_ids: ids,
+        };
+        let proto =
+            |replica_count, computation_devices: Vec<ComputationDevice>| DeviceAssignmentProto {
+                replica_count,
+                computation_count: computation_devices.len() as i32,
+                computation_devices,
+            };
+
+        // Missing replica device
+        let result = DeviceAssignment::try_from(proto(
+            2,
+            vec![computation(vec![0, 1]), computation(vec![2])],
+        ));
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+
+        // Device id out of range
+        let result = DeviceAssignment::try_from(proto(1, vec![computation(vec![1 << 40])]));
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+
+        // Negative replica count
+        let result = DeviceAssignment::try_from(proto(-1, vec![]));
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+
+        // No replicas or no computations
+        let result = DeviceAssignment::try_from(proto(0, vec![computation(vec![])]));
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        let result = DeviceAssignment::try_from(proto(1, vec![]));
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+
+        // Computation count disagrees with the computation devices
+        let mut mismatch = proto(1, vec![computation(vec![0])]);
+        mismatch.computation_count = 2;
+        let result = DeviceAssignment::try_from(mismatch);
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+
     #[test]
     fn test_device_assignment_new_wrong_length() {
         // Should return Err because we expect 2*2=4 devices but only provide 3
@@ -1,3 +1,8 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
 use pjrt_sys::{
     PJRT_Chunk, PJRT_CopyToDeviceStream, PJRT_CopyToDeviceStream_AddChunk_Args,
     PJRT_CopyToDeviceStream_CurrentBytes_Args, PJRT_CopyToDeviceStream_Destroy_Args,
@@ -83,4 +88,204 @@ impl CopyToDeviceStream {
         args = self.api.PJRT_CopyToDeviceStream_CurrentBytes(args)?;
         Ok(args.current_bytes)
     }
+
+    /// Returns an async writer that feeds this stream granule by granule.
+    pub fn writer(&self) -> Result<CopyToDeviceStreamWriter<'_>> {
+        CopyToDeviceStreamWriter::new(self)
+    }
+}
+
+/// An [`AsyncWrite`](tokio::io::AsyncWrite) adapter for a
+/// [`CopyToDeviceStream`].
+///
+/// Written bytes are buffered until they fill whole granules of
+/// [`CopyToDeviceStream::granule_size`], which are then added as one chunk.
+/// Each chunk's transfer event is awaited before more data is accepted, so at
+/// most one chunk is in flight, and the stream's
+/// [`CopyToDeviceStream::current_bytes`] is then re-read, so chunks added to
+/// the stream by other means are accounted for. Shutting the writer down pads the final
+/// partial granule with zeros and fails unless
+/// [`CopyToDeviceStream::total_bytes`] have been sent.
+///
+/// Writes beyond `total_bytes` fail with `ErrorKind::WriteZero`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use tokio::io::AsyncWriteExt;
+///
+/// let mut writer = stream.writer()?;
+/// writer.write_all(&payload).await?;
+/// writer.shutdown().await?;
+/// ```
+pub struct CopyToDeviceStreamWriter<'a> {
+    stream: &'a CopyToDeviceStream,
+    granule: usize,
+    total: usize,
+    /// Bytes handed to the stream, including those added before the writer
+    /// was created or outside of it; refreshed when a chunk completes.
+    sent: usize,
+    /// Accepted bytes that do not fill a granule yet.
+    pending: Vec<u8>,
+    in_flight: Option<Event>,
+}
+
+impl<'a> CopyToDeviceStreamWriter<'a> {
+    fn new(stream: &'a CopyToDeviceStream) -> Result<Self> {
+        Ok(Self {
+            stream,
+            granule: stream.granule_size()?.max(1) as usize,
+            total: stream.total_bytes()?.max(0) as usize,
+            sent: stream.current_bytes()?.max(0) as usize,
+            pending: vec![],
+            in_flight: None,
+        })
+    }
+
+    pub fn stream(&self) -> &'a CopyToDeviceStream {
+        self.stream
+    }
+
+    /// Returns the number of bytes handed to the stream so far.
+    pub fn bytes_sent(&self) -> usize {
+        self.sent
+    }
+
+    /// Returns the number of bytes the stream expects in total.
+    pub fn total_bytes(&self) -> usize {
+        self.total
+    }
+
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(event) = self.in_flight.as_mut() {
+            let result = ready!(Pin::new(event).poll(cx));
+            self.in_flight = None;
+            result.map_err(io::Error::other)?;
+            let current = self.stream.current_bytes().map_err(io::Error::other)?;
+            self.sent = current.max(0) as usize;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, last: bool) -> io::Result<()> {
+        let room = self.total.saturating_sub(self.sent);
+        let Some(chunk) = take_chunk(&mut self.pending, self.granule, room, last) else {
+            return Ok(());
+        };
+        self.sent += chunk.len();
+        let args = self
+            .stream
+            .call_add_chunk(Chunk::new(chunk))
+            .map_err(io::Error::other)?;
+        self.in_flight = Some(Event::wrap(&self.stream.api, args.transfer_complete));
+        Ok(())
+    }
+}
+
+impl tokio::io::AsyncWrite for CopyToDeviceStreamWriter<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_in_flight(cx))?;
+        let room = this.total.saturating_sub(this.sent + this.pending.len());
+        if room == 0 && !buf.is_empty() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                format!("stream already has all {} bytes", this.total),
+            )));
+        }
+        let n = buf.len().min(room);
+        this.pending.extend_from_slice(&buf[..n]);
+        let last = this.sent + this.pending.len() == this.total;
+        this.send(last)?;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_in_flight(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_in_flight(cx))?;
+            if this.pending.is_empty() {
+                break;
+            }
+            this.send(true)?;
+        }
+        if this.sent < this.total {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("stream closed after {} of {} bytes", this.sent, this.total),
+            )));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl std::fmt::Debug for CopyToDeviceStreamWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CopyToDeviceStreamWriter")
+            .field("granule", &self.granule)
+            .field("total", &self.total)
+            .field("sent", &self.sent)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+/// Takes the next chunk to send from `pending`.
+///
+/// Without `last`, only whole granules are taken. With `last`, everything is
+/// taken and zero-padded to a whole granule, but never beyond `room`.
+fn take_chunk(pending: &mut Vec<u8>, granule: usize, room: usize, last: bool) -> Option<Vec<u8>> {
+    if pending.is_empty() {
+        return None;
+    }
+    if last {
+        let padded = pending.len().div_ceil(granule) * granule;
+        let mut chunk = std::mem::take(pending);
+        chunk.resize(padded.min(room).max(chunk.len()), 0);
+        return Some(chunk);
+    }
+    let whole = pending.len() / granule * granule;
+    if whole == 0 {
+        return None;
+    }
+    let rest = pending.split_off(whole);
+    Some(std::mem::replace(pending, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_chunk_whole_granules() {
+        let mut pending = vec![1, 2, 3, 4, 5];
+        assert_eq!(
+            take_chunk(&mut pending, 2, 8, false),
+            Some(vec![1, 2, 3, 4])
+        );
+        assert_eq!(pending, vec![5]);
+        assert_eq!(take_chunk(&mut pending, 2, 8, false), None);
+    }
+
+    #[test]
+    fn test_take_chunk_last_pads() {
+        let mut pending = vec![1, 2, 3];
+        assert_eq!(take_chunk(&mut pending, 4, 8, true), Some(vec![1, 2, 3, 0]));
+        assert!(pending.is_empty());
+        assert_eq!(take_chunk(&mut pending, 4, 8, true), None);
+    }
+
+    #[test]
+    fn test_take_chunk_last_padding_limited_by_room() {
+        let mut pending = vec![1, 2, 3];
+        assert_eq!(take_chunk(&mut pending, 4, 3, true), Some(vec![1, 2, 3]));
+    }
 }
@@ -0,0 +1,223 @@
+//! Multi-Process Initialization
+//!
+//! This module provides [`initialize`], which sets up a client participating
+//! in a multi-process job in one call, like `jax.distributed.initialize`:
+//!
+//! 1. Process 0 starts a [`CoordinationService`] on the coordinator address.
+//! 2. Every process connects a [`CoordinationClient`] to it.
+//! 3. The client is created with the coordination client as its key-value
+//!    store, and with plugin options carrying the process id and count.
+//! 4. All processes wait at a barrier, then pass the state of every process
+//!    to the client with `Client::update_global_process_info`.
+//!
+//! The option names default to the ones read by the XLA GPU plugin
+//! (`node_id` and `num_nodes`) and can be changed for other plugins.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let api = plugin("pjrt_c_api_gpu_plugin.so").load()?;
+//! let distributed = pjrt::distributed::initialize(&api, "host0:8476", process_id, 4).connect()?;
+//!
+//! let client = distributed.client();
+//! println!("local devices: {:?}", client.local_devices()?);
+//! println!("all devices: {:?}", client.devices_by_process()?);
+//!
+//! distributed.shutdown()?;
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bon::builder;
+
+use crate::{
+    Api, Client, CoordinationClient, CoordinationService, Error, ErrorCode, NamedValue, Result,
+};
+
+/// The default time to wait for every process to join or leave.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+const INITIALIZE_BARRIER: &str = "pjrt_distributed_initialize";
+const SHUTDOWN_BARRIER: &str = "pjrt_distributed_shutdown";
+
+/// Initializes process `process_id` of `num_processes` and creates its
+/// client.
+///
+/// `coordinator_address` is the `host:port` of the coordination service.
+/// Process 0 starts the service there unless `start_service` is false, in
+/// which case it must be run separately.
+///
+/// `options` are passed to the client in addition to the process id and
+/// count, under the names `process_id_option` and `num_processes_option`.
+/// `timeout` bounds the wait for every process to connect and reach the
+/// initialization barrier.
+#[builder(finish_fn = connect)]
+pub fn initialize(
+    #[builder(start_fn)] api: &Api,
+    #[builder(start_fn, into)] coordinator_address: String,
+    #[builder(start_fn)] process_id: i32,
+    #[builder(start_fn)] num_processes: usize,
+    #[builder(default = bon::vec![], into)] options: Vec<NamedValue>,
+    #[builder(default = "node_id".to_string(), into)] process_id_option: String,
+    #[builder(default = "num_nodes".to_string(), into)] num_processes_option: String,
+    #[builder(default = true)] start_service: bool,
+    #[builder(default = DEFAULT_TIMEOUT)] timeout: Duration,
+    heartbeat_interval: Option<Duration>,
+    heartbeat_timeout: Option<Duration>,
+) -> Result<Distributed> {
+    if !usize::try_from(process_id).is_ok_and(|id| id < num_processes) {
+        return Err(Error::InvalidArgument(format!(
+            "invalid process id {process_id} for {num_processes} processes"
+        )));
+    }
+
+    let service = if process_id == 0 && start_service {
+        Some(
+            CoordinationService::builder(num_processes)
+                .address(coordinator_address.clone())
+                .maybe_heartbeat_timeout(heartbeat_timeout)
+                .start()?,
+        )
+    } else {
+        None
+    };
+
+    let coordinator = Arc::new(
+        CoordinationClient::builder(coordinator_address, process_id)
+            .maybe_heartbeat_interval(heartbeat_interval)
+            .connect_timeout(timeout)
+            .connect()?,
+    );
+
+    let mut options = options;
+    options.push(NamedValue::i64(&process_id_option, process_id as i64));
+    options.push(NamedValue::i64(&num_processes_option, num_processes as i64));
+    let client = api.create_client(options, Some(coordinator.clone()))?;
+
+    let distributed = Distributed {
+        client,
+        coordinator,
+        service,
+        num_processes,
+        timeout,
+    };
+    distributed
+        .coordinator
+        .barrier(INITIALIZE_BARRIER, timeout)?;
+    distributed.update_process_info()?;
+    Ok(distributed)
+}
+
+/// A client participating in a multi-process job.
+///
+/// Created by [`initialize`]. Dropping it disconnects from the coordination
+/// service and, on process 0, stops the service; use
+/// [`Distributed::shutdown`] to first wait for the other processes.
+///
+/// # Thread Safety
+///
+/// `Distributed` is `!Send + !Sync` because it holds a [`Client`].
+pub struct Distributed {
+    // Fields drop in order: the client before the store it calls back into,
+    // and the service last.
+    client: Client,
+    coordinator: Arc<CoordinationClient>,
+    service: Option<CoordinationService>,
+    num_processes: usize,
+    timeout: Duration,
+}
+
+impl Distributed {
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Returns the connection to the coordination service, which also
+    /// serves as the client's key-value store.
+    pub fn coordinator(&self) -> &CoordinationClient {
+        &self.coordinator
+    }
+
+    /// Returns the coordination service, if this process runs it.
+    pub fn service(&self) -> Option<&CoordinationService> {
+        self.service.as_ref()
+    }
+
+    pub fn process_id(&self) -> i32 {
+        self.coordinator.task_id()
+    }
+
+    pub fn num_processes(&self) -> usize {
+        self.num_processes
+    }
+
+    /// Waits until every process has reached the barrier `id`.
+    ///
+    /// See [`CoordinationClient::barrier`].
+    pub fn barrier(&self, id: &str, timeout: Duration) -> Result<()> {
+        self.coordinator.barrier(id, timeout)
+    }
+
+    /// Passes the current state of every process to the client.
+    ///
+    /// Call it again after a process fails or restarts. Plugins that do not
+    /// support process state updates are ignored.
+    pub fn update_process_info(&self) -> Result<()> {
+        ignore_unsupported(self.coordinator.update_process_info(&self.client))
+    }
+
+    /// Waits for every process to shut down, then disconnects.
+    ///
+    /// This keeps process 0 from stopping the coordination service while
+    /// other processes still use it.
+    pub fn shutdown(self) -> Result<()> {
+        self.coordinator.barrier(SHUTDOWN_BARRIER, self.timeout)
+    }
+}
+
+impl std::fmt::Debug for Distributed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Distributed")
+            .field("process_id", &self.process_id())
+            .field("num_processes", &self.num_processes)
+            .field("coordinator", &self.coordinator.address())
+            .field("runs_service", &self.service.is_some())
+            .finish()
+    }
+}
+
+/// Treats a plugin without process state updates as success.
+///
+/// Plugins report this either by not providing the function or by returning
+/// `Unimplemented`.
+fn ignore_unsupported(result: Result<()>) -> Result<()> {
+    match result {
+        Err(Error::NullFunctionPointer(_)) => Ok(()),
+        Err(err) if err.code() == ErrorCode::Unimplemented => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_unsupported() {
+        assert!(ignore_unsupported(Ok(())).is_ok());
+        assert!(ignore_unsupported(Err(Error::NullFunctionPointer(
+            "PJRT_Client_UpdateGlobalProcessInfo"
+        )))
+        .is_ok());
+        assert!(ignore_unsupported(Err(Error::PjrtError {
+            function: "PJRT_Client_UpdateGlobalProcessInfo",
+            msg: "not supported".to_string(),
+            code: ErrorCode::Unimplemented,
+            backtrace: String::new(),
+        }))
+        .is_ok());
+        let err = ignore_unsupported(Err(Error::InvalidArgument("bad".to_string()))).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+    }
+}
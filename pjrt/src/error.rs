@@ -26,7 +26,7 @@ use pjrt_sys::{
     PJRT_Error_Code_PJRT_Error_Code_UNIMPLEMENTED, PJRT_Error_Code_PJRT_Error_Code_UNKNOWN,
 };
 
-use crate::{GlobalDeviceId, PrimitiveType};
+use crate::{GlobalDeviceId, PrimitiveType, StablehloVersion};
 
 /// Error type for PJRT operations.
 ///
@@ -97,6 +97,9 @@ pub enum Error {
     #[error("lib loading error: {0}")]
     LibLoadingError(#[from] libloading::Error),
 
+    #[error("protobuf decode error: {0}")]
+    ProtoDecodeError(#[from] prost::DecodeError),
+
     #[error("lock poison error: {0}")]
     PoisonError(String),
 
@@ -108,6 +111,118 @@ pub enum Error {
         code: i32,
     },
 
+    /// An execution input does not match the executable's signature.
+    ///
+    /// `arg_index` is `None` when the mismatch concerns the inputs as a whole,
+    /// such as the number of devices or arguments.
+    #[error(
+        "input mismatch for {}: expected {expected}, got {actual}",
+        .arg_index.map_or_else(|| "inputs".to_string(), |i| format!("argument {i}"))
+    )]
+    InputMismatch {
+        /// Index of the mismatching argument, if the mismatch is per-argument
+        arg_index: Option<usize>,
+        /// What the executable expects
+        expected: String,
+        /// What was provided
+        actual: String,
+    },
+
+    #[error("missing input: {0}")]
+    MissingInput(String),
+
+    #[error("unexpected input: {0}")]
+    UnexpectedInput(String),
+
+    /// The program was serialized with a StableHLO version the plugin cannot
+    /// deserialize.
+    #[error(
+        "program uses StableHLO {program}, but the plugin supports versions {minimum} to {current}"
+    )]
+    IncompatibleStablehloVersion {
+        /// Version recorded in the program
+        program: StablehloVersion,
+        /// Oldest version the plugin supports
+        minimum: StablehloVersion,
+        /// Newest version the plugin supports
+        current: StablehloVersion,
+    },
+
+    /// An ahead-of-time compiled artifact does not match the client it is
+    /// loaded on.
+    #[error("artifact was compiled for {expected}, but the client provides {actual}")]
+    IncompatibleArtifact {
+        /// What the artifact was compiled for
+        expected: String,
+        /// What the client provides
+        actual: String,
+    },
+
+    /// A buffer was used after being donated to an execution.
+    #[error("buffer was donated to an execution and can no longer be used")]
+    BufferDonated,
+
+    #[error("execution deadline exceeded")]
+    DeadlineExceeded,
+
+    #[error("execution cancelled")]
+    Cancelled,
+
+    /// A batched execution failed; reported to every request in the batch.
+    #[error("batch execution failed: {msg}")]
+    BatchFailed {
+        /// The error code of the underlying failure
+        code: ErrorCode,
+        /// The message of the underlying failure
+        msg: String,
+    },
+
+    /// A request was sent to a `Batcher` that was closed or dropped before
+    /// serving it.
+    #[error("batcher is closed")]
+    BatcherClosed,
+
+    /// A key is not present in a `KeyValueStore`.
+    #[error("key not found: {0}")]
+    KeyNotFound(String),
+
+    /// A blocking `KeyValueStore::get` timed out before the key was set.
+    #[error("timed out after {timeout_in_ms}ms waiting for key: {key}")]
+    KeyValueTimeout {
+        /// The key that was waited for
+        key: String,
+        /// The timeout of the get
+        timeout_in_ms: i32,
+    },
+
+    /// An error reported by the coordination service.
+    #[error("coordination service error: {msg}")]
+    Coordination {
+        /// The error code reported by the service
+        code: ErrorCode,
+        /// The error message reported by the service
+        msg: String,
+    },
+
+    /// A value did not have the tree structure it was expected to have.
+    #[error("tree structure mismatch: expected {expected}, got {actual}")]
+    TreeMismatch {
+        /// Description of the expected structure
+        expected: String,
+        /// Description of the actual structure
+        actual: String,
+    },
+
+    /// A device is not one of the addressable devices of an executable, e.g.
+    /// because another process owns it.
+    #[error("device {0} is not addressable by this executable")]
+    DeviceNotAddressable(GlobalDeviceId),
+
+    /// The plugin launched an execution but returned no completion event for
+    /// a device.
+    #[error("plugin returned no completion event for device {0}")]
+    MissingDeviceEvent(GlobalDeviceId),
+
     #[error("unimplemented")]
     Unimplemented,
 }
@@ -115,11 +230,35 @@ pub enum Error {
 impl Error {
     /// Returns the PJRT error code associated with this error.
     ///
-    /// For `PjrtError` variants, returns the actual PJRT error code.
-    /// For other variants, returns `ErrorCode::Internal`.
+    /// - `PjrtError`, `BatchFailed`, `Coordination`: the code of the
+    ///   underlying error
+    /// - `InputMismatch`, `MissingInput`, `UnexpectedInput`,
+    ///   `IncompatibleStablehloVersion`, `TreeMismatch`,
+    ///   `DeviceNotAddressable`: `ErrorCode::InvalidArgument`
+    /// - `BufferDonated`, `IncompatibleArtifact`: `ErrorCode::FailedPrecondition`
+    /// - `KeyNotFound`: `ErrorCode::NotFound`
+    /// - `KeyValueTimeout`, `DeadlineExceeded`: `ErrorCode::DeadlineExceeded`
+    /// - `Cancelled`: `ErrorCode::Cancel`
+    /// - `BatcherClosed`: `ErrorCode::Unavailable`
+    /// - other variants: `ErrorCode::Internal`
     pub fn code(&self) -> ErrorCode {
         match self {
             Error::PjrtError { code, .. } => *code,
+            Error::InputMismatch { .. }
+            | Error::MissingInput(_)
+            | Error::UnexpectedInput(_)
+            | Error::IncompatibleStablehloVersion { .. }
+            | Error::TreeMismatch { .. }
+            | Error::DeviceNotAddressable(_) => ErrorCode::InvalidArgument,
+            Error::BufferDonated | Error::IncompatibleArtifact { .. } => {
+                ErrorCode::FailedPrecondition
+            }
+            Error::KeyNotFound(_) => ErrorCode::NotFound,
+            Error::KeyValueTimeout { .. } => ErrorCode::DeadlineExceeded,
+            Error::DeadlineExceeded => ErrorCode::DeadlineExceeded,
+            Error::Cancelled => ErrorCode::Cancel,
+            Error::BatcherClosed => ErrorCode::Unavailable,
+            Error::BatchFailed { code, .. } | Error::Coordination { code, .. } => *code,
             _ => ErrorCode::Internal,
         }
     }
@@ -367,6 +506,155 @@ mod tests {
         assert!(display.contains("io error"));
     }
 
+    #[test]
+    fn test_input_mismatch_error() {
+        let err = Error::InputMismatch {
+            arg_index: Some(1),
+            expected: "F32[2, 3]".to_string(),
+            actual: "F32[3, 2]".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "input mismatch for argument 1: expected F32[2, 3], got F32[3, 2]"
+        );
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+
+        let err = Error::InputMismatch {
+            arg_index: None,
+            expected: "2 devices".to_string(),
+            actual: "1 device".to_string(),
+        };
+        assert!(err.to_string().starts_with("input mismatch for inputs"));
+    }
+
+    #[test]
+    fn test_named_input_errors() {
+        let err = Error::MissingInput("x".to_string());
+        assert_eq!(err.to_string(), "missing input: x");
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+
+        let err = Error::UnexpectedInput("y".to_string());
+        assert_eq!(err.to_string(), "unexpected input: y");
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn test_incompatible_stablehlo_version_error() {
+        let err = Error::IncompatibleStablehloVersion {
+            program: StablehloVersion::new(1, 10, 0),
+            minimum: StablehloVersion::new(0, 9, 0),
+            current: StablehloVersion::new(1, 9, 5),
+        };
+        assert_eq!(
+            err.to_string(),
+            "program uses StableHLO 1.10.0, but the plugin supports versions 0.9.0 to 1.9.5"
+        );
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn test_incompatible_artifact_error() {
+        let err = Error::IncompatibleArtifact {
+            expected: "platform cuda".to_string(),
+            actual: "platform cpu".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "artifact was compiled for platform cuda, but the client provides platform cpu"
+        );
+        assert_eq!(err.code(), ErrorCode::FailedPrecondition);
+    }
+
+    #[test]
+    fn test_execution_interrupted_errors() {
+        assert_eq!(
+            Error::DeadlineExceeded.to_string(),
+            "execution deadline exceeded"
+        );
+        assert_eq!(Error::DeadlineExceeded.code(), ErrorCode::DeadlineExceeded);
+        assert_eq!(Error::Cancelled.to_string(), "execution cancelled");
+        assert_eq!(Error::Cancelled.code(), ErrorCode::Cancel);
+    }
+
+    #[test]
+    fn test_buffer_donated_error() {
+        assert_eq!(Error::BufferDonated.code(), ErrorCode::FailedPrecondition);
+        assert!(Error::BufferDonated.to_string().contains("donated"));
+    }
+
+    #[test]
+    fn test_batch_failed_error() {
+        let err = Error::BatchFailed {
+            code: ErrorCode::ResourceExhausted,
+            msg: "out of memory".to_string(),
+        };
+        assert_eq!(err.code(), ErrorCode::ResourceExhausted);
+        assert_eq!(err.to_string(), "batch execution failed: out of memory");
+    }
+
+    #[test]
+    fn test_batcher_closed_error() {
+        assert_eq!(Error::BatcherClosed.code(), ErrorCode::Unavailable);
+        assert_eq!(Error::BatcherClosed.to_string(), "batcher is closed");
+    }
+
+    #[test]
+    fn test_key_value_errors() {
+        let err = Error::KeyNotFound("a".to_string());
+        assert_eq!(err.code(), ErrorCode::NotFound);
+        assert_eq!(err.to_string(), "key not found: a");
+
+        let err = Error::KeyValueTimeout {
+            key: "a".to_string(),
+            timeout_in_ms: 100,
+        };
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+        assert_eq!(err.to_string(), "timed out after 100ms waiting for key: a");
+    }
+
+    #[test]
+    fn test_coordination_error() {
+        let err = Error::Coordination {
+            code: ErrorCode::Aborted,
+            msg: "barrier aborted".to_string(),
+        };
+        assert_eq!(err.code(), ErrorCode::Aborted);
+        assert_eq!(
+            err.to_string(),
+            "coordination service error: barrier aborted"
+        );
+    }
+
+    #[test]
+    fn test_tree_mismatch_error() {
+        let err = Error::TreeMismatch {
+            expected: "(*, *)".to_string(),
+            actual: "[*]".to_string(),
+        };
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+        assert_eq!(
+            err.to_string(),
+            "tree structure mismatch: expected (*, *), got [*]"
+        );
+    }
+
+    #[test]
+    fn test_device_launch_errors() {
+        let err = Error::DeviceNotAddressable(3);
+        assert_eq!(err.code(), ErrorCode::InvalidArgument);
+        assert_eq!(
+            err.to_string(),
+            "device 3 is not addressable by this executable"
+        );
+
+        let err = Error::MissingDeviceEvent(3);
+        assert_eq!(err.code(), ErrorCode::Internal);
+        assert_eq!(
+            err.to_string(),
+            "plugin returned no completion event for device 3"
+        );
+    }
+
     #[test]
     fn test_error_from_io_error() {
         let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "test");
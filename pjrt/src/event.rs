@@ -10,21 +10,29 @@
 //!
 //! The `Event` struct implements Rust's `Future` trait, allowing it to be used with
 //! async/await syntax for convenient asynchronous programming.
+//!
+//! Beyond awaiting a single event, this module provides:
+//!
+//! - `Event::join_all` and `Event::select` to wait on many events at once
+//! - `Event::wait_timeout` to bound blocking waits
+//! - `Event::on_ready` to run a closure when an event completes
+//! - `Event::from_future` to express host-side work as a PJRT event
 
 use std::ffi::c_void;
-use std::future::Future;
+use std::future::{poll_fn, Future};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 
 use pjrt_sys::{
-    PJRT_Error, PJRT_Error_Code, PJRT_Error_Destroy_Args, PJRT_Event, PJRT_Event_Await_Args,
-    PJRT_Event_Create_Args, PJRT_Event_Destroy_Args, PJRT_Event_Error_Args,
-    PJRT_Event_IsReady_Args, PJRT_Event_OnReady_Args, PJRT_Event_Set_Args,
+    PJRT_Error, PJRT_Error_Code, PJRT_Error_Code_PJRT_Error_Code_OK, PJRT_Error_Destroy_Args,
+    PJRT_Event, PJRT_Event_Await_Args, PJRT_Event_Create_Args, PJRT_Event_Destroy_Args,
+    PJRT_Event_Error_Args, PJRT_Event_IsReady_Args, PJRT_Event_OnReady_Args, PJRT_Event_Set_Args,
 };
 
-use crate::{Api, ErrorCode, Result};
+use crate::{Api, Error, ErrorCode, Result};
 
 /// Shared state between the Event and the on-ready callback.
 ///
@@ -55,6 +63,43 @@ extern "C" fn on_ready_callback(err: *mut PJRT_Error, cb_data: *mut c_void) {
     });
 }
 
+/// Boxed closure passed to `PJRT_Event_OnReady` by `Event::on_ready`.
+struct OnReadyClosure {
+    api: Api,
+    callback: Box<dyn FnOnce(Result<()>) + Send>,
+}
+
+extern "C" fn on_ready_closure(err: *mut PJRT_Error, cb_data: *mut c_void) {
+    // Wrap in catch_unwind to prevent panicking across the FFI boundary (UB).
+    let _ = std::panic::catch_unwind(|| {
+        // SAFETY: cb_data was created by Box::into_raw in Event::on_ready.
+        let closure = unsafe { Box::from_raw(cb_data as *mut OnReadyClosure) };
+        let result = closure.api.err_or_with_fn(err, (), "PJRT_Event_OnReady");
+        (closure.callback)(result);
+    });
+}
+
+/// A created event shared with the task that sets it.
+///
+/// The PJRT event is destroyed once both the `Event` and the setter are gone,
+/// so the setter never touches a destroyed event.
+struct SharedEvent {
+    api: Api,
+    ptr: *mut PJRT_Event,
+}
+
+// SAFETY: PJRT events may be set and destroyed from any thread.
+unsafe impl Send for SharedEvent {}
+unsafe impl Sync for SharedEvent {}
+
+impl Drop for SharedEvent {
+    fn drop(&mut self) {
+        let mut args = PJRT_Event_Destroy_Args::new();
+        args.event = self.ptr;
+        let _ = self.api.PJRT_Event_Destroy(args);
+    }
+}
+
 /// An asynchronous event that signals completion of a PJRT operation.
 ///
 /// Events are used throughout PJRT to track the completion of asynchronous
@@ -80,10 +125,15 @@ pub struct Event {
     registered_callback: AtomicBool,
     /// Shared state for the waker, updated on each poll and read by the callback.
     callback_state: Arc<CallbackState>,
+    /// Set for events created by `Event::from_future`, which own `ptr`.
+    shared: Option<Arc<SharedEvent>>,
 }
 
 impl Drop for Event {
     fn drop(&mut self) {
+        if self.shared.is_some() {
+            return;
+        }
         let mut args = PJRT_Event_Destroy_Args::new();
         args.event = self.ptr;
         let _ = self.api.PJRT_Event_Destroy(args);
@@ -114,6 +164,7 @@ impl Event {
                 api: api.clone(),
                 waker: Mutex::new(None),
             }),
+            shared: None,
         }
     }
 
@@ -182,15 +233,158 @@ impl Event {
     /// This marks the event as complete. If error_code is OK, the event
     /// completes successfully. Otherwise, it completes with an error.
     pub fn set(&self, error_code: ErrorCode, error_message: Option<&str>) -> Result<()> {
-        let mut args = PJRT_Event_Set_Args::new();
+        set_event(
+            &self.api,
+            self.ptr,
+            error_code as PJRT_Error_Code,
+            error_message,
+        )
+    }
+
+    /// Creates an event that completes with the result of `future`.
+    ///
+    /// The future is spawned on the current Tokio runtime, which lets
+    /// host-side work be passed wherever PJRT expects an event dependency.
+    /// An error result completes the event with the error's code and message.
+    ///
+    /// Returns an error when called outside a Tokio runtime.
+    pub fn from_future<F>(api: &Api, future: F) -> Result<Self>
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|e| Error::InvalidArgument(format!("no tokio runtime: {e}")))?;
+        let mut event = Self::create(api)?;
+        let shared = Arc::new(SharedEvent {
+            api: api.clone(),
+            ptr: event.ptr,
+        });
+        event.shared = Some(Arc::clone(&shared));
+        handle.spawn(async move {
+            let _ = match future.await {
+                Ok(()) => set_event(
+                    &shared.api,
+                    shared.ptr,
+                    PJRT_Error_Code_PJRT_Error_Code_OK,
+                    None,
+                ),
+                Err(err) => set_event(
+                    &shared.api,
+                    shared.ptr,
+                    err.code() as PJRT_Error_Code,
+                    Some(&err.to_string()),
+                ),
+            };
+        });
+        Ok(event)
+    }
+
+    /// Calls `callback` with the event's result once it completes.
+    ///
+    /// The callback may run immediately on the calling thread if the event is
+    /// already complete, or later on a runtime thread.
+    pub fn on_ready<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnOnce(Result<()>) + Send + 'static,
+    {
+        let closure = Box::into_raw(Box::new(OnReadyClosure {
+            api: self.api.clone(),
+            callback: Box::new(callback),
+        }));
+        let mut args = PJRT_Event_OnReady_Args::new();
         args.event = self.ptr;
-        args.error_code = error_code as PJRT_Error_Code;
-        if let Some(msg) = error_message {
-            args.error_message = msg.as_ptr() as *const i8;
-            args.error_message_size = msg.len();
+        args.user_arg = closure as *mut c_void;
+        args.callback = Some(on_ready_closure);
+        if let Err(e) = self.api.PJRT_Event_OnReady(args) {
+            // Registration failed — reclaim the closure to avoid a leak.
+            drop(unsafe { Box::from_raw(closure) });
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Blocks until the event completes or `timeout` elapses.
+    ///
+    /// Returns `Ok(true)` if the event completed successfully, `Ok(false)` on
+    /// timeout, and the event's error if it failed. Unlike [`Event::wait`],
+    /// the event is not consumed, so waiting can be retried.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool> {
+        if !self.is_ready()? {
+            let state = Arc::new((Mutex::new(false), Condvar::new()));
+            let notify = Arc::clone(&state);
+            self.on_ready(move |_| {
+                let (ready, condvar) = &*notify;
+                *ready.lock().unwrap_or_else(|e| e.into_inner()) = true;
+                condvar.notify_all();
+            })?;
+            let (ready, condvar) = &*state;
+            let guard = ready.lock().unwrap_or_else(|e| e.into_inner());
+            let (guard, _) = condvar
+                .wait_timeout_while(guard, timeout, |ready| !*ready)
+                .unwrap_or_else(|e| e.into_inner());
+            if !*guard {
+                return Ok(false);
+            }
         }
-        self.api.PJRT_Event_Set(args).map(|_| ())
+        self.error().map(|_| true)
+    }
+
+    /// Waits for all `events` to complete.
+    ///
+    /// Every event is awaited even if an earlier one fails; the first error
+    /// in iteration order is returned.
+    pub async fn join_all<I>(events: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Event>,
+    {
+        let mut result = Ok(());
+        for event in events {
+            let event_result = event.await;
+            if result.is_ok() {
+                result = event_result;
+            }
+        }
+        result
+    }
+
+    /// Waits for the first of `events` to complete.
+    ///
+    /// Returns the index of the completed event, its result, and the
+    /// remaining events in their original order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `events` is empty.
+    pub async fn select(mut events: Vec<Event>) -> (usize, Result<()>, Vec<Event>) {
+        assert!(!events.is_empty(), "select requires at least one event");
+        let (index, result) = poll_fn(|cx| {
+            for (index, event) in events.iter_mut().enumerate() {
+                if let Poll::Ready(result) = Pin::new(event).poll(cx) {
+                    return Poll::Ready((index, result));
+                }
+            }
+            Poll::Pending
+        })
+        .await;
+        events.remove(index);
+        (index, result, events)
+    }
+}
+
+fn set_event(
+    api: &Api,
+    event: *mut PJRT_Event,
+    error_code: PJRT_Error_Code,
+    error_message: Option<&str>,
+) -> Result<()> {
+    let mut args = PJRT_Event_Set_Args::new();
+    args.event = event;
+    args.error_code = error_code;
+    if let Some(msg) = error_message {
+        args.error_message = msg.as_ptr() as *const i8;
+        args.error_message_size = msg.len();
     }
+    api.PJRT_Event_Set(args).map(|_| ())
 }
 
 impl Future for Event {
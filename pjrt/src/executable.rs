@@ -11,12 +11,17 @@
 //! - `SerializedCompileOptions`: Serialized compilation options
 //! - `CompiledMemoryStats`: Memory usage statistics for compiled executables
 //!
+//! `Executable::signature` describes parameters and outputs; see the
+//! `signature` module for details.
+//!
 //! Executables are created by compiling programs through the `Api::compile` or
 //! `Client::compile` methods.
 
 use std::borrow::{Borrow, Cow};
+use std::cell::OnceCell;
 
 use bon::bon;
+use pjrt_sys::protos::xla::HloModuleProto;
 use pjrt_sys::{
     PJRT_Executable, PJRT_Executable_Destroy_Args, PJRT_Executable_Fingerprint_Args,
     PJRT_Executable_GetCompileOptions_Args, PJRT_Executable_GetCompiledMemoryStats_Args,
@@ -28,11 +33,12 @@ use pjrt_sys::{
     PJRT_Executable_SizeOfGeneratedCodeInBytes_Args, PJRT_SerializedCompileOptions,
     PJRT_SerializedExecutable,
 };
+use prost::Message;
 
 use crate::program::ProgramFormat;
 use crate::{
-    utils, Api, Client, CompileOptions, CompileToExecutable, NamedValueMap, PrimitiveType, Program,
-    Result, TopologyDescription,
+    utils, Api, Client, CompileOptions, CompileToExecutable, Error, ExecutableSignature,
+    NamedValueMap, PrimitiveType, Program, Result, TopologyDescription,
 };
 
 /// A compiled PJRT program ready to be loaded onto devices.
@@ -64,6 +70,7 @@ use crate::{
 pub struct Executable {
     api: Api,
     pub(crate) ptr: *mut PJRT_Executable,
+    signature: OnceCell<ExecutableSignature>,
 }
 
 impl Drop for Executable {
@@ -93,6 +100,7 @@ impl Executable {
         Self {
             api: api.clone(),
             ptr,
+            signature: OnceCell::new(),
         }
     }
 
@@ -232,6 +240,31 @@ impl Executable {
         Ok(out)
     }
 
+    /// Returns the parameter and output signature of this executable.
+    ///
+    /// The signature is derived from the optimized HLO module on first use and
+    /// cached for the lifetime of this `Executable`.
+    pub fn signature(&self) -> Result<&ExecutableSignature> {
+        if let Some(signature) = self.signature.get() {
+            return Ok(signature);
+        }
+        let program = self.optimize()?;
+        if program.format() != ProgramFormat::HLO {
+            return Err(Error::InvalidProgramFormat(format!(
+                "expected optimized HLO module, got {}",
+                program.format().as_str()
+            )));
+        }
+        let module = HloModuleProto::decode(program.code())?;
+        // Not every plugin reports output memory kinds.
+        let memory_kinds: Vec<String> = self
+            .output_memory_kinds()
+            .map(|kinds| kinds.into_iter().map(Cow::into_owned).collect())
+            .unwrap_or_default();
+        let signature = ExecutableSignature::from_hlo_module(&module, &memory_kinds)?;
+        Ok(self.signature.get_or_init(|| signature))
+    }
+
     pub fn serialize(&self) -> Result<SerializedExecutable> {
         let mut args = PJRT_Executable_Serialize_Args::new();
         args.executable = self.ptr;
@@ -3,37 +3,76 @@
 //! This module provides types and traits for executing compiled PJRT programs.
 //! It includes:
 //!
-//! - `ExecuteContext`: Context for execution operations
+//! - `ExecuteContext`: Context for execution operations, carrying typed user
+//!   data for FFI handlers
 //! - `ExecuteOptions`: Configuration options for execution
 //! - `Execution`: A builder pattern for configuring and running executions
+//! - `DeviceExecution`: An execution launched on a single addressable device
 //! - `ExecutionInputs`: Trait for types that can be used as execution inputs
 //! - `SendCallbackInfo` / `RecvCallbackInfo`: Callbacks for distributed communication
+//! - `ExecuteOptions::on_send` / `ExecuteOptions::on_recv`: Safe closure-based
+//!   host callbacks
 //! - `CallLocation`: Source location information for debugging
 //!
+//! Executions can be bounded with `ExecuteOptions::deadline` and interrupted
+//! through a `CancellationToken`. On expiry the launch is poisoned on every
+//! participating device, which turns its outputs into error buffers, and the
+//! wait fails with `Error::DeadlineExceeded` or `Error::Cancelled`.
+//!
 //! The module provides both synchronous and asynchronous execution patterns,
 //! supporting various input types including single buffers, arrays, and vectors.
 
-use std::collections::HashSet;
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
 use std::ffi::{c_void, CString};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 use pjrt_sys::{
     PJRT_Buffer, PJRT_ExecuteContext, PJRT_ExecuteContext_Destroy_Args, PJRT_ExecuteOptions,
     PJRT_RecvCallbackInfo, PJRT_SendCallbackInfo,
 };
 
-use crate::{Api, Buffer, LoadedExecutable, Result};
+use crate::host_callback::{LaunchCallbacks, RecvHandler, SendHandler};
+use crate::{
+    Api, Buffer, CancellationToken, Chunk, CopyToDeviceStream, Device, Error, FfiExt,
+    LoadedExecutable, Result,
+};
 
 /// Context for PJRT execution operations.
 ///
 /// An `ExecuteContext` provides the environment for executing compiled programs.
 /// It can be used to share state across multiple executions and manage
 /// resources for execution operations.
+///
+/// Rust values attached with [`ExecuteContext::insert_user_data`] are passed
+/// to FFI handlers of executions launched with [`ExecuteOptions::context`].
+/// Executions share the context through an `Arc`, and every launch holds on
+/// to it until it completes.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let type_id = ffi.register_type("MyState", &type_info, 0)?;
+/// let mut context = api.create_execute_context()?;
+/// context.insert_user_data(type_id, MyState::new())?;
+///
+/// let options = ExecuteOptions::new().context(Arc::new(context));
+/// let outputs = loaded_executable.execute(inputs, &options).await?;
+/// ```
 pub struct ExecuteContext {
     api: Api,
     pub(crate) ptr: *mut PJRT_ExecuteContext,
+    user_data: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
 }
 
+// SAFETY: PJRT execute contexts are used by executions running on plugin
+// threads, and the attached user data is `Send + Sync`.
+unsafe impl Send for ExecuteContext {}
+unsafe impl Sync for ExecuteContext {}
+
 impl Drop for ExecuteContext {
     fn drop(&mut self) {
         let mut args = PJRT_ExecuteContext_Destroy_Args::new();
@@ -48,12 +87,51 @@ impl ExecuteContext {
         Self {
             api: api.clone(),
             ptr,
+            user_data: HashMap::new(),
         }
     }
 
     pub fn api(&self) -> &Api {
         &self.api
     }
+
+    /// Attaches `value` as FFI user data under the registered FFI `type_id`.
+    ///
+    /// FFI handlers looking up `type_id` receive a pointer to the stored `T`,
+    /// which they may access concurrently from plugin threads. The value is
+    /// owned by the context and dropped after the PJRT context is destroyed.
+    /// Executions share the context through [`ExecuteOptions::context`], so
+    /// it can neither be dropped nor modified while they are in flight.
+    /// Register `type_id` without a deleter, as the context frees the value.
+    ///
+    /// Returns an error if a value of type `T` is already attached, or
+    /// `Error::Unimplemented` if the plugin lacks the FFI extension.
+    pub fn insert_user_data<T>(&mut self, type_id: i64, value: T) -> Result<()>
+    where
+        T: Send + Sync + 'static,
+    {
+        if self.user_data.contains_key(&TypeId::of::<T>()) {
+            return Err(Error::InvalidArgument(format!(
+                "user data of type `{}` already attached",
+                std::any::type_name::<T>()
+            )));
+        }
+        let ffi = self.api.ffi_extension().ok_or(Error::Unimplemented)?;
+        let mut value = Box::new(value);
+        let data = &mut *value as *mut T as *mut c_void;
+        // SAFETY: the box is kept in `user_data` until after the PJRT context
+        // is destroyed, and moving the box does not move its contents.
+        unsafe { ffi.add_user_data(self, type_id, data)? };
+        self.user_data.insert(TypeId::of::<T>(), value);
+        Ok(())
+    }
+
+    /// Returns the user data of type `T`, if attached.
+    pub fn user_data<T: 'static>(&self) -> Option<&T> {
+        self.user_data
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
 }
 
 /// Options for configuring PJRT execution.
@@ -73,6 +151,7 @@ impl ExecuteContext {
 pub struct ExecuteOptions<'a> {
     launch_id: i32,
     non_donatable_input_indices: Vec<i64>,
+    donated_input_indices: Vec<i64>,
     call_location: Option<CallLocation>,
     task_ids: Vec<i32>,
     incarnation_ids: Vec<i64>,
@@ -80,6 +159,24 @@ pub struct ExecuteOptions<'a> {
     send_callbacks: Vec<Vec<SendCallbackInfo<'a>>>,
     /// Recv callbacks per device. Outer vec is per device, inner vec is per recv op.
     recv_callbacks: Vec<Vec<RecvCallbackInfo<'a>>>,
+    /// Closure-based send callbacks, shared by all devices and launches.
+    send_handlers: Vec<Arc<SendHandler>>,
+    /// Closure-based recv callbacks, shared by all devices and launches.
+    recv_handlers: Vec<Arc<RecvHandler>>,
+    context: Option<Arc<ExecuteContext>>,
+    validate_inputs: bool,
+    deadline: Option<Instant>,
+    cancellation_token: Option<CancellationToken>,
+}
+
+/// Returns a process-unique, non-zero launch ID.
+fn next_launch_id() -> i32 {
+    static NEXT_LAUNCH_ID: AtomicI32 = AtomicI32::new(1);
+    NEXT_LAUNCH_ID
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| {
+            Some(id.checked_add(1).unwrap_or(1))
+        })
+        .unwrap_or(1)
 }
 
 impl<'a> ExecuteOptions<'a> {
@@ -87,11 +184,18 @@ impl<'a> ExecuteOptions<'a> {
         Self {
             launch_id: 0,
             non_donatable_input_indices: vec![],
+            donated_input_indices: vec![],
             call_location: None,
             task_ids: vec![],
             incarnation_ids: vec![],
             send_callbacks: vec![],
             recv_callbacks: vec![],
+            send_handlers: vec![],
+            recv_handlers: vec![],
+            context: None,
+            validate_inputs: true,
+            deadline: None,
+            cancellation_token: None,
         }
     }
 
@@ -115,11 +219,39 @@ impl<'a> ExecuteOptions<'a> {
     ///
     /// By defining this list of indices, a higher-level PJRT caller can
     /// instruct PJRT client not to donate specific input buffers.
+    ///
+    /// These indices add to the ones the inputs keep by default, see
+    /// [`ExecutionInputs`].
     pub fn non_donatable_input_indices(mut self, indices: impl Into<Vec<i64>>) -> Self {
         self.non_donatable_input_indices = indices.into();
         self
     }
 
+    /// Allows the argument at `index` to be donated to the outputs.
+    ///
+    /// Donation only happens if the executable aliases the parameter with an
+    /// output. This overrides borrowed inputs, which are kept by default, so
+    /// a donated borrowed buffer can no longer be used; see
+    /// [`ExecutionInputs`].
+    pub fn donate(mut self, index: usize) -> Self {
+        let index = index as i64;
+        self.non_donatable_input_indices.retain(|&i| i != index);
+        if !self.donated_input_indices.contains(&index) {
+            self.donated_input_indices.push(index);
+        }
+        self
+    }
+
+    /// Prevents the argument at `index` from being donated.
+    pub fn keep(mut self, index: usize) -> Self {
+        let index = index as i64;
+        self.donated_input_indices.retain(|&i| i != index);
+        if !self.non_donatable_input_indices.contains(&index) {
+            self.non_donatable_input_indices.push(index);
+        }
+        self
+    }
+
     /// Sets the call location for debugging and error reporting.
     ///
     /// The call location stores the source location (e.g., file:line) of the
@@ -157,21 +289,131 @@ impl<'a> ExecuteOptions<'a> {
         self
     }
 
+    /// Sets the execution context passed to the plugin.
+    ///
+    /// User data attached to the context is available to FFI handlers during
+    /// execution. Every launch with these options keeps a reference to the
+    /// context until it completes, even if the options, the returned events
+    /// and the outputs are dropped first.
+    pub fn context(mut self, context: Arc<ExecuteContext>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Enables or disables pre-execution input validation.
+    ///
+    /// Validation is enabled by default. Before calling into the plugin, the
+    /// inputs are checked against the executable's addressable devices and
+    /// signature: device count, argument count, element type, dimensions and
+    /// device placement. Mismatches are reported as `Error::InputMismatch`.
+    ///
+    /// Disabling validation saves a few PJRT queries per argument on hot paths
+    /// where inputs are known to be well-formed.
+    pub fn validate_inputs(mut self, validate: bool) -> Self {
+        self.validate_inputs = validate;
+        self
+    }
+
+    /// Sets a deadline for the execution.
+    ///
+    /// If the execution has not completed by `deadline`, waiting for it in
+    /// `LoadedExecutable::execute` or `Execution::run` poisons the launch on
+    /// every participating device and fails with `Error::DeadlineExceeded`.
+    /// The asynchronous variants require a Tokio runtime with the time driver
+    /// enabled.
+    ///
+    /// Poisoning targets the launch ID, so a unique one is assigned if none
+    /// was set. Multi-process launches should set a shared launch ID.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self.ensure_launch_id();
+        self
+    }
+
+    /// Sets a token that cancels the execution.
+    ///
+    /// Cancelling the token while the execution is awaited poisons the launch
+    /// on every participating device and fails with `Error::Cancelled`. As
+    /// with [`ExecuteOptions::deadline`], a unique launch ID is assigned if
+    /// none was set.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self.ensure_launch_id();
+        self
+    }
+
+    fn ensure_launch_id(&mut self) {
+        if self.launch_id == 0 {
+            self.launch_id = next_launch_id();
+        }
+    }
+
     /// Returns the launch ID.
     pub fn get_launch_id(&self) -> i32 {
         self.launch_id
     }
 
     /// Returns the non-donatable input indices.
+    ///
+    /// These are the indices set on the options, without the ones the inputs
+    /// keep by default.
     pub fn get_non_donatable_input_indices(&self) -> &[i64] {
         &self.non_donatable_input_indices
     }
 
+    /// Returns the indices of the inputs allowed to be donated with
+    /// [`ExecuteOptions::donate`].
+    pub fn get_donated_input_indices(&self) -> &[i64] {
+        &self.donated_input_indices
+    }
+
+    /// Returns the indices of the inputs the runtime must not donate when
+    /// executing `inputs` with these options.
+    ///
+    /// Those are the inputs kept by default and the ones kept by the options,
+    /// less the ones the options donate, sorted.
+    pub(crate) fn resolve_non_donatable_input_indices<I>(&self, inputs: &I) -> Vec<i64>
+    where
+        I: ExecutionInputs + ?Sized,
+    {
+        let mut indices = inputs.non_donatable_input_indices();
+        indices.extend_from_slice(&self.non_donatable_input_indices);
+        indices.retain(|i| !self.donated_input_indices.contains(i));
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
     /// Returns the call location if set.
     pub fn get_call_location(&self) -> Option<&CallLocation> {
         self.call_location.as_ref()
     }
 
+    /// Returns the execution context if set.
+    pub fn get_context(&self) -> Option<&Arc<ExecuteContext>> {
+        self.context.as_ref()
+    }
+
+    /// Returns whether inputs are validated before execution.
+    pub fn get_validate_inputs(&self) -> bool {
+        self.validate_inputs
+    }
+
+    /// Returns the deadline if set.
+    pub fn get_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the cancellation token if set.
+    pub fn get_cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+
+    /// Returns whether waiting for the execution can be interrupted.
+    pub(crate) fn is_interruptible(&self) -> bool {
+        self.deadline.is_some() || self.cancellation_token.is_some()
+    }
+
     /// Sets the send callbacks for distributed execution.
     ///
     /// The outer vector corresponds to each device (length `num_devices`).
@@ -202,6 +444,45 @@ impl<'a> ExecuteOptions<'a> {
         self
     }
 
+    /// Handles host sends on `channel_id` with a closure.
+    ///
+    /// The closure runs on a runtime thread once per device and transfer,
+    /// with the chunks of the transfer joined into one [`Chunk`]. The C API
+    /// does not expose the shape of the transferred value, so `metadata`
+    /// describes it as a `U8` vector of the transfer size.
+    ///
+    /// Errors returned by the closure, and panics inside it, fail the
+    /// execution with a `CallbackError`. Every launch keeps the closure
+    /// alive until it completes, even if these options are dropped first.
+    pub fn on_send<F>(mut self, channel_id: i64, callback: F) -> Self
+    where
+        F: FnMut(Chunk, TransferMetadata) -> Result<()> + Send + 'static,
+    {
+        self.send_handlers
+            .push(Arc::new(SendHandler::new(channel_id, Box::new(callback))));
+        self
+    }
+
+    /// Handles host receives on `channel_id` with a closure.
+    ///
+    /// The closure runs on a runtime thread once per device and transfer and
+    /// must write `metadata.size_in_bytes()` bytes to the stream, e.g. with
+    /// [`CopyToDeviceStream::add_chunk_sync`] or a
+    /// [`CopyToDeviceStream::writer`]. The stream is destroyed when
+    /// the closure returns.
+    ///
+    /// Recv callbacks have no way to report failure to the runtime, so
+    /// errors and panics only cut the transfer short. Like send closures,
+    /// the closure is kept alive by every launch until it completes.
+    pub fn on_recv<F>(mut self, channel_id: i64, callback: F) -> Self
+    where
+        F: FnMut(&TransferMetadata, &CopyToDeviceStream) -> Result<()> + Send + 'static,
+    {
+        self.recv_handlers
+            .push(Arc::new(RecvHandler::new(channel_id, Box::new(callback))));
+        self
+    }
+
     /// Returns the send callbacks.
     pub fn get_send_callbacks(&self) -> &[Vec<SendCallbackInfo<'a>>] {
         &self.send_callbacks
@@ -211,6 +492,16 @@ impl<'a> ExecuteOptions<'a> {
     pub fn get_recv_callbacks(&self) -> &[Vec<RecvCallbackInfo<'a>>] {
         &self.recv_callbacks
     }
+
+    /// Returns the closure-based callbacks for a launch on `num_devices`
+    /// devices, or `None` if there are none.
+    pub(crate) fn launch_callbacks(
+        &self,
+        api: &Api,
+        num_devices: usize,
+    ) -> Option<Arc<LaunchCallbacks>> {
+        LaunchCallbacks::new(&self.send_handlers, &self.recv_handlers, api, num_devices)
+    }
 }
 
 impl Default for ExecuteOptions<'_> {
@@ -509,7 +800,18 @@ pub(crate) struct ExecuteOptionsRaw {
 
 impl ExecuteOptionsRaw {
     /// Creates a new ExecuteOptionsRaw from ExecuteOptions and populates the raw PJRT_ExecuteOptions.
-    pub fn new<'a>(options: &'a ExecuteOptions<'a>, raw: &mut PJRT_ExecuteOptions) -> Self {
+    ///
+    /// `non_donatable_input_indices` replaces the indices of `options`, see
+    /// [`ExecuteOptions::resolve_non_donatable_input_indices`], and must
+    /// outlive the raw options like `options` does. `callbacks`, from
+    /// [`ExecuteOptions::launch_callbacks`], are added to each device's
+    /// callback lists and must be kept alive until the launch completes.
+    pub fn for_launch(
+        options: &ExecuteOptions<'_>,
+        non_donatable_input_indices: &[i64],
+        callbacks: Option<&LaunchCallbacks>,
+        raw: &mut PJRT_ExecuteOptions,
+    ) -> Self {
         // Convert send callbacks to raw format
         let mut send_callbacks_raw: Vec<Vec<PJRT_SendCallbackInfo>> = options
             .send_callbacks
@@ -524,6 +826,24 @@ impl ExecuteOptionsRaw {
             .map(|device_callbacks| device_callbacks.iter().map(|cb| cb.to_raw()).collect())
             .collect();
 
+        // Append closure-based callbacks to every device's list
+        if let Some(launch) = callbacks {
+            if launch.has_sends() {
+                let len = send_callbacks_raw.len().max(launch.num_devices());
+                send_callbacks_raw.resize_with(len, Vec::new);
+                for (device_index, device_callbacks) in send_callbacks_raw.iter_mut().enumerate() {
+                    device_callbacks.extend(launch.send_callbacks(device_index));
+                }
+            }
+            if launch.has_recvs() {
+                let len = recv_callbacks_raw.len().max(launch.num_devices());
+                recv_callbacks_raw.resize_with(len, Vec::new);
+                for device_callbacks in recv_callbacks_raw.iter_mut() {
+                    device_callbacks.extend(launch.recv_callbacks());
+                }
+            }
+        }
+
         // Create pointer arrays for send callbacks
         let send_callbacks_ptrs: Vec<*mut PJRT_SendCallbackInfo> = send_callbacks_raw
             .iter_mut()
@@ -538,13 +858,17 @@ impl ExecuteOptionsRaw {
 
         // Populate the raw options
         raw.launch_id = options.launch_id;
-        raw.non_donatable_input_indices = options.non_donatable_input_indices.as_ptr();
-        raw.num_non_donatable_input_indices = options.non_donatable_input_indices.len();
+        raw.non_donatable_input_indices = non_donatable_input_indices.as_ptr();
+        raw.num_non_donatable_input_indices = non_donatable_input_indices.len();
 
         if let Some(ref location) = options.call_location {
             raw.call_location = location.as_ptr();
         }
 
+        if let Some(context) = &options.context {
+            raw.context = context.ptr;
+        }
+
         if !options.task_ids.is_empty() {
             raw.num_tasks = options.task_ids.len();
             raw.task_ids = options.task_ids.as_ptr() as *mut i32;
@@ -583,8 +907,8 @@ impl ExecuteOptionsRaw {
     }
 }
 
-impl<'a> From<&'a ExecuteOptions<'a>> for PJRT_ExecuteOptions {
-    fn from(v: &'a ExecuteOptions<'a>) -> Self {
+impl From<&ExecuteOptions<'_>> for PJRT_ExecuteOptions {
+    fn from(v: &ExecuteOptions<'_>) -> Self {
         let mut options = PJRT_ExecuteOptions::new();
         options.launch_id = v.launch_id;
         options.non_donatable_input_indices = v.non_donatable_input_indices.as_ptr();
@@ -594,6 +918,10 @@ impl<'a> From<&'a ExecuteOptions<'a>> for PJRT_ExecuteOptions {
             options.call_location = location.as_ptr();
         }
 
+        if let Some(context) = &v.context {
+            options.context = context.ptr;
+        }
+
         if !v.task_ids.is_empty() {
             options.num_tasks = v.task_ids.len();
             options.task_ids = v.task_ids.as_ptr() as *mut i32;
@@ -601,7 +929,7 @@ impl<'a> From<&'a ExecuteOptions<'a>> for PJRT_ExecuteOptions {
         }
 
         // Note: send/recv callbacks require ExecuteOptionsRaw to be kept alive
-        // This simple From impl doesn't support callbacks - use ExecuteOptionsRaw::new() instead
+        // This simple From impl doesn't support callbacks - use ExecuteOptionsRaw::for_launch() instead
 
         options
     }
@@ -640,19 +968,10 @@ where
     T: ExecutionInputs,
 {
     pub fn new(loaded_executable: &'a LoadedExecutable, inputs: T) -> Self {
-        let options = ExecuteOptions {
-            launch_id: 0,
-            non_donatable_input_indices: inputs.non_donatable_input_indices(),
-            call_location: None,
-            task_ids: vec![],
-            incarnation_ids: vec![],
-            send_callbacks: vec![],
-            recv_callbacks: vec![],
-        };
         Self {
             loaded_executable,
             inputs,
-            options,
+            options: ExecuteOptions::new(),
         }
     }
 
@@ -666,13 +985,59 @@ where
         self
     }
 
+    /// See [`ExecuteOptions::context`].
+    pub fn context(mut self, context: Arc<ExecuteContext>) -> Self {
+        self.options = self.options.context(context);
+        self
+    }
+
+    /// See [`ExecuteOptions::validate_inputs`].
+    pub fn validate_inputs(mut self, validate: bool) -> Self {
+        self.options = self.options.validate_inputs(validate);
+        self
+    }
+
+    /// See [`ExecuteOptions::donate`].
+    pub fn donate(mut self, index: usize) -> Self {
+        self.options = self.options.donate(index);
+        self
+    }
+
+    /// See [`ExecuteOptions::keep`].
+    pub fn keep(mut self, index: usize) -> Self {
+        self.options = self.options.keep(index);
+        self
+    }
+
+    /// See [`ExecuteOptions::deadline`].
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.options = self.options.deadline(deadline);
+        self
+    }
+
+    /// See [`ExecuteOptions::cancellation_token`].
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.options = self.options.cancellation_token(token);
+        self
+    }
+
+    /// Launches on `device` only, with inputs for that device.
+    ///
+    /// See [`LoadedExecutable::call_execute_on_device`].
+    pub fn on_device(self, device: &'a Device) -> DeviceExecution<'a, T> {
+        DeviceExecution {
+            execution: self,
+            device,
+        }
+    }
+
     pub async fn run(self) -> Result<Vec<Vec<Buffer>>> {
         let (events, outputs) = self
             .loaded_executable
             .call_execute(self.inputs, &self.options)?;
-        for event in events {
-            event.await?;
-        }
+        self.loaded_executable
+            .wait_for_completion(events, &self.options, None)
+            .await?;
         Ok(outputs)
     }
 
@@ -680,9 +1045,52 @@ where
         let (events, outputs) = self
             .loaded_executable
             .call_execute(self.inputs, &self.options)?;
-        for event in events {
-            event.wait()?;
-        }
+        self.loaded_executable
+            .wait_for_completion_sync(events, &self.options, None)?;
+        Ok(outputs)
+    }
+}
+
+/// An [`Execution`] launched on a single addressable device.
+///
+/// Created by [`Execution::on_device`]. Outputs are returned as a flat list
+/// for that device.
+pub struct DeviceExecution<'a, T> {
+    execution: Execution<'a, T>,
+    device: &'a Device,
+}
+
+impl<'a, T> DeviceExecution<'a, T>
+where
+    T: ExecutionInputs,
+{
+    pub fn device(&self) -> &'a Device {
+        self.device
+    }
+
+    pub async fn run(self) -> Result<Vec<Buffer>> {
+        let Execution {
+            loaded_executable,
+            inputs,
+            options,
+        } = self.execution;
+        let (event, outputs) =
+            loaded_executable.call_execute_on_device(inputs, &options, self.device)?;
+        loaded_executable
+            .wait_for_completion(vec![event], &options, Some(self.device))
+            .await?;
+        Ok(outputs)
+    }
+
+    pub fn run_sync(self) -> Result<Vec<Buffer>> {
+        let Execution {
+            loaded_executable,
+            inputs,
+            options,
+        } = self.execution;
+        let (event, outputs) =
+            loaded_executable.call_execute_on_device(inputs, &options, self.device)?;
+        loaded_executable.wait_for_completion_sync(vec![event], &options, Some(self.device))?;
         Ok(outputs)
     }
 }
@@ -700,29 +1108,60 @@ where
 /// - `[[Buffer; A]; D]`: 2D array of buffers (multi-device)
 /// - `Vec<Buffer>`: Vector of buffers
 /// - `Vec<Vec<Buffer>>`: Vector of vectors (multi-device)
+/// - `&Buffer`, `&[Buffer]`, `Vec<&Buffer>`, `&[Vec<Buffer>]` and
+///   `Vec<Vec<&Buffer>>`: Borrowed buffers
+///
+/// # Donation
+///
+/// Owned inputs are consumed by the execution, so buffers the executable
+/// donates to its outputs cannot be used afterwards. Borrowed inputs report
+/// every argument as non-donatable, which every execute entry point uses to
+/// keep them by default; [`ExecuteOptions::donate`] and [`Execution::donate`]
+/// opt an argument back in. Borrowed buffers that do get donated are marked
+/// as such, and later operations on their data fail with
+/// `Error::BufferDonated`.
 pub trait ExecutionInputs {
     fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>>;
     fn non_donatable_input_indices(&self) -> Vec<i64> {
         vec![]
     }
+    /// Returns the input buffers per device, used for input validation.
+    ///
+    /// The default returns `None`, which limits validation to device and
+    /// argument counts.
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        None
+    }
 }
 
 impl ExecutionInputs for () {
     fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
         vec![vec![]]
     }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![vec![]])
+    }
 }
 
 impl ExecutionInputs for Buffer {
     fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
         vec![vec![self.ptr]]
     }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![vec![self]])
+    }
 }
 
 impl<const A: usize> ExecutionInputs for [Buffer; A] {
     fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
         vec![self.iter().map(|b| b.ptr).collect()]
     }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![self.iter().collect()])
+    }
 }
 
 impl<const D: usize, const A: usize> ExecutionInputs for [[Buffer; A]; D] {
@@ -733,12 +1172,20 @@ impl<const D: usize, const A: usize> ExecutionInputs for [[Buffer; A]; D] {
         }
         buffer_refs
     }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(self.iter().map(|array| array.iter().collect()).collect())
+    }
 }
 
 impl ExecutionInputs for Vec<Buffer> {
     fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
         vec![self.iter().map(|b| b.ptr).collect()]
     }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![self.iter().collect()])
+    }
 }
 
 impl ExecutionInputs for Vec<Vec<Buffer>> {
@@ -759,4 +1206,90 @@ impl ExecutionInputs for Vec<Vec<Buffer>> {
             .map(|buffers| buffers.iter().map(|b| b.ptr).collect())
             .collect()
     }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(
+            self.iter()
+                .map(|buffers| buffers.iter().collect())
+                .collect(),
+        )
+    }
+}
+
+impl ExecutionInputs for &Buffer {
+    fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
+        vec![vec![self.ptr]]
+    }
+
+    fn non_donatable_input_indices(&self) -> Vec<i64> {
+        vec![0]
+    }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![vec![*self]])
+    }
+}
+
+impl ExecutionInputs for &[Buffer] {
+    fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
+        vec![self.iter().map(|b| b.ptr).collect()]
+    }
+
+    fn non_donatable_input_indices(&self) -> Vec<i64> {
+        (0..self.len() as i64).collect()
+    }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![self.iter().collect()])
+    }
+}
+
+impl ExecutionInputs for Vec<&Buffer> {
+    fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
+        vec![self.iter().map(|b| b.ptr).collect()]
+    }
+
+    fn non_donatable_input_indices(&self) -> Vec<i64> {
+        (0..self.len() as i64).collect()
+    }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(vec![self.clone()])
+    }
+}
+
+impl ExecutionInputs for &[Vec<Buffer>] {
+    fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
+        self.iter()
+            .map(|buffers| buffers.iter().map(|b| b.ptr).collect())
+            .collect()
+    }
+
+    fn non_donatable_input_indices(&self) -> Vec<i64> {
+        (0..self.first().map_or(0, Vec::len) as i64).collect()
+    }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(
+            self.iter()
+                .map(|buffers| buffers.iter().collect())
+                .collect(),
+        )
+    }
+}
+
+impl ExecutionInputs for Vec<Vec<&Buffer>> {
+    fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
+        self.iter()
+            .map(|buffers| buffers.iter().map(|b| b.ptr).collect())
+            .collect()
+    }
+
+    fn non_donatable_input_indices(&self) -> Vec<i64> {
+        (0..self.first().map_or(0, Vec::len) as i64).collect()
+    }
+
+    fn buffers(&self) -> Option<Vec<Vec<&Buffer>>> {
+        Some(self.clone())
+    }
 }
@@ -25,6 +25,9 @@
 //!
 //! // Add user data to execution context
 //! ffi_ext.add_user_data(&execute_context, type_id, data_ptr)?;
+//!
+//! // Or let the context own a typed Rust value
+//! execute_context.insert_user_data(type_id, my_state)?;
 //! ```
 
 use std::ffi::CString;
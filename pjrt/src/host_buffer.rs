@@ -194,6 +194,15 @@ impl<T: Type> TypedHostBuffer<T> {
         &self.data
     }
 
+    /// Returns the raw bytes of the data.
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: element types are plain numeric values without padding,
+        // and the byte slice covers exactly the elements' storage.
+        unsafe {
+            std::slice::from_raw_parts(self.data.as_ptr() as *const u8, self.data.len() * T::SIZE)
+        }
+    }
+
     pub fn dims(&self) -> &[i64] {
         &self.dims
     }
@@ -414,6 +423,26 @@ impl HostBuffer {
         }
     }
 
+    /// Returns the raw bytes of the data.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::BF16(buf) => buf.as_bytes(),
+            Self::F16(buf) => buf.as_bytes(),
+            Self::F32(buf) => buf.as_bytes(),
+            Self::F64(buf) => buf.as_bytes(),
+            Self::I8(buf) => buf.as_bytes(),
+            Self::I16(buf) => buf.as_bytes(),
+            Self::I32(buf) => buf.as_bytes(),
+            Self::I64(buf) => buf.as_bytes(),
+            Self::U8(buf) => buf.as_bytes(),
+            Self::U16(buf) => buf.as_bytes(),
+            Self::U32(buf) => buf.as_bytes(),
+            Self::U64(buf) => buf.as_bytes(),
+            Self::C64(buf) => buf.as_bytes(),
+            Self::C128(buf) => buf.as_bytes(),
+        }
+    }
+
     /// Read the buffer data as f32 values
     ///
     /// Returns the data as a slice of f32 if the buffer contains F32 data.
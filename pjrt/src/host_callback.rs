@@ -0,0 +1,368 @@
+//! Closure-based Host Send/Recv Callbacks
+//!
+//! This module backs `ExecuteOptions::on_send` and `ExecuteOptions::on_recv`.
+//! The options hold each registered closure in an `Arc`. Every launch
+//! collects the handlers into a [`LaunchCallbacks`], which owns the state the
+//! `extern "C"` trampolines point to and is kept alive by the launch's
+//! completion events, so dropping the options or an unfinished `execute`
+//! future never frees memory the runtime may still call into.
+//!
+//! The trampolines never unwind into the runtime: closure panics are caught
+//! and, like closure errors, reported through the runtime's `CallbackError`.
+
+use std::any::Any;
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pjrt_sys::{
+    PJRT_Chunk, PJRT_CopyToDeviceStream, PJRT_Error, PJRT_RecvCallbackInfo, PJRT_SendCallbackInfo,
+};
+
+use crate::{
+    Api, CallbackError, Chunk, CopyToDeviceStream, ErrorCode, PrimitiveType, Result,
+    TransferMetadata,
+};
+
+pub(crate) type SendFn = dyn FnMut(Chunk, TransferMetadata) -> Result<()> + Send;
+pub(crate) type RecvFn = dyn FnMut(&TransferMetadata, &CopyToDeviceStream) -> Result<()> + Send;
+
+/// A send closure registered for a channel.
+pub(crate) struct SendHandler {
+    channel_id: i64,
+    callback: Mutex<Box<SendFn>>,
+}
+
+impl SendHandler {
+    pub(crate) fn new(channel_id: i64, callback: Box<SendFn>) -> Self {
+        Self {
+            channel_id,
+            callback: Mutex::new(callback),
+        }
+    }
+
+    fn on_transfer(&self, data: Vec<u8>, total: usize) -> Result<()> {
+        let metadata = TransferMetadata::new(vec![total as i64], PrimitiveType::U8);
+        let mut callback = lock(&self.callback);
+        (*callback)(Chunk::new(data), metadata)
+    }
+}
+
+impl std::fmt::Debug for SendHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SendHandler")
+            .field("channel_id", &self.channel_id)
+            .finish()
+    }
+}
+
+/// A recv closure registered for a channel.
+pub(crate) struct RecvHandler {
+    channel_id: i64,
+    callback: Mutex<Box<RecvFn>>,
+}
+
+impl RecvHandler {
+    pub(crate) fn new(channel_id: i64, callback: Box<RecvFn>) -> Self {
+        Self {
+            channel_id,
+            callback: Mutex::new(callback),
+        }
+    }
+
+    fn on_stream(&self, stream: &CopyToDeviceStream) -> Result<()> {
+        let total = stream.total_bytes()?;
+        let metadata = TransferMetadata::new(vec![total], PrimitiveType::U8);
+        let mut callback = lock(&self.callback);
+        (*callback)(&metadata, stream)
+    }
+}
+
+impl std::fmt::Debug for RecvHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecvHandler")
+            .field("channel_id", &self.channel_id)
+            .finish()
+    }
+}
+
+/// The `user_arg` handed to the send trampoline for one device and handler.
+struct SendSlot {
+    handler: Arc<SendHandler>,
+    /// Chunks of the current transfer, so concurrent devices never share a
+    /// buffer.
+    pending: Mutex<Vec<u8>>,
+}
+
+impl SendSlot {
+    fn new(handler: Arc<SendHandler>) -> Self {
+        Self {
+            handler,
+            pending: Mutex::new(vec![]),
+        }
+    }
+
+    /// Accumulates `data` and invokes the closure once the transfer is done.
+    fn on_chunk(&self, data: Vec<u8>, total: usize, done: bool) -> Result<()> {
+        let data = {
+            let mut pending = lock(&self.pending);
+            if pending.is_empty() && done {
+                data
+            } else {
+                pending.extend_from_slice(&data);
+                if !done {
+                    return Ok(());
+                }
+                std::mem::take(&mut *pending)
+            }
+        };
+        self.handler.on_transfer(data, total)
+    }
+}
+
+/// The `user_arg` handed to the recv trampoline.
+struct RecvSlot {
+    handler: Arc<RecvHandler>,
+    /// Needed to wrap the streams the runtime hands over.
+    api: Api,
+}
+
+/// The closure-based callbacks of one launch.
+///
+/// The raw callback infos point into the slots, which are never moved or
+/// resized after construction. The launch keeps its `Arc` alive until all of
+/// its completion events are ready.
+pub(crate) struct LaunchCallbacks {
+    num_devices: usize,
+    /// Outer vec is per device, inner vec is per send handler.
+    send_slots: Vec<Vec<SendSlot>>,
+    recv_slots: Vec<RecvSlot>,
+}
+
+impl LaunchCallbacks {
+    /// Returns the callbacks for a launch on `num_devices` devices, or `None`
+    /// if there are no handlers.
+    pub(crate) fn new(
+        send_handlers: &[Arc<SendHandler>],
+        recv_handlers: &[Arc<RecvHandler>],
+        api: &Api,
+        num_devices: usize,
+    ) -> Option<Arc<Self>> {
+        if send_handlers.is_empty() && recv_handlers.is_empty() {
+            return None;
+        }
+        let send_slots = if send_handlers.is_empty() {
+            vec![]
+        } else {
+            (0..num_devices)
+                .map(|_| send_handlers.iter().cloned().map(SendSlot::new).collect())
+                .collect()
+        };
+        let recv_slots = recv_handlers
+            .iter()
+            .map(|handler| RecvSlot {
+                handler: handler.clone(),
+                api: api.clone(),
+            })
+            .collect();
+        Some(Arc::new(Self {
+            num_devices,
+            send_slots,
+            recv_slots,
+        }))
+    }
+
+    /// Returns the number of devices of the launch.
+    pub(crate) fn num_devices(&self) -> usize {
+        self.num_devices
+    }
+
+    /// Returns whether there are send callbacks.
+    pub(crate) fn has_sends(&self) -> bool {
+        !self.send_slots.is_empty()
+    }
+
+    /// Returns whether there are recv callbacks.
+    pub(crate) fn has_recvs(&self) -> bool {
+        !self.recv_slots.is_empty()
+    }
+
+    /// Returns the raw send callback infos for the device at `device_index`.
+    pub(crate) fn send_callbacks(
+        &self,
+        device_index: usize,
+    ) -> impl Iterator<Item = PJRT_SendCallbackInfo> + '_ {
+        self.send_slots
+            .get(device_index)
+            .into_iter()
+            .flatten()
+            .map(|slot| {
+                let mut info = PJRT_SendCallbackInfo::new();
+                info.channel_id = slot.handler.channel_id;
+                info.user_arg = slot as *const SendSlot as *mut c_void;
+                info.send_callback = Some(send_trampoline);
+                info
+            })
+    }
+
+    /// Returns the raw recv callback infos, shared by all devices.
+    pub(crate) fn recv_callbacks(&self) -> impl Iterator<Item = PJRT_RecvCallbackInfo> + '_ {
+        self.recv_slots.iter().map(|slot| {
+            let mut info = PJRT_RecvCallbackInfo::new();
+            info.channel_id = slot.handler.channel_id;
+            info.user_arg = slot as *const RecvSlot as *mut c_void;
+            info.recv_callback = Some(recv_trampoline);
+            info
+        })
+    }
+}
+
+impl std::fmt::Debug for LaunchCallbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LaunchCallbacks")
+            .field("num_devices", &self.num_devices)
+            .field("num_send_ops", &self.send_slots.first().map_or(0, Vec::len))
+            .field("num_recv_ops", &self.recv_slots.len())
+            .finish()
+    }
+}
+
+extern "C" fn send_trampoline(
+    chunk: *mut PJRT_Chunk,
+    callback_error: *mut CallbackError,
+    total_size_in_bytes: usize,
+    done: bool,
+    user_arg: *mut c_void,
+) -> *mut PJRT_Error {
+    // Wrap in catch_unwind to prevent panicking across the FFI boundary (UB).
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: user_arg is a SendSlot of a LaunchCallbacks, which the
+        // launch keeps alive until it completes; the runtime hands over
+        // `chunk`.
+        let (slot, data) = unsafe {
+            let slot = &*(user_arg as *const SendSlot);
+            (slot, Chunk::from_raw(chunk).into_vec())
+        };
+        slot.on_chunk(data, total_size_in_bytes, done)
+    }));
+    let (code, message) = match result {
+        Ok(Ok(())) => return std::ptr::null_mut(),
+        Ok(Err(err)) => (err.code(), err.to_string()),
+        Err(payload) => (ErrorCode::Internal, panic_message(&*payload)),
+    };
+    // SAFETY: the runtime passes a valid PJRT_CallbackError, which copies
+    // `message` into the returned error.
+    match unsafe { callback_error.as_ref() }.and_then(|f| *f) {
+        Some(make_error) => unsafe {
+            make_error(code as _, message.as_ptr() as *const _, message.len())
+        },
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Recv callbacks cannot report errors; the stream is destroyed either way,
+/// which fails the transfer on the device side.
+extern "C" fn recv_trampoline(stream: *mut PJRT_CopyToDeviceStream, user_arg: *mut c_void) {
+    // Wrap in catch_unwind to prevent panicking across the FFI boundary (UB).
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: user_arg is a RecvSlot of a LaunchCallbacks, which the
+        // launch keeps alive until it completes.
+        let slot = unsafe { &*(user_arg as *const RecvSlot) };
+        // The callback owns the stream and must destroy it, which the
+        // wrapper does on drop.
+        let stream = CopyToDeviceStream::wrap(&slot.api, stream);
+        let _ = slot.handler.on_stream(&stream);
+    }));
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panicking closure poisons its mutex; later transfers may still run.
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    format!("host callback panicked: {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let payload: Box<dyn Any + Send> = Box::new("boom");
+        assert_eq!(panic_message(&*payload), "host callback panicked: boom");
+        let payload: Box<dyn Any + Send> = Box::new(String::from("bang"));
+        assert_eq!(panic_message(&*payload), "host callback panicked: bang");
+        let payload: Box<dyn Any + Send> = Box::new(1);
+        assert_eq!(
+            panic_message(&*payload),
+            "host callback panicked: unknown panic"
+        );
+    }
+
+    #[test]
+    fn test_send_slot_reassembles_chunks() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        let handler = SendHandler::new(
+            7,
+            Box::new(
+                move |chunk: Chunk, metadata: TransferMetadata| -> Result<()> {
+                    sink.lock().unwrap().push((chunk.into_vec(), metadata.dims));
+                    Ok(())
+                },
+            ),
+        );
+        let slot = SendSlot::new(Arc::new(handler));
+
+        slot.on_chunk(vec![1, 2], 4, false).unwrap();
+        assert!(received.lock().unwrap().is_empty());
+        slot.on_chunk(vec![3, 4], 4, true).unwrap();
+        slot.on_chunk(vec![5], 1, true).unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received[0], (vec![1, 2, 3, 4], vec![4]));
+        assert_eq!(received[1], (vec![5], vec![1]));
+    }
+
+    #[test]
+    fn test_launch_callbacks_slots_per_device() {
+        let api = unsafe { Api::empty_for_testing() };
+        let handler = Arc::new(SendHandler::new(
+            3,
+            Box::new(|_: Chunk, _: TransferMetadata| -> Result<()> { Ok(()) }),
+        ));
+        let callbacks = LaunchCallbacks::new(&[handler.clone()], &[], &api, 2).unwrap();
+        assert!(callbacks.has_sends());
+        assert!(!callbacks.has_recvs());
+
+        let first: Vec<_> = callbacks.send_callbacks(0).collect();
+        let second: Vec<_> = callbacks.send_callbacks(1).collect();
+        assert_eq!(first[0].channel_id, 3);
+        assert_ne!(first[0].user_arg, second[0].user_arg);
+        assert_eq!(
+            callbacks.send_callbacks(0).next().unwrap().user_arg,
+            first[0].user_arg
+        );
+        assert_eq!(callbacks.send_callbacks(2).count(), 0);
+
+        // Each launch holds the handler until it is dropped.
+        assert_eq!(Arc::strong_count(&handler), 3);
+        drop(callbacks);
+        assert_eq!(Arc::strong_count(&handler), 1);
+    }
+
+    #[test]
+    fn test_launch_callbacks_none_without_handlers() {
+        let api = unsafe { Api::empty_for_testing() };
+        assert!(LaunchCallbacks::new(&[], &[], &api, 2).is_none());
+    }
+}
@@ -0,0 +1,202 @@
+//! Named Execution Inputs and Outputs
+//!
+//! This module provides `IoNames`, which maps between the positional arguments
+//! and outputs of an executable and user-facing names. Names can come from:
+//!
+//! - The `jax.arg_info` / `jax.result_info` attributes of `func.func @main` in
+//!   a textual MLIR program, as produced by JAX exports
+//! - The parameter names recorded in an HLO module
+//! - An explicit list via `IoNames::new`
+//!
+//! Arguments or outputs without a recorded name fall back to `arg{i}` and
+//! `output{i}` respectively.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let names = IoNames::from_program(&program)?;
+//! let loaded = client.compile(&program, CompileOptions::new())?;
+//!
+//! let mut inputs = HashMap::new();
+//! inputs.insert("x".to_string(), x);
+//! inputs.insert("scale".to_string(), scale);
+//!
+//! let outputs = loaded.execute_named_sync(&names, inputs, &ExecuteOptions::new())?;
+//! let out = &outputs["out"];
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use pjrt_sys::protos::xla::HloModuleProto;
+use prost::Message;
+
+use crate::{Buffer, Error, ExecutableSignature, Program, ProgramFormat, Result};
+
+/// Names of an executable's arguments and outputs, in positional order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoNames {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl IoNames {
+    /// Creates names from explicit lists.
+    ///
+    /// Returns an error if either list contains duplicates.
+    pub fn new<I, O>(inputs: I, outputs: O) -> Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+        O: IntoIterator,
+        O::Item: Into<String>,
+    {
+        let inputs: Vec<String> = inputs.into_iter().map(Into::into).collect();
+        let outputs: Vec<String> = outputs.into_iter().map(Into::into).collect();
+        check_unique("input", &inputs)?;
+        check_unique("output", &outputs)?;
+        Ok(Self { inputs, outputs })
+    }
+
+    /// Reads names from a program.
+    ///
+    /// For MLIR programs the `jax.arg_info` and `jax.result_info` attributes of
+    /// `func.func @main` are used; other argument attributes such as
+    /// `mhlo.sharding` are ignored. For HLO programs the entry computation's
+    /// parameter names are used.
+    pub fn from_program(program: &Program) -> Result<Self> {
+        match program.format() {
+            ProgramFormat::MLIR => {
+                let metadata = program.metadata()?;
+                let inputs = metadata.arguments.iter().enumerate().map(|(i, arg)| {
+                    arg.string_attribute("jax.arg_info")
+                        .filter(|name| !name.is_empty())
+                        .or_else(|| arg.name.clone())
+                        .unwrap_or_else(|| format!("arg{i}"))
+                });
+                let outputs = metadata.results.iter().enumerate().map(|(i, result)| {
+                    result
+                        .string_attribute("jax.result_info")
+                        .filter(|name| !name.is_empty())
+                        .unwrap_or_else(|| format!("output{i}"))
+                });
+                Self::new(inputs, outputs)
+            }
+            ProgramFormat::HLO => {
+                let module = HloModuleProto::decode(program.code())?;
+                Self::from_signature(&ExecutableSignature::from_hlo_module(&module, &[])?)
+            }
+        }
+    }
+
+    /// Reads names from an executable signature.
+    ///
+    /// Outputs are not named in HLO, so they are always `output{i}`.
+    pub fn from_signature(signature: &ExecutableSignature) -> Result<Self> {
+        let inputs = signature
+            .parameters()
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p.name.clone().unwrap_or_else(|| format!("arg{i}")));
+        let outputs = (0..signature.num_outputs()).map(|i| format!("output{i}"));
+        Self::new(inputs, outputs)
+    }
+
+    /// Returns the input names in argument order.
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    /// Returns the output names in output order.
+    pub fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    /// Orders named inputs positionally.
+    ///
+    /// Returns `Error::MissingInput` if a name has no buffer and
+    /// `Error::UnexpectedInput` if a buffer has an unknown name.
+    pub fn bind(&self, mut inputs: HashMap<String, Buffer>) -> Result<Vec<Buffer>> {
+        let mut ordered = Vec::with_capacity(self.inputs.len());
+        for name in &self.inputs {
+            let buffer = inputs
+                .remove(name)
+                .ok_or_else(|| Error::MissingInput(name.clone()))?;
+            ordered.push(buffer);
+        }
+        if let Some(extra) = inputs.into_keys().min() {
+            return Err(Error::UnexpectedInput(extra));
+        }
+        Ok(ordered)
+    }
+
+    /// Attaches output names to positional outputs.
+    pub fn name_outputs(&self, outputs: Vec<Buffer>) -> Result<HashMap<String, Buffer>> {
+        if outputs.len() != self.outputs.len() {
+            return Err(Error::InvalidArgument(format!(
+                "expected {} outputs, got {}",
+                self.outputs.len(),
+                outputs.len()
+            )));
+        }
+        Ok(self.outputs.iter().cloned().zip(outputs).collect())
+    }
+}
+
+fn check_unique(kind: &str, names: &[String]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name.as_str()) {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate {kind} name `{name}`"
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_names_new() {
+        let names = IoNames::new(["x", "y"], ["out"]).unwrap();
+        assert_eq!(names.inputs(), &["x".to_string(), "y".to_string()]);
+        assert_eq!(names.outputs(), &["out".to_string()]);
+    }
+
+    #[test]
+    fn test_io_names_duplicate() {
+        let err = IoNames::new(["x", "x"], Vec::<String>::new()).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(msg) if msg.contains("`x`")));
+    }
+
+    #[test]
+    fn test_io_names_from_mlir_program() {
+        let code = r#"
+module @jit_f {
+  func.func public @main(%arg0: tensor<2xf32> {jax.arg_info = "x", mhlo.sharding = "{replicated}"}, %arg1: tensor<f32>) -> (tensor<2xf32> {jax.result_info = "out"}, tensor<f32> {jax.result_info = ""}) {
+    return %arg0, %arg1 : tensor<2xf32>, tensor<f32>
+  }
+}
+"#;
+        let program = Program::new(ProgramFormat::MLIR, code);
+        let names = IoNames::from_program(&program).unwrap();
+        assert_eq!(names.inputs(), &["x".to_string(), "arg1".to_string()]);
+        assert_eq!(names.outputs(), &["out".to_string(), "output1".to_string()]);
+    }
+
+    #[test]
+    fn test_io_names_name_outputs_count_mismatch() {
+        let names = IoNames::new(["x"], ["out"]).unwrap();
+        let err = names.name_outputs(vec![]).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn test_io_names_bind_missing_input() {
+        let names = IoNames::new(["x"], ["out"]).unwrap();
+        let err = names.bind(HashMap::new()).unwrap_err();
+        assert!(matches!(err, Error::MissingInput(name) if name == "x"));
+    }
+}
@@ -1,4 +1,26 @@
+//! Key-Value Stores
+//!
+//! This module provides the `KeyValueStore` trait used by PJRT clients to
+//! exchange coordination data between processes, the glue that exposes it
+//! through the C API callbacks, and two ready-made stores:
+//!
+//! - `InMemoryKeyValueStore`: shared between clients of a single process,
+//!   e.g. simulated processes in tests.
+//! - `FileKeyValueStore`: backed by a directory shared between processes,
+//!   e.g. on a local disk or network file system.
+//!
+//! Blocking gets on both honour their timeout and fail with
+//! `Error::KeyValueTimeout`, reported to PJRT as `DEADLINE_EXCEEDED`. Both
+//! also support keys that expire, put with `KeyValueStore::put_with_ttl`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
 use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use pjrt_sys::{
     PJRT_Error, PJRT_Error_Code, PJRT_Error_Code_PJRT_Error_Code_INVALID_ARGUMENT,
@@ -6,7 +28,7 @@ use pjrt_sys::{
     PJRT_KeyValuePutCallback_Args, PJRT_KeyValueTryGetCallback_Args,
 };
 
-use crate::Result;
+use crate::{Error, ErrorCode, Result};
 
 // ---------------------------------------------------------------------------
 // Value allocation helpers for returning binary data through the C API
@@ -91,7 +113,7 @@ unsafe fn create_callback_error(
 ///
 /// This function is called by the PJRT runtime.
 /// - `args` must be a valid, non-null pointer to `PJRT_KeyValueGetCallback_Args`
-/// - `args.user_arg` must point to a valid `&dyn KeyValueStore` reference
+/// - `args.user_arg` must point to a valid `SharedKeyValueStore`
 /// - `args.callback_error` must be a valid pointer to the error callback function
 pub(crate) unsafe extern "C" fn kv_get_callback(
     args: *mut PJRT_KeyValueGetCallback_Args,
@@ -104,7 +126,7 @@ pub(crate) unsafe extern "C" fn kv_get_callback(
     };
 
     // SAFETY-001 fix: Check for null user_arg pointer
-    let Some(store) = (unsafe { (args.user_arg as *mut &dyn KeyValueStore).as_mut() }) else {
+    let Some(store) = (unsafe { (args.user_arg as *const SharedKeyValueStore).as_ref() }) else {
         return unsafe {
             create_callback_error(
                 args.callback_error,
@@ -143,7 +165,7 @@ pub(crate) unsafe extern "C" fn kv_get_callback(
 ///
 /// This function is called by the PJRT runtime.
 /// - `args` must be a valid, non-null pointer to `PJRT_KeyValuePutCallback_Args`
-/// - `args.user_arg` must point to a valid `&dyn KeyValueStore` reference
+/// - `args.user_arg` must point to a valid `SharedKeyValueStore`
 /// - `args.callback_error` must be a valid pointer to the error callback function
 pub(crate) unsafe extern "C" fn kv_put_callback(
     args: *mut PJRT_KeyValuePutCallback_Args,
@@ -154,7 +176,7 @@ pub(crate) unsafe extern "C" fn kv_put_callback(
     };
 
     // SAFETY-001 fix: Check for null user_arg pointer
-    let Some(store) = (unsafe { (args.user_arg as *mut &dyn KeyValueStore).as_mut() }) else {
+    let Some(store) = (unsafe { (args.user_arg as *const SharedKeyValueStore).as_ref() }) else {
         return unsafe {
             create_callback_error(
                 args.callback_error,
@@ -188,7 +210,7 @@ pub(crate) unsafe extern "C" fn kv_put_callback(
 ///
 /// This function is called by the PJRT runtime.
 /// - `args` must be a valid, non-null pointer to `PJRT_KeyValueTryGetCallback_Args`
-/// - `args.user_arg` must point to a valid `&dyn KeyValueStore` reference
+/// - `args.user_arg` must point to a valid `SharedKeyValueStore`
 /// - `args.callback_error` must be a valid pointer to the error callback function
 pub(crate) unsafe extern "C" fn kv_try_get_callback(
     args: *mut PJRT_KeyValueTryGetCallback_Args,
@@ -199,7 +221,7 @@ pub(crate) unsafe extern "C" fn kv_try_get_callback(
     };
 
     // SAFETY-001 fix: Check for null user_arg pointer
-    let Some(store) = (unsafe { (args.user_arg as *mut &dyn KeyValueStore).as_mut() }) else {
+    let Some(store) = (unsafe { (args.user_arg as *const SharedKeyValueStore).as_ref() }) else {
         return unsafe {
             create_callback_error(
                 args.callback_error,
@@ -242,6 +264,30 @@ pub(crate) unsafe extern "C" fn kv_try_get_callback(
     }
 }
 
+/// A key-value store shared with the callbacks of a client.
+pub(crate) type SharedKeyValueStore = Arc<dyn KeyValueStore + Send + Sync>;
+
+/// The store behind a client's key-value callbacks.
+///
+/// Kept alive by the client, so that the `user_arg` pointer handed to PJRT
+/// stays valid for as long as PJRT may call back.
+pub(crate) struct CallbackStore {
+    /// The `user_arg` of the callbacks, boxed for a stable thin pointer.
+    store: Box<SharedKeyValueStore>,
+}
+
+impl CallbackStore {
+    pub(crate) fn new(store: SharedKeyValueStore) -> Self {
+        Self {
+            store: Box::new(store),
+        }
+    }
+
+    pub(crate) fn user_arg(&self) -> *mut std::ffi::c_void {
+        &*self.store as *const SharedKeyValueStore as *mut _
+    }
+}
+
 /// A key-value store trait for distributed PJRT coordination.
 ///
 /// Values are opaque byte slices (`Vec<u8>` / `&[u8]`) rather than strings
@@ -257,4 +303,1146 @@ pub trait KeyValueStore {
     /// Returns `Ok(None)` if the key does not exist.
     /// Returns `Err` for other errors.
     fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+    /// Remove `key`, returning whether it existed.
+    ///
+    /// Optional; PJRT never deletes keys. The default returns
+    /// `Error::Unimplemented`.
+    fn delete(&self, key: &str) -> Result<bool> {
+        let _ = key;
+        Err(Error::Unimplemented)
+    }
+    /// List the entries whose key starts with `prefix`, sorted by key.
+    ///
+    /// Optional; PJRT never lists keys. The default returns
+    /// `Error::Unimplemented`.
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let _ = prefix;
+        Err(Error::Unimplemented)
+    }
+    /// Store a value under `key` that expires `ttl` after it is put.
+    ///
+    /// Expired keys behave as if they were deleted. A later `put` of the key
+    /// replaces the value and its expiry. Optional; PJRT never sets a TTL.
+    /// The default returns `Error::Unimplemented`.
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let _ = (key, value, ttl);
+        Err(Error::Unimplemented)
+    }
+}
+
+impl<T: KeyValueStore + ?Sized> KeyValueStore for &T {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        (**self).get(key, timeout_in_ms)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        (**self).put(key, value)
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        (**self).try_get(key)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        (**self).delete(key)
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        (**self).list_prefix(prefix)
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        (**self).put_with_ttl(key, value, ttl)
+    }
+}
+
+impl<T: KeyValueStore + ?Sized> KeyValueStore for Arc<T> {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        (**self).get(key, timeout_in_ms)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        (**self).put(key, value)
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        (**self).try_get(key)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        (**self).delete(key)
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        (**self).list_prefix(prefix)
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        (**self).put_with_ttl(key, value, ttl)
+    }
+}
+
+/// Returns the instant a get with `timeout_in_ms` gives up. Non-positive
+/// timeouts check the store once.
+fn get_deadline(timeout_in_ms: i32) -> Instant {
+    Instant::now() + Duration::from_millis(timeout_in_ms.max(0) as u64)
+}
+
+/// A value held in memory, with the instant it expires if it was put with a
+/// TTL.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyValueEntry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+impl KeyValueEntry {
+    /// Creates an entry expiring `ttl` from now, or never if `ttl` is `None`
+    /// or too large to represent.
+    pub(crate) fn new(value: &[u8], ttl: Option<Duration>) -> Self {
+        Self {
+            value: value.to_vec(),
+            expires_at: ttl.and_then(|ttl| Instant::now().checked_add(ttl)),
+        }
+    }
+
+    /// Returns the value, or `None` once the entry has expired.
+    pub(crate) fn value(&self) -> Option<&Vec<u8>> {
+        match self.expires_at {
+            Some(expires_at) if expires_at <= Instant::now() => None,
+            _ => Some(&self.value),
+        }
+    }
+}
+
+/// Inserts `entry` under `key`, dropping the entries that have expired.
+pub(crate) fn insert_entry(
+    entries: &mut HashMap<String, KeyValueEntry>,
+    key: &str,
+    entry: KeyValueEntry,
+) {
+    entries.retain(|_, entry| entry.value().is_some());
+    entries.insert(key.to_string(), entry);
+}
+
+/// Returns the entries whose key starts with `prefix` and that have not
+/// expired, sorted by key.
+pub(crate) fn list_entries(
+    entries: &HashMap<String, KeyValueEntry>,
+    prefix: &str,
+) -> Vec<(String, Vec<u8>)> {
+    let mut entries: Vec<_> = entries
+        .iter()
+        .filter(|(key, _)| key.starts_with(prefix))
+        .filter_map(|(key, entry)| Some((key.clone(), entry.value()?.clone())))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+/// A thread-safe in-memory `KeyValueStore`.
+///
+/// Clones share the same entries, so a single store can be handed to several
+/// clients simulating the processes of a distributed job. Blocking gets wait
+/// on a condition variable until the key is put or the timeout expires.
+///
+/// # Examples
+///
+/// ```rust
+/// use pjrt::{InMemoryKeyValueStore, KeyValueStore};
+///
+/// let store = InMemoryKeyValueStore::new();
+/// let process = store.clone();
+/// std::thread::spawn(move || process.put("ready", b"1").unwrap());
+///
+/// assert_eq!(store.get("ready", 5_000).unwrap(), b"1");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryKeyValueStore {
+    shared: Arc<InMemoryShared>,
+}
+
+#[derive(Debug, Default)]
+struct InMemoryShared {
+    entries: Mutex<HashMap<String, KeyValueEntry>>,
+    /// Notified on every put.
+    changed: Condvar,
+}
+
+impl InMemoryKeyValueStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of keys in the store that have not expired.
+    pub fn len(&self) -> usize {
+        self.entries()
+            .values()
+            .filter(|entry| entry.value().is_some())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, KeyValueEntry>> {
+        self.shared
+            .entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl KeyValueStore for InMemoryKeyValueStore {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        let deadline = get_deadline(timeout_in_ms);
+        let mut entries = self.entries();
+        loop {
+            if let Some(value) = entries.get(key).and_then(KeyValueEntry::value) {
+                return Ok(value.clone());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::KeyValueTimeout {
+                    key: key.to_string(),
+                    timeout_in_ms,
+                });
+            }
+            entries = self
+                .shared
+                .changed
+                .wait_timeout(entries, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        insert_entry(&mut self.entries(), key, KeyValueEntry::new(value, None));
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .entries()
+            .get(key)
+            .and_then(KeyValueEntry::value)
+            .cloned())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let entry = self.entries().remove(key);
+        Ok(entry.is_some_and(|entry| entry.value().is_some()))
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        Ok(list_entries(&self.entries(), prefix))
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        insert_entry(
+            &mut self.entries(),
+            key,
+            KeyValueEntry::new(value, Some(ttl)),
+        );
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+/// The default interval at which `FileKeyValueStore` polls for a key.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The longest file name `FileKeyValueStore` writes.
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Distinguishes temporary files written concurrently by one process.
+static NEXT_TEMP_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// A `KeyValueStore` backed by a directory shared between processes.
+///
+/// Each key is stored in its own file. Puts write a temporary file and
+/// atomically rename it into place, so readers never observe a partially
+/// written value. Blocking gets poll the directory until the key appears or
+/// the timeout expires.
+///
+/// Keys are escaped into file names, so any key can be used as long as its
+/// escaped name fits in 255 bytes, the `NAME_MAX` of common file systems.
+/// Longer keys are rejected with `Error::InvalidArgument`.
+///
+/// A key put with a TTL has a second file next to its value holding the
+/// expiry as milliseconds since the Unix epoch, so the processes sharing the
+/// directory need roughly synchronized clocks.
+#[derive(Debug, Clone)]
+pub struct FileKeyValueStore {
+    dir: PathBuf,
+    poll_interval: Duration,
+}
+
+impl FileKeyValueStore {
+    /// Creates a store in `dir`, creating the directory if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        })
+    }
+
+    /// Sets how often blocking gets check for the key. Defaults to 10ms.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn get_poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        Ok(self.dir.join(file_name(key)?))
+    }
+
+    /// Returns the path of the file holding the expiry of `key`, named like
+    /// its value file with an `e` in place of the leading `k`.
+    fn expiry_path(&self, key: &str) -> Result<PathBuf> {
+        Ok(self.dir.join(format!("e{}", &file_name(key)?[1..])))
+    }
+
+    /// Writes `contents` to `path` through a temporary file renamed into
+    /// place.
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let temp = self.dir.join(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = (|| {
+            let mut file = fs::File::create(&temp)?;
+            file.write_all(contents)?;
+            file.sync_all()?;
+            fs::rename(&temp, path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        Ok(result?)
+    }
+
+    /// Returns whether `key` was put with a TTL that has passed.
+    fn is_expired(&self, key: &str) -> Result<bool> {
+        let expires_at = match read_optional(&self.expiry_path(key)?)? {
+            Some(bytes) => match <[u8; 8]>::try_from(bytes.as_slice()) {
+                Ok(bytes) => u64::from_le_bytes(bytes),
+                // A torn expiry file is treated as no expiry.
+                Err(_) => return Ok(false),
+            },
+            None => return Ok(false),
+        };
+        Ok(expires_at <= unix_millis(SystemTime::now()))
+    }
+}
+
+/// Returns the milliseconds from the Unix epoch to `time`.
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis().try_into().unwrap_or(u64::MAX))
+}
+
+/// Reads the file at `path`, returning `None` if it does not exist.
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Removes the file at `path`, returning whether it existed.
+fn remove_optional(path: &Path) -> Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns the name of the file holding `key`, rejecting keys whose name
+/// would exceed `MAX_FILE_NAME_LEN`.
+fn file_name(key: &str) -> Result<String> {
+    let name = escape_key(key);
+    if name.len() > MAX_FILE_NAME_LEN {
+        return Err(Error::InvalidArgument(format!(
+            "key of {} bytes escapes to a file name of {} bytes, exceeding {MAX_FILE_NAME_LEN}",
+            key.len(),
+            name.len()
+        )));
+    }
+    Ok(name)
+}
+
+/// Escapes `key` into a file name, keeping ASCII alphanumerics, `-` and `_`
+/// and writing every other byte as `%XX`.
+///
+/// Escaped names never start with `.`, which is reserved for temporary files.
+fn escape_key(key: &str) -> String {
+    let mut name = String::with_capacity(key.len() + 1);
+    // Prefix so that the empty key has a valid name.
+    name.push('k');
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    name
+}
+
+/// Reverses `escape_key`, returning `None` for names it does not produce.
+fn unescape_key(name: &str) -> Option<String> {
+    let escaped = name.strip_prefix('k')?.as_bytes();
+    let mut key = Vec::with_capacity(escaped.len());
+    let mut i = 0;
+    while i < escaped.len() {
+        if escaped[i] == b'%' {
+            let hex = std::str::from_utf8(escaped.get(i + 1..i + 3)?).ok()?;
+            key.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            key.push(escaped[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(key).ok()
+}
+
+impl KeyValueStore for FileKeyValueStore {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        let deadline = get_deadline(timeout_in_ms);
+        loop {
+            if let Some(value) = self.try_get(key)? {
+                return Ok(value);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::KeyValueTimeout {
+                    key: key.to_string(),
+                    timeout_in_ms,
+                });
+            }
+            std::thread::sleep(remaining.min(self.poll_interval));
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.write_atomic(&self.path(key)?, value)?;
+        // Until the expiry is removed the new value may read as expired,
+        // which is better than an expired value reappearing.
+        remove_optional(&self.expiry_path(key)?)?;
+        Ok(())
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let Some(value) = read_optional(&self.path(key)?)? else {
+            return Ok(None);
+        };
+        if self.is_expired(key)? {
+            return Ok(None);
+        }
+        Ok(Some(value))
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        let expired = self.is_expired(key)?;
+        let existed = remove_optional(&self.path(key)?)?;
+        remove_optional(&self.expiry_path(key)?)?;
+        Ok(existed && !expired)
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            // Skips temporary and foreign files.
+            let Some(key) = name.to_str().and_then(unescape_key) else {
+                continue;
+            };
+            if !key.starts_with(prefix) {
+                continue;
+            }
+            // The key may have been deleted since the directory was read.
+            if let Some(value) = self.try_get(&key)? {
+                entries.push((key, value));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let expires_at = unix_millis(SystemTime::now())
+            .saturating_add(ttl.as_millis().try_into().unwrap_or(u64::MAX));
+        self.write_atomic(&self.expiry_path(key)?, &expires_at.to_le_bytes())?;
+        self.write_atomic(&self.path(key)?, value)
+    }
+}
+
+/// A `KeyValueStore` adapter that confines keys to a namespace.
+///
+/// Every key is prefixed with `"{namespace}/"`, so that several jobs, or
+/// several incarnations of one job, can share a backing store without seeing
+/// each other's keys. The keys left behind by a crashed run can be removed
+/// with [`Namespaced::clear`].
+///
+/// # Examples
+///
+/// ```rust
+/// use pjrt::{InMemoryKeyValueStore, KeyValueStore, Namespaced};
+///
+/// let store = InMemoryKeyValueStore::new();
+/// let job_a = Namespaced::new(&store, "job_a/1");
+/// let job_b = Namespaced::new(&store, "job_b/1");
+///
+/// job_a.put("ready", b"1").unwrap();
+/// assert_eq!(job_b.try_get("ready").unwrap(), None);
+/// assert_eq!(store.try_get("job_a/1/ready").unwrap().unwrap(), b"1");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Namespaced<S> {
+    inner: S,
+    namespace: String,
+    prefix: String,
+}
+
+impl<S: KeyValueStore> Namespaced<S> {
+    pub fn new(inner: S, namespace: impl Into<String>) -> Self {
+        let namespace = namespace.into();
+        let prefix = format!("{namespace}/");
+        Self {
+            inner,
+            namespace,
+            prefix,
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Deletes every key in the namespace and returns how many were deleted.
+    ///
+    /// The backing store must support `list_prefix` and `delete`.
+    pub fn clear(&self) -> Result<usize> {
+        let mut deleted = 0;
+        for (key, _) in self.inner.list_prefix(&self.prefix)? {
+            if self.inner.delete(&key)? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
+
+impl<S: KeyValueStore> KeyValueStore for Namespaced<S> {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        self.inner.get(&self.key(key), timeout_in_ms)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.inner.put(&self.key(key), value)
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.try_get(&self.key(key))
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        self.inner.delete(&self.key(key))
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let entries = self.inner.list_prefix(&self.key(prefix))?;
+        Ok(entries
+            .into_iter()
+            .map(|(key, value)| (key[self.prefix.len()..].to_string(), value))
+            .collect())
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        self.inner.put_with_ttl(&self.key(key), value, ttl)
+    }
+}
+
+/// A key-value store operation, as recorded by [`LoggingKeyValueStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyValueOp {
+    Get,
+    Put,
+    TryGet,
+    Delete,
+    ListPrefix,
+    PutWithTtl,
+}
+
+/// The outcome of a recorded key-value store operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyAccessStatus {
+    /// The operation has not returned yet, e.g. a get waiting for its key.
+    Pending,
+    Succeeded,
+    Failed {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+/// A key-value store access recorded by [`LoggingKeyValueStore`].
+#[derive(Debug, Clone)]
+pub struct KeyAccess {
+    pub op: KeyValueOp,
+    /// The key, or the prefix for `ListPrefix`.
+    pub key: String,
+    /// The timeout of a `Get`.
+    pub timeout_in_ms: Option<i32>,
+    /// The size of the value put or returned, or the number of entries
+    /// listed. `None` for a `TryGet` of a missing key.
+    pub value_size: Option<usize>,
+    pub started: Instant,
+    /// How long the operation took, once it has returned.
+    pub duration: Option<Duration>,
+    pub status: KeyAccessStatus,
+}
+
+/// A `KeyValueStore` decorator that records every access.
+///
+/// Given to a client in place of the store it wraps, it records the keys the
+/// plugin reads and writes while the processes of a job find each other.
+/// Accesses are recorded when they start, so a handshake that hangs shows up
+/// in [`LoggingKeyValueStore::pending`] as a get of the key no process has
+/// put.
+///
+/// # Examples
+///
+/// ```rust
+/// use pjrt::{InMemoryKeyValueStore, KeyValueStore, LoggingKeyValueStore};
+///
+/// let store = LoggingKeyValueStore::new(InMemoryKeyValueStore::new());
+/// store.put("a", b"1").unwrap();
+/// assert!(store.get("b", 0).is_err());
+///
+/// for access in store.accesses() {
+///     println!("{:?} {} {:?}", access.op, access.key, access.status);
+/// }
+/// assert!(store.pending().is_empty());
+/// ```
+#[derive(Debug)]
+pub struct LoggingKeyValueStore<S> {
+    inner: S,
+    accesses: Mutex<Vec<KeyAccess>>,
+}
+
+impl<S: KeyValueStore> LoggingKeyValueStore<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            accesses: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Returns every recorded access, in the order they started.
+    pub fn accesses(&self) -> Vec<KeyAccess> {
+        self.lock().clone()
+    }
+
+    /// Returns the accesses that have not returned yet.
+    pub fn pending(&self) -> Vec<KeyAccess> {
+        self.lock()
+            .iter()
+            .filter(|access| access.status == KeyAccessStatus::Pending)
+            .cloned()
+            .collect()
+    }
+
+    /// Forgets the recorded accesses.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<KeyAccess>> {
+        self.accesses.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records an access, runs `f` and records its outcome.
+    fn record<T>(
+        &self,
+        op: KeyValueOp,
+        key: &str,
+        timeout_in_ms: Option<i32>,
+        value_size: Option<usize>,
+        f: impl FnOnce() -> Result<T>,
+        result_size: impl FnOnce(&T) -> Option<usize>,
+    ) -> Result<T> {
+        let started = Instant::now();
+        let index = {
+            let mut accesses = self.lock();
+            accesses.push(KeyAccess {
+                op,
+                key: key.to_string(),
+                timeout_in_ms,
+                value_size,
+                started,
+                duration: None,
+                status: KeyAccessStatus::Pending,
+            });
+            accesses.len() - 1
+        };
+        let result = f();
+        let mut accesses = self.lock();
+        // The log may have been cleared while `f` ran.
+        let Some(access) = accesses
+            .get_mut(index)
+            .filter(|access| access.started == started && access.op == op)
+        else {
+            return result;
+        };
+        access.duration = Some(started.elapsed());
+        match &result {
+            Ok(value) => {
+                access.status = KeyAccessStatus::Succeeded;
+                access.value_size = access.value_size.or_else(|| result_size(value));
+            }
+            Err(err) => {
+                access.status = KeyAccessStatus::Failed {
+                    code: err.code(),
+                    message: err.to_string(),
+                };
+            }
+        }
+        drop(accesses);
+        result
+    }
+}
+
+impl<S: KeyValueStore> KeyValueStore for LoggingKeyValueStore<S> {
+    fn get(&self, key: &str, timeout_in_ms: i32) -> Result<Vec<u8>> {
+        self.record(
+            KeyValueOp::Get,
+            key,
+            Some(timeout_in_ms),
+            None,
+            || self.inner.get(key, timeout_in_ms),
+            |value| Some(value.len()),
+        )
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.record(
+            KeyValueOp::Put,
+            key,
+            None,
+            Some(value.len()),
+            || self.inner.put(key, value),
+            |_| None,
+        )
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.record(
+            KeyValueOp::TryGet,
+            key,
+            None,
+            None,
+            || self.inner.try_get(key),
+            |value| value.as_ref().map(Vec::len),
+        )
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        self.record(
+            KeyValueOp::Delete,
+            key,
+            None,
+            None,
+            || self.inner.delete(key),
+            |_| None,
+        )
+    }
+
+    fn list_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        self.record(
+            KeyValueOp::ListPrefix,
+            prefix,
+            None,
+            None,
+            || self.inner.list_prefix(prefix),
+            |entries| Some(entries.len()),
+        )
+    }
+
+    fn put_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        self.record(
+            KeyValueOp::PutWithTtl,
+            key,
+            None,
+            Some(value.len()),
+            || self.inner.put_with_ttl(key, value, ttl),
+            |_| None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// A unique scratch directory, removed on drop.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("pjrt-kv-store-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn check_store(store: &(impl KeyValueStore + Clone + Send + 'static)) {
+        assert_eq!(store.try_get("a/b").unwrap(), None);
+        store.put("a/b", b"\0binary\0").unwrap();
+        assert_eq!(store.try_get("a/b").unwrap().unwrap(), b"\0binary\0");
+        assert_eq!(store.get("a/b", 0).unwrap(), b"\0binary\0");
+
+        store.put("a/b", b"").unwrap();
+        assert_eq!(store.get("a/b", 0).unwrap(), b"");
+
+        let start = Instant::now();
+        let err = store.get("missing", 50).unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(matches!(err, Error::KeyValueTimeout { .. }));
+        assert_eq!(err.code(), ErrorCode::DeadlineExceeded);
+
+        let writer = store.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            writer.put("late", b"value").unwrap();
+        });
+        assert_eq!(store.get("late", 10_000).unwrap(), b"value");
+        handle.join().unwrap();
+    }
+
+    fn check_delete_and_list(store: &impl KeyValueStore) {
+        store.put("x/2", b"2").unwrap();
+        store.put("x/1", b"1").unwrap();
+        store.put("x:3", b"3").unwrap();
+        store.put("y/1", b"4").unwrap();
+        assert_eq!(
+            store.list_prefix("x/").unwrap(),
+            vec![
+                ("x/1".to_string(), b"1".to_vec()),
+                ("x/2".to_string(), b"2".to_vec()),
+            ]
+        );
+        assert_eq!(store.list_prefix("x").unwrap().len(), 3);
+        assert_eq!(store.list_prefix("z").unwrap(), vec![]);
+
+        assert!(store.delete("x/1").unwrap());
+        assert!(!store.delete("x/1").unwrap());
+        assert_eq!(store.try_get("x/1").unwrap(), None);
+        assert_eq!(store.list_prefix("x/").unwrap().len(), 1);
+    }
+
+    fn check_ttl(store: &impl KeyValueStore) {
+        store
+            .put_with_ttl("t/long", b"1", Duration::from_secs(600))
+            .unwrap();
+        store
+            .put_with_ttl("t/short", b"2", Duration::from_millis(50))
+            .unwrap();
+        store
+            .put_with_ttl("t/cleared", b"3", Duration::from_millis(50))
+            .unwrap();
+        // A plain put replaces the value and its expiry.
+        store.put("t/cleared", b"4").unwrap();
+        assert_eq!(store.try_get("t/short").unwrap().unwrap(), b"2");
+        assert_eq!(store.list_prefix("t/").unwrap().len(), 3);
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(store.try_get("t/short").unwrap(), None);
+        assert!(matches!(
+            store.get("t/short", 0),
+            Err(Error::KeyValueTimeout { .. })
+        ));
+        assert_eq!(store.get("t/long", 0).unwrap(), b"1");
+        assert_eq!(
+            store.list_prefix("t/").unwrap(),
+            vec![
+                ("t/cleared".to_string(), b"4".to_vec()),
+                ("t/long".to_string(), b"1".to_vec()),
+            ]
+        );
+        assert!(!store.delete("t/short").unwrap());
+        assert!(store.delete("t/long").unwrap());
+
+        // An expired key can be put again.
+        store
+            .put_with_ttl("t/short", b"5", Duration::from_secs(600))
+            .unwrap();
+        assert_eq!(store.try_get("t/short").unwrap().unwrap(), b"5");
+    }
+
+    #[test]
+    fn test_in_memory_store() {
+        let store = InMemoryKeyValueStore::new();
+        check_store(&store);
+        assert_eq!(store.len(), 2);
+
+        let store = InMemoryKeyValueStore::new();
+        check_delete_and_list(&store);
+        assert_eq!(store.len(), 3);
+
+        let store = InMemoryKeyValueStore::new();
+        check_ttl(&store);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_in_memory_store_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<InMemoryKeyValueStore>();
+        assert_send_sync::<FileKeyValueStore>();
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = ScratchDir::new("basic");
+        let store = FileKeyValueStore::new(&dir.0)
+            .unwrap()
+            .poll_interval(Duration::from_millis(1));
+        assert_eq!(store.get_poll_interval(), Duration::from_millis(1));
+        check_store(&store);
+
+        // Another store on the same directory sees the same entries.
+        let other = FileKeyValueStore::new(store.dir()).unwrap();
+        assert_eq!(other.try_get("late").unwrap().unwrap(), b"value");
+        // No temporary files are left behind.
+        let names: Vec<_> = fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 2);
+
+        let dir = ScratchDir::new("list");
+        let store = FileKeyValueStore::new(&dir.0).unwrap();
+        // Files not written by the store are ignored.
+        fs::write(dir.0.join(".tmp-0-0"), b"").unwrap();
+        fs::write(dir.0.join("foreign"), b"").unwrap();
+        check_delete_and_list(&store);
+        assert_eq!(store.list_prefix("").unwrap().len(), 3);
+
+        let dir = ScratchDir::new("ttl");
+        let store = FileKeyValueStore::new(&dir.0).unwrap();
+        check_ttl(&store);
+        // Another store on the same directory sees the same expiries.
+        let other = FileKeyValueStore::new(store.dir()).unwrap();
+        assert_eq!(other.try_get("t/short").unwrap().unwrap(), b"5");
+        // The expiry of the deleted key is removed with its value.
+        assert!(!dir.0.join(escape_key("t/long")).exists());
+        assert!(!dir
+            .0
+            .join(format!("e{}", &escape_key("t/long")[1..]))
+            .exists());
+    }
+
+    #[test]
+    fn test_file_store_long_key() {
+        let dir = ScratchDir::new("long");
+        let store = FileKeyValueStore::new(&dir.0).unwrap();
+        let longest = "a".repeat(MAX_FILE_NAME_LEN - 1);
+        store.put(&longest, b"value").unwrap();
+        assert_eq!(store.try_get(&longest).unwrap().unwrap(), b"value");
+
+        // Escaping triples the length of every `/`.
+        let long = "/".repeat(100);
+        for result in [
+            store.put(&long, b"value"),
+            store.try_get(&long).map(|_| ()),
+            store.delete(&long).map(|_| ()),
+        ] {
+            assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn test_escape_key() {
+        assert_eq!(escape_key(""), "k");
+        assert_eq!(escape_key("a-b_C9"), "ka-b_C9");
+        assert_eq!(escape_key("a/b.c:d"), "ka%2Fb%2Ec%3Ad");
+        assert_ne!(escape_key("a/b"), escape_key("a%2Fb"));
+
+        for key in ["", "a-b_C9", "a/b.c:d", "a%2Fb", "\u{e9}t\u{e9}"] {
+            assert_eq!(unescape_key(&escape_key(key)).unwrap(), key);
+        }
+        assert_eq!(unescape_key(".tmp-1-2"), None);
+        assert_eq!(unescape_key("k%2"), None);
+        assert_eq!(unescape_key("k%ZZ"), None);
+    }
+
+    #[test]
+    fn test_default_methods() {
+        struct GetOnly;
+
+        impl KeyValueStore for GetOnly {
+            fn get(&self, key: &str, _timeout_in_ms: i32) -> Result<Vec<u8>> {
+                Err(Error::KeyNotFound(key.to_string()))
+            }
+
+            fn put(&self, _key: &str, _value: &[u8]) -> Result<()> {
+                Ok(())
+            }
+
+            fn try_get(&self, _key: &str) -> Result<Option<Vec<u8>>> {
+                Ok(None)
+            }
+        }
+
+        assert!(matches!(GetOnly.delete("a"), Err(Error::Unimplemented)));
+        assert!(matches!(
+            GetOnly.list_prefix("a"),
+            Err(Error::Unimplemented)
+        ));
+        assert!(matches!(
+            GetOnly.put_with_ttl("a", b"", Duration::from_secs(1)),
+            Err(Error::Unimplemented)
+        ));
+        assert!(matches!(
+            Namespaced::new(GetOnly, "job").clear(),
+            Err(Error::Unimplemented)
+        ));
+    }
+
+    #[test]
+    fn test_namespaced() {
+        let store = InMemoryKeyValueStore::new();
+        let job_a = Namespaced::new(store.clone(), "job/a");
+        let job_b = Namespaced::new(&store, "job/b");
+        assert_eq!(job_a.namespace(), "job/a");
+
+        job_a.put("ready", b"a").unwrap();
+        job_b.put("ready", b"b").unwrap();
+        store.put("job/ab", b"other").unwrap();
+        assert_eq!(job_a.get("ready", 0).unwrap(), b"a");
+        assert_eq!(job_b.try_get("ready").unwrap().unwrap(), b"b");
+        assert_eq!(store.try_get("job/a/ready").unwrap().unwrap(), b"a");
+        assert_eq!(
+            job_a.list_prefix("").unwrap(),
+            vec![("ready".to_string(), b"a".to_vec())]
+        );
+
+        // Timeouts report the namespaced key.
+        match job_a.get("missing", 0).unwrap_err() {
+            Error::KeyValueTimeout { key, .. } => assert_eq!(key, "job/a/missing"),
+            err => panic!("unexpected error {err:?}"),
+        }
+
+        assert_eq!(job_a.clear().unwrap(), 1);
+        assert_eq!(job_a.try_get("ready").unwrap(), None);
+        assert_eq!(store.len(), 2);
+
+        let job_a = Namespaced::new(job_a.into_inner(), "job");
+        check_delete_and_list(&job_a);
+        assert_eq!(store.try_get("job/x/2").unwrap().unwrap(), b"2");
+        check_ttl(&job_a);
+        assert_eq!(store.try_get("job/t/short").unwrap().unwrap(), b"5");
+    }
+
+    #[test]
+    fn test_logging_store() {
+        let store = Arc::new(LoggingKeyValueStore::new(InMemoryKeyValueStore::new()));
+        store.put("a", b"123").unwrap();
+        assert_eq!(store.try_get("b").unwrap(), None);
+        assert!(store.get("b", 0).is_err());
+
+        let accesses = store.accesses();
+        assert_eq!(accesses.len(), 3);
+        assert_eq!(accesses[0].op, KeyValueOp::Put);
+        assert_eq!(accesses[0].key, "a");
+        assert_eq!(accesses[0].value_size, Some(3));
+        assert_eq!(accesses[0].status, KeyAccessStatus::Succeeded);
+        assert!(accesses[0].duration.is_some());
+        assert_eq!(accesses[1].op, KeyValueOp::TryGet);
+        assert_eq!(accesses[1].value_size, None);
+        assert_eq!(accesses[2].op, KeyValueOp::Get);
+        assert_eq!(accesses[2].timeout_in_ms, Some(0));
+        assert!(matches!(
+            accesses[2].status,
+            KeyAccessStatus::Failed {
+                code: ErrorCode::DeadlineExceeded,
+                ..
+            }
+        ));
+
+        // A get blocked on a missing key shows up as pending.
+        store.clear();
+        let reader = store.clone();
+        let handle = thread::spawn(move || reader.get("late", 10_000).unwrap());
+        let start = Instant::now();
+        while store.pending().is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(store.pending()[0].key, "late");
+        store.inner().put("late", b"value").unwrap();
+        assert_eq!(handle.join().unwrap(), b"value");
+        assert!(store.pending().is_empty());
+        assert_eq!(store.accesses()[0].value_size, Some(5));
+
+        check_delete_and_list(&*store);
+        let ops: Vec<_> = store.accesses().iter().map(|access| access.op).collect();
+        assert!(ops.contains(&KeyValueOp::Delete));
+        assert!(ops.contains(&KeyValueOp::ListPrefix));
+
+        store.clear();
+        store
+            .put_with_ttl("t", b"12", Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(store.accesses()[0].op, KeyValueOp::PutWithTtl);
+        assert_eq!(store.accesses()[0].value_size, Some(2));
+    }
 }
@@ -43,20 +43,29 @@
 //!
 //! ### Types that are `Send + Sync` (thread-safe)
 //!
-//! - [`Api`]: The only FFI wrapper with explicit `unsafe impl Send + Sync`.
+//! - [`Api`]: An FFI wrapper with explicit `unsafe impl Send + Sync`.
 //!   PJRT plugin function tables are immutable after loading, so sharing
 //!   an `Api` across threads is safe.
+//! - [`ExecuteContext`]: Shared through an `Arc` with the executions that
+//!   use it, which run on plugin threads. Its user data is `Send + Sync`.
 //! - All pure-data types: [`CompileOptions`], [`ExecutableBuildOptions`],
 //!   [`Error`], [`ErrorCode`], [`PrimitiveType`], [`NamedValue`],
-//!   [`NamedValueMap`], [`DeviceAssignment`], [`MemoryLayout`],
-//!   [`MemoryStats`], [`CompiledMemoryStats`], [`Chunk`], [`CallLocation`],
-//!   [`LogicalId`], [`BufferShape`], and all F8 element types.
+//!   [`NamedValueMap`], [`DeviceAssignment`], [`Mesh`], the shardings
+//!   ([`NamedSharding`], [`Replicated`], [`PositionalSharding`]),
+//!   [`MemoryLayout`], [`MemoryStats`], [`CompiledMemoryStats`], [`Chunk`],
+//!   [`CallLocation`], [`LogicalId`], [`BufferShape`],
+//!   [`ExecutableSignature`], [`CancellationToken`], [`ReshardPlan`],
+//!   [`AotArtifact`], and all F8 element types.
+//! - The built-in key-value stores, [`InMemoryKeyValueStore`] and
+//!   [`FileKeyValueStore`], the [`Namespaced`] and [`LoggingKeyValueStore`]
+//!   adapters when their inner store is, and the coordination service types,
+//!   [`CoordinationService`] and [`CoordinationClient`].
 //!
 //! ### Types that are `!Send + !Sync` (single-threaded)
 //!
 //! - [`Client`], [`Device`], [`Buffer`], [`Memory`], [`LoadedExecutable`],
-//!   [`Executable`], [`Event`], [`TopologyDescription`], and all extension
-//!   types. These are `!Send` because `Client` internally uses `Rc` for
+//!   [`Executable`], [`Event`], [`TopologyDescription`], [`ShardedArray`],
+//!   and all extension types. These are `!Send` because `Client` internally uses `Rc` for
 //!   reference counting. All device-side types hold a `Client` reference,
 //!   making them transitively `!Send + !Sync`.
 //! - [`HostBuffer`] and [`TypedHostBuffer`]: Use `Rc` internally for the
@@ -75,6 +84,9 @@
 //!
 //! For more detailed examples and advanced usage patterns, see the `examples/` directory.
 
+// Lets `#[derive(PjrtTree)]`, which refers to `::pjrt`, be used in this crate.
+extern crate self as pjrt;
+
 mod utils;
 
 mod error;
@@ -126,12 +138,33 @@ pub use device_assignment::{DeviceAssignment, LogicalId};
 mod memory;
 pub use memory::Memory;
 
+mod mesh;
+pub use mesh::Mesh;
+
+mod sharding;
+pub use sharding::{NamedSharding, PartitionSpec, PositionalSharding, Replicated, Sharding};
+
+mod sharded_array;
+pub use sharded_array::{Shard, ShardedArray};
+
+mod reshard;
+pub use reshard::{ReshardPlan, ShardTransfer};
+
 mod topology_description;
 pub use topology_description::{SerializedTopology, TopologyDescription};
 
 mod program;
 pub use program::{Program, ProgramFormat};
 
+mod mlir;
+pub use mlir::{ProgramMetadata, ShardingAnnotation, TensorType, ValueInfo};
+
+mod io_names;
+pub use io_names::IoNames;
+
+mod stablehlo;
+pub use stablehlo::StablehloVersion;
+
 mod loaded_executable;
 pub use loaded_executable::LoadedExecutable;
 
@@ -140,26 +173,58 @@ pub use executable::{
     CompiledMemoryStats, Executable, SerializedCompileOptions, SerializedExecutable,
 };
 
+mod aot;
+pub use aot::AotArtifact;
+
+mod signature;
+pub use signature::{
+    AliasKind, Donation, ExecutableSignature, OutputSignature, ParameterSignature, ShapeSignature,
+};
+
 mod event;
 pub use event::Event;
 
 mod named_value;
 pub use named_value::{NamedValue, NamedValueMap};
 
+mod cancellation;
+pub use cancellation::CancellationToken;
+
 mod execute;
 pub use execute::{
-    CallLocation, CallbackError, ExecuteContext, ExecuteOptions, Execution, ExecutionInputs,
-    RecvCallback, RecvCallbackInfo, SendCallback, SendCallbackInfo, TransferMetadata,
+    CallLocation, CallbackError, DeviceExecution, ExecuteContext, ExecuteOptions, Execution,
+    ExecutionInputs, RecvCallback, RecvCallbackInfo, SendCallback, SendCallbackInfo,
+    TransferMetadata,
 };
 
+mod tree;
+pub use pjrt_derive::PjrtTree;
+pub use tree::{PjrtTree, Tree, TreeDef};
+
+mod pipeline;
+pub use pipeline::Pipeline;
+
+mod batching;
+pub use batching::Batcher;
+
 mod device_stream;
-pub use device_stream::CopyToDeviceStream;
+pub use device_stream::{CopyToDeviceStream, CopyToDeviceStreamWriter};
 
 mod chunk;
 pub use chunk::Chunk;
 
+mod host_callback;
+
 mod kv_store;
-pub use kv_store::KeyValueStore;
+pub use kv_store::{
+    FileKeyValueStore, InMemoryKeyValueStore, KeyAccess, KeyAccessStatus, KeyValueOp,
+    KeyValueStore, LoggingKeyValueStore, Namespaced,
+};
+
+mod coordination;
+pub use coordination::{CoordinationClient, CoordinationService};
+
+pub mod distributed;
 
 mod extension;
 pub use extension::{Extension, ExtensionType};
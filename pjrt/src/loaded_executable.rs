@@ -10,24 +10,61 @@
 //!
 //! The loaded executable can be executed multiple times with different inputs,
 //! making it efficient for inference and training loops.
+//!
+//! Unless disabled through `ExecuteOptions::validate_inputs`, inputs are
+//! checked against the executable's signature before each execution so that
+//! mistakes surface as `Error::InputMismatch` rather than opaque plugin errors.
+//!
+//! Waits honour `ExecuteOptions::deadline` and
+//! `ExecuteOptions::cancellation_token`: an interrupted launch is poisoned on
+//! its devices so that its outputs become error buffers.
 
+use std::cell::OnceCell;
+use std::collections::HashMap;
 use std::mem::MaybeUninit;
 use std::slice;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
 use bon::bon;
+use pjrt_sys::protos::xla::DeviceAssignmentProto;
 use pjrt_sys::{
     PJRT_Buffer, PJRT_Event, PJRT_ExecuteOptions, PJRT_LoadedExecutable,
     PJRT_LoadedExecutable_AddressableDevices_Args, PJRT_LoadedExecutable_Delete_Args,
     PJRT_LoadedExecutable_Destroy_Args, PJRT_LoadedExecutable_Execute_Args,
-    PJRT_LoadedExecutable_GetExecutable_Args, PJRT_LoadedExecutable_IsDeleted_Args,
+    PJRT_LoadedExecutable_GetDeviceAssignment_Args, PJRT_LoadedExecutable_GetExecutable_Args,
+    PJRT_LoadedExecutable_IsDeleted_Args,
 };
+use prost::Message;
 
 use crate::execute::ExecuteOptionsRaw;
 use crate::{
-    event, utils, Buffer, Client, CompileOptions, CompileToLoadedExecutable, Device, Event,
-    Executable, ExecuteOptions, Execution, ExecutionInputs, Result,
+    event, utils, Buffer, Client, CompileOptions, CompileToLoadedExecutable, Device,
+    DeviceAssignment, Donation, Error, ErrorCode, Event, Executable, ExecutableSignature,
+    ExecuteOptions, Execution, ExecutionInputs, IoNames, Pipeline, PjrtTree, PrimitiveType, Result,
+    Tree, TreeDef,
 };
 
+/// How often blocking waits poll a cancellation token.
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Returns the error for an expired deadline or cancelled token, if any.
+fn interruption(options: &ExecuteOptions<'_>) -> Option<Error> {
+    if options
+        .get_cancellation_token()
+        .is_some_and(|token| token.is_cancelled())
+    {
+        return Some(Error::Cancelled);
+    }
+    if options
+        .get_deadline()
+        .is_some_and(|deadline| Instant::now() >= deadline)
+    {
+        return Some(Error::DeadlineExceeded);
+    }
+    None
+}
+
 /// An executable loaded onto devices and ready for execution.
 ///
 /// A `LoadedExecutable` represents a compiled program that has been loaded
@@ -57,6 +94,8 @@ use crate::{
 pub struct LoadedExecutable {
     client: Client,
     pub(crate) ptr: *mut PJRT_LoadedExecutable,
+    /// `None` when the plugin cannot provide the optimized program.
+    signature: OnceCell<Option<ExecutableSignature>>,
 }
 
 impl Drop for LoadedExecutable {
@@ -89,6 +128,7 @@ impl LoadedExecutable {
         Self {
             client: client.clone(),
             ptr,
+            signature: OnceCell::new(),
         }
     }
 
@@ -135,6 +175,113 @@ impl LoadedExecutable {
             .collect())
     }
 
+    /// Returns the device assignment the executable was compiled and loaded
+    /// with.
+    ///
+    /// Use [`DeviceAssignment::devices`] to resolve it to `Device` handles.
+    pub fn device_assignment(&self) -> Result<DeviceAssignment> {
+        let mut args = PJRT_LoadedExecutable_GetDeviceAssignment_Args::new();
+        args.executable = self.ptr;
+        args = self
+            .client
+            .api()
+            .PJRT_LoadedExecutable_GetDeviceAssignment(args)?;
+        let bytes = if args.serialized_bytes.is_null() {
+            &[][..]
+        } else {
+            unsafe {
+                slice::from_raw_parts(
+                    args.serialized_bytes as *const u8,
+                    args.serialized_bytes_size,
+                )
+            }
+        };
+        let proto = DeviceAssignmentProto::decode(bytes);
+        if let Some(deleter) = args.serialized_device_assignment_deleter {
+            unsafe { deleter(args.serialized_device_assignment) };
+        }
+        DeviceAssignment::try_from(proto?)
+    }
+
+    /// Returns the signature of the underlying executable, if available.
+    ///
+    /// The lookup is performed once; plugins that cannot return the optimized
+    /// program yield `None` on every call.
+    pub(crate) fn cached_signature(&self) -> Option<&ExecutableSignature> {
+        self.signature
+            .get_or_init(|| {
+                let executable = self.executable().ok()?;
+                let signature = executable.signature().ok()?;
+                Some(signature.clone())
+            })
+            .as_ref()
+    }
+
+    /// Checks `inputs` against the devices they run on and the signature.
+    fn validate_inputs<I>(&self, inputs: &I, devices: &[Device]) -> Result<()>
+    where
+        I: ExecutionInputs,
+    {
+        let buffers = inputs.buffers();
+        let num_args: Vec<usize> = match &buffers {
+            Some(buffers) => buffers.iter().map(Vec::len).collect(),
+            None => inputs.buffer_ptrs().iter().map(Vec::len).collect(),
+        };
+        if num_args.len() != devices.len() {
+            return Err(Error::InputMismatch {
+                arg_index: None,
+                expected: plural(devices.len(), "device"),
+                actual: plural(num_args.len(), "device"),
+            });
+        }
+        let Some(signature) = self.cached_signature() else {
+            return Ok(());
+        };
+        let parameters = signature.parameters();
+        for (device_index, &n) in num_args.iter().enumerate() {
+            if n != parameters.len() {
+                return Err(Error::InputMismatch {
+                    arg_index: None,
+                    expected: plural(parameters.len(), "argument"),
+                    actual: format!("{} on device {}", plural(n, "argument"), device_index),
+                });
+            }
+        }
+        let Some(buffers) = buffers else {
+            return Ok(());
+        };
+        for (device_index, device_buffers) in buffers.iter().enumerate() {
+            let device = &devices[device_index];
+            for (arg_index, (buffer, param)) in device_buffers.iter().zip(parameters).enumerate() {
+                let expected = &param.shape;
+                let primitive_type = buffer.primitive_type()?;
+                let dims = buffer.dims()?;
+                let dims_match = if expected.dynamic_dims.iter().any(|&d| d) {
+                    dims.len() == expected.dims.len()
+                        && dims.iter().zip(&expected.dims).all(|(d, bound)| d <= bound)
+                } else {
+                    dims == expected.dims
+                };
+                if primitive_type != expected.primitive_type || !dims_match {
+                    return Err(Error::InputMismatch {
+                        arg_index: Some(arg_index),
+                        expected: describe_shape(expected.primitive_type, &expected.dims),
+                        actual: describe_shape(primitive_type, &dims),
+                    });
+                }
+                let buffer_device = buffer.device()?;
+                if buffer_device.ptr != device.ptr {
+                    return Err(Error::InputMismatch {
+                        arg_index: Some(arg_index),
+                        expected: format!("buffer on device {}", device.description()?.id()?),
+                        actual: format!("buffer on device {}", buffer_device.description()?.id()?),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn delete(self) -> Result<()> {
         let mut args = PJRT_LoadedExecutable_Delete_Args::new();
         args.executable = self.ptr;
@@ -157,13 +304,70 @@ impl LoadedExecutable {
     where
         I: ExecutionInputs,
     {
+        self.launch(inputs, options, None)
+    }
+
+    /// Launches this executable on one of its addressable devices.
+    ///
+    /// `inputs` holds the arguments for `device` only. This is how each
+    /// process of a multi-process SPMD program launches its share of a
+    /// multi-device executable.
+    ///
+    /// Returns `Error::DeviceNotAddressable` if `device` is not one of
+    /// [`LoadedExecutable::addressable_devices`], including devices of the
+    /// assignment that belong to other processes.
+    pub fn call_execute_on_device<'a, I>(
+        &self,
+        inputs: I,
+        options: &'a ExecuteOptions<'a>,
+        device: &Device,
+    ) -> Result<(Event, Vec<Buffer>)>
+    where
+        I: ExecutionInputs,
+    {
+        let (events, outputs) = self.launch(inputs, options, Some(device))?;
+        let Some(event) = events.into_iter().next() else {
+            return Err(Error::MissingDeviceEvent(device.description()?.id()?));
+        };
+        Ok((event, outputs.into_iter().next().unwrap_or_default()))
+    }
+
+    fn launch<'a, I>(
+        &self,
+        inputs: I,
+        options: &'a ExecuteOptions<'a>,
+        execute_device: Option<&Device>,
+    ) -> Result<(Vec<Event>, Vec<Vec<Buffer>>)>
+    where
+        I: ExecutionInputs,
+    {
+        if let Some(buffers) = inputs.buffers() {
+            for buffer in buffers.iter().flatten() {
+                buffer.check_not_donated()?;
+            }
+        }
+        if execute_device.is_some() || options.get_validate_inputs() {
+            let mut devices = self.addressable_devices()?;
+            if let Some(device) = execute_device {
+                devices.retain(|d| d.ptr == device.ptr);
+                if devices.is_empty() {
+                    return Err(Error::DeviceNotAddressable(device.description()?.id()?));
+                }
+            }
+            if options.get_validate_inputs() {
+                self.validate_inputs(&inputs, &devices)?;
+            }
+        }
         let executable = self.executable()?;
         let num_outputs = executable.num_outputs()?;
         let input_buffers = inputs.buffer_ptrs();
         let mut args = PJRT_LoadedExecutable_Execute_Args::new();
         args.executable = self.ptr;
+        if let Some(device) = execute_device {
+            args.execute_device = device.ptr;
+        }
         args.num_devices = input_buffers.len();
-        args.num_args = input_buffers[0].len();
+        args.num_args = input_buffers.first().map_or(0, Vec::len);
         // allocate argument lists — a flat array of pointers, one per device,
         // each pointing to that device's argument buffer array.
         let argument_lists: Vec<*const *mut PJRT_Buffer> =
@@ -184,8 +388,15 @@ impl LoadedExecutable {
         let complete_events = vec![MaybeUninit::<*mut PJRT_Event>::uninit(); args.num_devices];
         args.device_complete_events = complete_events.as_ptr() as *mut *mut PJRT_Event;
         // options - use ExecuteOptionsRaw to handle callback lifetimes
+        let non_donatable = options.resolve_non_donatable_input_indices(&inputs);
+        let callbacks = options.launch_callbacks(self.client.api(), args.num_devices);
         let mut raw_options = PJRT_ExecuteOptions::new();
-        let _options_raw = ExecuteOptionsRaw::new(options, &mut raw_options);
+        let _options_raw = ExecuteOptionsRaw::for_launch(
+            options,
+            &non_donatable,
+            callbacks.as_deref(),
+            &mut raw_options,
+        );
         args.options = &mut raw_options as *mut PJRT_ExecuteOptions;
         args = self.client.api().PJRT_LoadedExecutable_Execute(args)?;
         let events =
@@ -195,14 +406,53 @@ impl LoadedExecutable {
             .cloned()
             .map(|ptr| event::Event::wrap(self.client.api(), ptr))
             .collect::<Vec<_>>();
+        if let Some(callbacks) = &callbacks {
+            keep_alive_until_ready(&events, callbacks);
+        }
+        if let Some(context) = options.get_context() {
+            keep_alive_until_ready(&events, context);
+        }
         let output_buffers = unsafe {
             utils::slice_to_vec2d(args.output_lists, args.num_devices, num_outputs, |ptr| {
                 Buffer::wrap(&self.client, ptr)
             })
         };
+        self.mark_donated_inputs(&inputs, &non_donatable);
         Ok((events, output_buffers))
     }
 
+    /// Marks the input buffers the runtime donated to the outputs.
+    ///
+    /// Candidates are the parameters the signature marks as donors or
+    /// aliases that are not `kept`; the runtime deletes a candidate when it
+    /// actually donates it.
+    fn mark_donated_inputs<I>(&self, inputs: &I, kept: &[i64])
+    where
+        I: ExecutionInputs,
+    {
+        let Some(buffers) = inputs.buffers() else {
+            return;
+        };
+        let signature = self.cached_signature();
+        for device_buffers in buffers {
+            for (index, buffer) in device_buffers.into_iter().enumerate() {
+                if kept.contains(&(index as i64)) {
+                    continue;
+                }
+                let candidate = match signature {
+                    Some(signature) => signature
+                        .parameters()
+                        .get(index)
+                        .is_some_and(|param| param.donation != Donation::None),
+                    None => true,
+                };
+                if candidate && buffer.is_deleted().unwrap_or(false) {
+                    buffer.mark_donated();
+                }
+            }
+        }
+    }
+
     pub fn execute_sync<'a, I>(
         &self,
         inputs: I,
@@ -212,9 +462,7 @@ impl LoadedExecutable {
         I: ExecutionInputs,
     {
         let (events, outputs) = self.call_execute(inputs, options)?;
-        for event in events {
-            event.wait()?;
-        }
+        self.wait_for_completion_sync(events, options, None)?;
         Ok(outputs)
     }
 
@@ -227,10 +475,208 @@ impl LoadedExecutable {
         I: ExecutionInputs,
     {
         let (events, outputs) = self.call_execute(inputs, options)?;
+        self.wait_for_completion(events, options, None).await?;
+        Ok(outputs)
+    }
+
+    /// Waits for the completion `events` of a launch made with `options`.
+    ///
+    /// If the options' deadline passes or its cancellation token is cancelled
+    /// first, the launch is poisoned on `device`, or on every addressable
+    /// device if `None`, and `Error::DeadlineExceeded` or `Error::Cancelled`
+    /// is returned.
+    pub(crate) async fn wait_for_completion(
+        &self,
+        events: Vec<Event>,
+        options: &ExecuteOptions<'_>,
+        device: Option<&Device>,
+    ) -> Result<()> {
+        if !options.is_interruptible() {
+            return Event::join_all(events).await;
+        }
+        let deadline = async {
+            match options.get_deadline() {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+        let cancelled = async {
+            match options.get_cancellation_token() {
+                Some(token) => token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            result = Event::join_all(events) => result,
+            _ = deadline => Err(self.interrupt(options, device, Error::DeadlineExceeded)),
+            _ = cancelled => Err(self.interrupt(options, device, Error::Cancelled)),
+        }
+    }
+
+    /// Blocking version of [`LoadedExecutable::wait_for_completion`].
+    pub(crate) fn wait_for_completion_sync(
+        &self,
+        events: Vec<Event>,
+        options: &ExecuteOptions<'_>,
+        device: Option<&Device>,
+    ) -> Result<()> {
+        let mut result = Ok(());
         for event in events {
-            event.await?;
+            if options.is_interruptible() {
+                let state = Arc::new((Mutex::new(false), Condvar::new()));
+                let notify = Arc::clone(&state);
+                event.on_ready(move |_| {
+                    let (ready, condvar) = &*notify;
+                    *ready.lock().unwrap_or_else(|e| e.into_inner()) = true;
+                    condvar.notify_all();
+                })?;
+                let (ready, condvar) = &*state;
+                let mut guard = ready.lock().unwrap_or_else(|e| e.into_inner());
+                while !*guard {
+                    if let Some(err) = interruption(options) {
+                        drop(guard);
+                        return Err(self.interrupt(options, device, err));
+                    }
+                    // Wake up at the deadline, and periodically to observe
+                    // a cancellation token.
+                    let mut timeout = options
+                        .get_deadline()
+                        .map(|d| d.saturating_duration_since(Instant::now()))
+                        .unwrap_or(CANCELLATION_POLL_INTERVAL);
+                    if options.get_cancellation_token().is_some() {
+                        timeout = timeout.min(CANCELLATION_POLL_INTERVAL);
+                    }
+                    guard = condvar
+                        .wait_timeout(guard, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            }
+            let event_result = event.wait();
+            if result.is_ok() {
+                result = event_result;
+            }
         }
-        Ok(outputs)
+        result
+    }
+
+    /// Poisons this launch on its devices and returns `err`.
+    ///
+    /// Poisoning fails the launch's outputs with `err`'s code. Devices whose
+    /// execution already finished are not affected.
+    fn interrupt(
+        &self,
+        options: &ExecuteOptions<'_>,
+        device: Option<&Device>,
+        err: Error,
+    ) -> Error {
+        let code: ErrorCode = err.code();
+        let message = err.to_string();
+        // Best effort: the error returned to the caller is the same whether
+        // or not the devices could be poisoned.
+        let poison = |device: &Device| {
+            let _ = device.poison_launch(options.get_launch_id(), code, &message);
+        };
+        match device {
+            Some(device) => poison(device),
+            None => self
+                .addressable_devices()
+                .unwrap_or_default()
+                .iter()
+                .for_each(poison),
+        }
+        err
+    }
+
+    /// Executes on a single device with inputs and outputs addressed by name.
+    ///
+    /// `names` maps the positional arguments and outputs of this executable;
+    /// see `IoNames::bind` for the errors reported on missing or extra inputs.
+    pub fn execute_named_sync<'a>(
+        &self,
+        names: &IoNames,
+        inputs: HashMap<String, Buffer>,
+        options: &'a ExecuteOptions<'a>,
+    ) -> Result<HashMap<String, Buffer>> {
+        let inputs = names.bind(inputs)?;
+        let outputs = self.execute_sync(inputs, options)?;
+        names.name_outputs(outputs.into_iter().next().unwrap_or_default())
+    }
+
+    /// Async version of [`LoadedExecutable::execute_named_sync`].
+    pub async fn execute_named<'a>(
+        &self,
+        names: &IoNames,
+        inputs: HashMap<String, Buffer>,
+        options: &'a ExecuteOptions<'a>,
+    ) -> Result<HashMap<String, Buffer>> {
+        let inputs = names.bind(inputs)?;
+        let outputs = self.execute(inputs, options).await?;
+        names.name_outputs(outputs.into_iter().next().unwrap_or_default())
+    }
+
+    /// Executes on a single device with structured inputs, returning the
+    /// outputs structured by `output_def`.
+    ///
+    /// `inputs` is flattened into positional arguments the same way JAX
+    /// flattens the arguments of the function the module was exported from.
+    /// Fails with `Error::TreeMismatch` if the number of outputs does not
+    /// match `output_def`.
+    pub fn execute_tree_sync<'a, I: PjrtTree>(
+        &self,
+        inputs: I,
+        output_def: &TreeDef,
+        options: &'a ExecuteOptions<'a>,
+    ) -> Result<Tree<Buffer>> {
+        let (inputs, _) = inputs.flatten();
+        let outputs = self.execute_sync(inputs, options)?;
+        Tree::new(
+            output_def.clone(),
+            outputs.into_iter().next().unwrap_or_default(),
+        )
+    }
+
+    /// Async version of [`LoadedExecutable::execute_tree_sync`].
+    pub async fn execute_tree<'a, I: PjrtTree>(
+        &self,
+        inputs: I,
+        output_def: &TreeDef,
+        options: &'a ExecuteOptions<'a>,
+    ) -> Result<Tree<Buffer>> {
+        let (inputs, _) = inputs.flatten();
+        let outputs = self.execute(inputs, options).await?;
+        Tree::new(
+            output_def.clone(),
+            outputs.into_iter().next().unwrap_or_default(),
+        )
+    }
+
+    /// Executes on a single device with structured inputs and rebuilds the
+    /// outputs as an `O`.
+    ///
+    /// `O` must have a fixed structure (see `PjrtTree::static_tree_def`);
+    /// use [`LoadedExecutable::execute_tree_sync`] otherwise.
+    pub fn execute_as_sync<'a, I: PjrtTree, O: PjrtTree>(
+        &self,
+        inputs: I,
+        options: &'a ExecuteOptions<'a>,
+    ) -> Result<O> {
+        let output_def = static_output_def::<O>()?;
+        self.execute_tree_sync(inputs, &output_def, options)?
+            .unflatten()
+    }
+
+    /// Async version of [`LoadedExecutable::execute_as_sync`].
+    pub async fn execute_as<'a, I: PjrtTree, O: PjrtTree>(
+        &self,
+        inputs: I,
+        options: &'a ExecuteOptions<'a>,
+    ) -> Result<O> {
+        let output_def = static_output_def::<O>()?;
+        self.execute_tree(inputs, &output_def, options)
+            .await?
+            .unflatten()
     }
 
     pub fn execution<I>(&self, inputs: I) -> Execution<'_, I>
@@ -239,4 +685,67 @@ impl LoadedExecutable {
     {
         Execution::new(self, inputs)
     }
+
+    /// Creates a [`Pipeline`] that overlaps input uploads with execution.
+    pub fn pipeline(&self) -> Result<Pipeline<'_>> {
+        Pipeline::new(self)
+    }
+}
+
+/// Keeps `value` alive until every one of `events` is ready.
+///
+/// A launch uses its context and host callbacks from plugin threads until it
+/// completes, which may be after the options, the events and the outputs are
+/// dropped.
+fn keep_alive_until_ready<T>(events: &[Event], value: &Arc<T>)
+where
+    T: Send + Sync + 'static,
+{
+    for event in events {
+        let held = Arc::clone(value);
+        if event.on_ready(move |_| drop(held)).is_err() {
+            // Without a way to observe completion, leaking is the only safe
+            // option.
+            std::mem::forget(Arc::clone(value));
+        }
+    }
+}
+
+fn static_output_def<O: PjrtTree>() -> Result<TreeDef> {
+    O::static_tree_def().ok_or_else(|| {
+        Error::InvalidArgument(format!(
+            "{} has no fixed tree structure; use execute_tree with an explicit output definition",
+            std::any::type_name::<O>()
+        ))
+    })
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("{n} {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+fn describe_shape(primitive_type: PrimitiveType, dims: &[i64]) -> String {
+    format!("{:?}{:?}", primitive_type, dims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plural() {
+        assert_eq!(plural(1, "device"), "1 device");
+        assert_eq!(plural(0, "argument"), "0 arguments");
+        assert_eq!(plural(3, "argument"), "3 arguments");
+    }
+
+    #[test]
+    fn test_describe_shape() {
+        assert_eq!(describe_shape(PrimitiveType::F32, &[2, 3]), "F32[2, 3]");
+        assert_eq!(describe_shape(PrimitiveType::S32, &[]), "S32[]");
+    }
 }
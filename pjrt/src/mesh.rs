@@ -0,0 +1,540 @@
+//! Device Meshes
+//!
+//! This module provides [`Mesh`], an N-dimensional grid of devices with named
+//! axes such as `data`, `model` or `pipeline`. Sharded programs describe how
+//! arrays are split over the axes of a mesh, and the mesh maps those
+//! positions to devices.
+//!
+//! A mesh stores global device ids in row-major order: the last axis varies
+//! fastest. Meshes built from a client or a topology order the devices by
+//! their physical coordinates when the plugin reports them, so that devices
+//! adjacent along the last axis are also physically adjacent.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let mesh = Mesh::from_client(&client, &[("data", 2), ("model", 4)])?;
+//! println!("{mesh}"); // Mesh{'data': 2, 'model': 4}
+//!
+//! // The 4 devices holding the first data-parallel replica.
+//! let replica = mesh.select("data", 0)?;
+//!
+//! let build_options = ExecutableBuildOptions::new().mesh(&mesh);
+//! let assignment = mesh.to_device_assignment(&[])?;
+//! ```
+
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use crate::named_value::Value;
+use crate::{
+    Client, Device, DeviceAssignment, DeviceDescription, Error, GlobalDeviceId, Result,
+    TopologyDescription,
+};
+
+/// An N-dimensional grid of devices with named axes.
+///
+/// # Thread Safety
+///
+/// `Mesh` is `Send + Sync`: it holds global device ids rather than
+/// [`Device`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mesh {
+    axis_names: Vec<String>,
+    shape: Vec<usize>,
+    device_ids: Vec<GlobalDeviceId>,
+}
+
+impl Mesh {
+    /// Creates a mesh with the given `(name, size)` axes over `device_ids`,
+    /// listed in row-major order.
+    pub fn new(axes: &[(&str, usize)], device_ids: Vec<GlobalDeviceId>) -> Result<Self> {
+        let mut axis_names: Vec<String> = Vec::with_capacity(axes.len());
+        for (name, size) in axes {
+            if *size == 0 {
+                return Err(Error::InvalidArgument(format!(
+                    "mesh axis {name} has size 0"
+                )));
+            }
+            if axis_names.iter().any(|n| n == name) {
+                return Err(Error::InvalidArgument(format!(
+                    "duplicate mesh axis: {name}"
+                )));
+            }
+            axis_names.push(name.to_string());
+        }
+        let shape: Vec<usize> = axes.iter().map(|(_, size)| *size).collect();
+        let size: usize = shape.iter().product();
+        if size != device_ids.len() {
+            return Err(Error::InvalidArgument(format!(
+                "mesh shape {shape:?} needs {size} devices, got {}",
+                device_ids.len()
+            )));
+        }
+        for (i, id) in device_ids.iter().enumerate() {
+            if device_ids[..i].contains(id) {
+                return Err(Error::InvalidArgument(format!(
+                    "device {id} appears twice in mesh"
+                )));
+            }
+        }
+        Ok(Self {
+            axis_names,
+            shape,
+            device_ids,
+        })
+    }
+
+    /// Creates a mesh over all devices of `client`, in the default order.
+    pub fn from_client(client: &Client, axes: &[(&str, usize)]) -> Result<Self> {
+        Self::from_devices(&client.devices()?, axes)
+    }
+
+    /// Creates a mesh over `devices`, in the default order.
+    pub fn from_devices(devices: &[Device], axes: &[(&str, usize)]) -> Result<Self> {
+        let descriptions = devices
+            .iter()
+            .map(Device::description)
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_descriptions(&descriptions, axes)
+    }
+
+    /// Creates a mesh over all devices of `topology`, in the default order.
+    ///
+    /// This allows building meshes for ahead-of-time compilation, without
+    /// a client.
+    pub fn from_topology(topology: &TopologyDescription, axes: &[(&str, usize)]) -> Result<Self> {
+        Self::from_descriptions(&topology.device_descriptions()?, axes)
+    }
+
+    /// Creates a mesh over the described devices, in the default order.
+    pub fn from_descriptions(
+        descriptions: &[DeviceDescription],
+        axes: &[(&str, usize)],
+    ) -> Result<Self> {
+        let devices = descriptions
+            .iter()
+            .map(MeshDevice::from_description)
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(axes, default_device_order(devices))
+    }
+
+    pub fn axis_names(&self) -> &[String] {
+        &self.axis_names
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Returns the number of axes.
+    pub fn ndim(&self) -> usize {
+        self.shape.len()
+    }
+
+    /// Returns the number of devices.
+    pub fn size(&self) -> usize {
+        self.device_ids.len()
+    }
+
+    /// Returns the position of the axis `name`.
+    pub fn axis_index(&self, name: &str) -> Result<usize> {
+        self.axis_names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown mesh axis: {name}")))
+    }
+
+    pub fn axis_size(&self, name: &str) -> Result<usize> {
+        Ok(self.shape[self.axis_index(name)?])
+    }
+
+    /// Returns the device ids in row-major order.
+    pub fn device_ids(&self) -> &[GlobalDeviceId] {
+        &self.device_ids
+    }
+
+    /// Returns the device at `coords`, one index per axis.
+    pub fn device_id(&self, coords: &[usize]) -> Result<GlobalDeviceId> {
+        if coords.len() != self.ndim() || coords.iter().zip(&self.shape).any(|(c, s)| c >= s) {
+            return Err(Error::InvalidArgument(format!(
+                "coordinates {coords:?} out of bounds for mesh shape {:?}",
+                self.shape
+            )));
+        }
+        Ok(self.device_ids[ravel_index(coords, &self.shape)])
+    }
+
+    /// Returns the coordinates of device `id`, or `None` if it is not in the
+    /// mesh.
+    pub fn coords(&self, id: GlobalDeviceId) -> Option<Vec<usize>> {
+        let index = self.device_ids.iter().position(|d| *d == id)?;
+        Some(unravel_index(index, &self.shape))
+    }
+
+    /// Returns a mesh over the same devices, in the same order, with new
+    /// axes.
+    pub fn reshape(&self, axes: &[(&str, usize)]) -> Result<Self> {
+        Self::new(axes, self.device_ids.clone())
+    }
+
+    /// Returns the sub-mesh at `index` along `axis`, without that axis.
+    pub fn select(&self, axis: &str, index: usize) -> Result<Self> {
+        let mut sub = self.slice(axis, index..index + 1)?;
+        let axis = self.axis_index(axis)?;
+        sub.axis_names.remove(axis);
+        sub.shape.remove(axis);
+        Ok(sub)
+    }
+
+    /// Returns the sub-mesh spanning `range` along `axis`.
+    pub fn slice(&self, axis: &str, range: Range<usize>) -> Result<Self> {
+        let axis_index = self.axis_index(axis)?;
+        if range.start >= range.end || range.end > self.shape[axis_index] {
+            return Err(Error::InvalidArgument(format!(
+                "invalid range {range:?} for mesh axis {axis} of size {}",
+                self.shape[axis_index]
+            )));
+        }
+        let mut shape = self.shape.clone();
+        shape[axis_index] = range.len();
+        let device_ids = self
+            .device_ids
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| range.contains(&unravel_index(*i, &self.shape)[axis_index]))
+            .map(|(_, id)| *id)
+            .collect();
+        Ok(Self {
+            axis_names: self.axis_names.clone(),
+            shape,
+            device_ids,
+        })
+    }
+
+    /// Converts the mesh to a replicas × partitions device assignment.
+    ///
+    /// The devices along `replica_axes`, in the given order, become the
+    /// replicas and the devices along the other axes, in mesh order, the
+    /// partitions. With no replica axes every device is a partition, which
+    /// is what SPMD-partitioned programs expect.
+    pub fn to_device_assignment(&self, replica_axes: &[&str]) -> Result<DeviceAssignment> {
+        let mut order = Vec::with_capacity(self.ndim());
+        for axis in replica_axes {
+            let index = self.axis_index(axis)?;
+            if order.contains(&index) {
+                return Err(Error::InvalidArgument(format!(
+                    "duplicate mesh axis: {axis}"
+                )));
+            }
+            order.push(index);
+        }
+        let num_replicas = order.iter().map(|i| self.shape[*i]).product();
+        let partition_axes: Vec<usize> = (0..self.ndim()).filter(|i| !order.contains(i)).collect();
+        order.extend(partition_axes);
+        let assignments = self.transposed_device_ids(&order);
+        DeviceAssignment::new(num_replicas, self.size() / num_replicas, assignments)
+    }
+
+    /// Returns the device ids in row-major order of the mesh with its axes
+    /// permuted to `order`.
+    pub(crate) fn transposed_device_ids(&self, order: &[usize]) -> Vec<GlobalDeviceId> {
+        self.transposed_positions(order)
+            .into_iter()
+            .map(|position| self.device_ids[position])
+            .collect()
+    }
+
+    /// Returns the positions of the devices in [`Mesh::device_ids`] in
+    /// row-major order of the mesh with its axes permuted to `order`.
+    pub(crate) fn transposed_positions(&self, order: &[usize]) -> Vec<usize> {
+        let transposed_shape: Vec<usize> = order.iter().map(|i| self.shape[*i]).collect();
+        let mut coords = vec![0; self.ndim()];
+        (0..self.size())
+            .map(|index| {
+                for (axis, c) in order.iter().zip(unravel_index(index, &transposed_shape)) {
+                    coords[*axis] = c;
+                }
+                ravel_index(&coords, &self.shape)
+            })
+            .collect()
+    }
+
+    /// Returns the shape in the form expected by
+    /// [`ExecutableBuildOptions::auto_spmd_partitioning_mesh_shape`].
+    ///
+    /// [`ExecutableBuildOptions::auto_spmd_partitioning_mesh_shape`]: crate::ExecutableBuildOptions::auto_spmd_partitioning_mesh_shape
+    pub fn mesh_shape(&self) -> Vec<i64> {
+        self.shape.iter().map(|s| *s as i64).collect()
+    }
+
+    /// Returns the logical device ids in the form expected by
+    /// [`ExecutableBuildOptions::auto_spmd_partitioning_mesh_ids`].
+    ///
+    /// These are positions in the device assignment returned by
+    /// `to_device_assignment(&[])`, which lists the devices in mesh order,
+    /// not global device ids.
+    ///
+    /// [`ExecutableBuildOptions::auto_spmd_partitioning_mesh_ids`]: crate::ExecutableBuildOptions::auto_spmd_partitioning_mesh_ids
+    pub fn mesh_ids(&self) -> Vec<i64> {
+        (0..self.size() as i64).collect()
+    }
+}
+
+impl Display for Mesh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mesh{{")?;
+        for (i, (name, size)) in self.axis_names.iter().zip(&self.shape).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "'{name}': {size}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Converts coordinates into a row-major index.
+pub(crate) fn ravel_index(coords: &[usize], shape: &[usize]) -> usize {
+    coords
+        .iter()
+        .zip(shape)
+        .fold(0, |index, (c, s)| index * s + c)
+}
+
+/// Converts a row-major index into coordinates.
+pub(crate) fn unravel_index(mut index: usize, shape: &[usize]) -> Vec<usize> {
+    let mut coords = vec![0; shape.len()];
+    for (c, s) in coords.iter_mut().zip(shape).rev() {
+        *c = index % s;
+        index /= s;
+    }
+    coords
+}
+
+/// The properties of a device that determine its default mesh position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MeshDevice {
+    id: GlobalDeviceId,
+    process_index: i32,
+    /// Physical coordinates, reported by TPU and some GPU plugins.
+    coords: Option<Vec<i64>>,
+    core_on_chip: i64,
+}
+
+impl MeshDevice {
+    fn from_description(description: &DeviceDescription) -> Result<Self> {
+        let attributes = description.attributes()?;
+        let coords = match attributes.get("coords") {
+            Some(Value::I64List(coords)) => Some(coords.clone()),
+            _ => None,
+        };
+        let core_on_chip = match attributes.get("core_on_chip") {
+            Some(Value::I64(core)) => *core,
+            _ => 0,
+        };
+        Ok(Self {
+            id: description.id()?,
+            process_index: description.process_index()?,
+            coords,
+            core_on_chip,
+        })
+    }
+}
+
+/// Orders devices for a row-major mesh.
+///
+/// If every device reports physical coordinates of the same rank, devices
+/// are sorted by coordinates with the first varying fastest, then by core,
+/// so that consecutive devices are physical neighbors. Otherwise devices are
+/// grouped by process, then sorted by id.
+fn default_device_order(mut devices: Vec<MeshDevice>) -> Vec<GlobalDeviceId> {
+    let rank = devices
+        .first()
+        .and_then(|d| d.coords.as_ref())
+        .map(Vec::len);
+    let has_coords = rank.is_some()
+        && devices
+            .iter()
+            .all(|d| d.coords.as_ref().map(Vec::len) == rank);
+    if has_coords {
+        devices.sort_by(|a, b| {
+            let a_coords = a.coords.iter().flatten().rev();
+            let b_coords = b.coords.iter().flatten().rev();
+            a_coords
+                .cmp(b_coords)
+                .then(a.core_on_chip.cmp(&b.core_on_chip))
+                .then(a.id.cmp(&b.id))
+        });
+    } else {
+        devices.sort_by_key(|d| (d.process_index, d.id));
+    }
+    devices.into_iter().map(|d| d.id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh_2x4() -> Mesh {
+        Mesh::new(&[("data", 2), ("model", 4)], vec![7, 6, 5, 4, 3, 2, 1, 0]).unwrap()
+    }
+
+    #[test]
+    fn test_new_validates() {
+        assert!(Mesh::new(&[("x", 2)], vec![0, 1]).is_ok());
+        assert!(Mesh::new(&[("x", 2)], vec![0]).is_err());
+        assert!(Mesh::new(&[("x", 0)], vec![]).is_err());
+        assert!(Mesh::new(&[("x", 1), ("x", 1)], vec![0]).is_err());
+        assert!(Mesh::new(&[("x", 2)], vec![3, 3]).is_err());
+
+        // A mesh with no axes has one device.
+        let scalar = Mesh::new(&[], vec![5]).unwrap();
+        assert_eq!(scalar.device_id(&[]).unwrap(), 5);
+        assert_eq!(scalar.to_string(), "Mesh{}");
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mesh = mesh_2x4();
+        assert_eq!(mesh.axis_names(), ["data", "model"]);
+        assert_eq!(mesh.shape(), [2, 4]);
+        assert_eq!(mesh.ndim(), 2);
+        assert_eq!(mesh.size(), 8);
+        assert_eq!(mesh.axis_index("model").unwrap(), 1);
+        assert_eq!(mesh.axis_size("data").unwrap(), 2);
+        assert!(matches!(
+            mesh.axis_size("pipeline"),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(mesh.device_id(&[1, 2]).unwrap(), 1);
+        assert!(mesh.device_id(&[2, 0]).is_err());
+        assert!(mesh.device_id(&[0]).is_err());
+        assert_eq!(mesh.coords(1), Some(vec![1, 2]));
+        assert_eq!(mesh.coords(8), None);
+        assert_eq!(mesh.to_string(), "Mesh{'data': 2, 'model': 4}");
+    }
+
+    #[test]
+    fn test_reshape() {
+        let mesh = mesh_2x4().reshape(&[("a", 4), ("b", 1), ("c", 2)]).unwrap();
+        assert_eq!(mesh.shape(), [4, 1, 2]);
+        assert_eq!(mesh.device_ids(), mesh_2x4().device_ids());
+        assert!(mesh_2x4().reshape(&[("a", 3)]).is_err());
+    }
+
+    #[test]
+    fn test_select_and_slice() {
+        let mesh = mesh_2x4();
+        let replica = mesh.select("data", 1).unwrap();
+        assert_eq!(replica.axis_names(), ["model"]);
+        assert_eq!(replica.device_ids(), [3, 2, 1, 0]);
+
+        let column = mesh.select("model", 2).unwrap();
+        assert_eq!(column.axis_names(), ["data"]);
+        assert_eq!(column.device_ids(), [5, 1]);
+
+        let half = mesh.slice("model", 1..3).unwrap();
+        assert_eq!(half.shape(), [2, 2]);
+        assert_eq!(half.device_ids(), [6, 5, 2, 1]);
+
+        assert!(mesh.select("data", 2).is_err());
+        assert!(mesh.slice("model", 2..2).is_err());
+        assert!(mesh.slice("model", 3..5).is_err());
+    }
+
+    #[test]
+    fn test_to_device_assignment() {
+        let mesh = Mesh::new(&[("data", 2), ("model", 3)], (0..6).collect()).unwrap();
+
+        let spmd = mesh.to_device_assignment(&[]).unwrap();
+        assert_eq!(spmd.num_replicas(), 1);
+        assert_eq!(spmd.num_partitions(), 6);
+        assert_eq!(spmd.lookup_logical_id(4).unwrap().partition_id, 4);
+
+        let data_parallel = mesh.to_device_assignment(&["data"]).unwrap();
+        assert_eq!(data_parallel.num_replicas(), 2);
+        assert_eq!(data_parallel.num_partitions(), 3);
+        let id = data_parallel.lookup_logical_id(4).unwrap();
+        assert_eq!((id.replica_id, id.partition_id), (1, 1));
+
+        // Replica axes need not come first in the mesh.
+        let model_replicas = mesh.to_device_assignment(&["model"]).unwrap();
+        assert_eq!(model_replicas.num_replicas(), 3);
+        let id = model_replicas.lookup_logical_id(4).unwrap();
+        assert_eq!((id.replica_id, id.partition_id), (1, 1));
+        let id = model_replicas.lookup_logical_id(2).unwrap();
+        assert_eq!((id.replica_id, id.partition_id), (2, 0));
+
+        let all = mesh.to_device_assignment(&["model", "data"]).unwrap();
+        assert_eq!(all.num_partitions(), 1);
+        assert_eq!(all.lookup_logical_id(3).unwrap().replica_id, 1);
+
+        assert!(mesh.to_device_assignment(&["data", "data"]).is_err());
+        assert!(mesh.to_device_assignment(&["pipeline"]).is_err());
+    }
+
+    #[test]
+    fn test_mesh_shape_and_ids() {
+        let mesh = mesh_2x4();
+        assert_eq!(mesh.mesh_shape(), vec![2, 4]);
+        assert_eq!(mesh.mesh_ids(), (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_unravel_index() {
+        assert_eq!(unravel_index(0, &[2, 3]), vec![0, 0]);
+        assert_eq!(unravel_index(5, &[2, 3]), vec![1, 2]);
+        assert_eq!(unravel_index(3, &[2, 1, 2]), vec![1, 0, 1]);
+        assert_eq!(unravel_index(0, &[]), Vec::<usize>::new());
+        for index in 0..12 {
+            assert_eq!(
+                ravel_index(&unravel_index(index, &[3, 1, 4]), &[3, 1, 4]),
+                index
+            );
+        }
+    }
+
+    fn device(id: i32, process_index: i32, coords: Option<Vec<i64>>, core: i64) -> MeshDevice {
+        MeshDevice {
+            id,
+            process_index,
+            coords,
+            core_on_chip: core,
+        }
+    }
+
+    #[test]
+    fn test_default_device_order_by_process() {
+        let devices = vec![
+            device(0, 1, None, 0),
+            device(3, 0, None, 0),
+            device(1, 0, None, 0),
+            device(2, 1, None, 0),
+        ];
+        assert_eq!(default_device_order(devices), [1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn test_default_device_order_by_coords() {
+        // A 2x2 grid of chips with 2 cores each, listed out of order.
+        let devices = vec![
+            device(0, 0, Some(vec![1, 1, 0]), 0),
+            device(1, 0, Some(vec![0, 0, 0]), 1),
+            device(2, 0, Some(vec![1, 0, 0]), 0),
+            device(3, 0, Some(vec![0, 1, 0]), 0),
+            device(4, 0, Some(vec![0, 0, 0]), 0),
+        ];
+        assert_eq!(default_device_order(devices), [4, 1, 2, 3, 0]);
+
+        // Devices without coordinates fall back to ids.
+        let devices = vec![device(1, 0, Some(vec![0, 0, 0]), 0), device(0, 0, None, 0)];
+        assert_eq!(default_device_order(devices), [0, 1]);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Mesh>();
+    }
+}
@@ -0,0 +1,653 @@
+//! MLIR Program Metadata
+//!
+//! This module inspects StableHLO programs without depending on an MLIR
+//! parser. For textual MLIR it extracts:
+//!
+//! - The module name and module attributes
+//! - The entry function (`@main`, or the only public function) and its
+//!   argument and result types, with `tensor<...>` types decoded into
+//!   dimensions and a `PrimitiveType`
+//! - `mhlo.sharding` and `sdy.sharding` annotations
+//!
+//! Only the syntax needed to split argument, result and attribute lists is
+//! understood: nesting of `()`, `[]`, `{}` and `<>`, and quoted strings. Other
+//! attribute values are kept as raw text.
+//!
+//! For MLIR bytecode only the header is read, which is enough to tell bytecode
+//! from text and to recover the producer (e.g. `StableHLO_v1.7.3`).
+//!
+//! # Examples
+//!
+//! ```rust
+//! use pjrt::{PrimitiveType, Program, ProgramFormat, ShardingAnnotation};
+//!
+//! let program = Program::new(
+//!     ProgramFormat::MLIR,
+//!     r#"module @jit_f attributes {mhlo.num_replicas = 1 : i32} {
+//!   func.func public @main(%arg0: tensor<2x3xf32> {mhlo.sharding = "{replicated}"}) -> tensor<2x3xf32> {
+//!     return %arg0 : tensor<2x3xf32>
+//!   }
+//! }"#,
+//! );
+//!
+//! let metadata = program.metadata().unwrap();
+//! assert_eq!(metadata.module_name.as_deref(), Some("jit_f"));
+//! assert_eq!(metadata.entry_function, "main");
+//!
+//! let arg = &metadata.arguments[0];
+//! let tensor = arg.tensor_type().unwrap();
+//! assert_eq!(tensor.dims, vec![2, 3]);
+//! assert_eq!(tensor.primitive_type, PrimitiveType::F32);
+//! assert_eq!(
+//!     arg.sharding(),
+//!     Some(ShardingAnnotation::Mhlo("{replicated}".to_string()))
+//! );
+//! ```
+
+use crate::{Error, PrimitiveType, Result};
+
+/// Magic number at the start of MLIR bytecode files (`"ML\xEFR"`).
+pub(crate) const BYTECODE_MAGIC: &[u8] = b"ML\xEFR";
+
+/// Metadata of a textual MLIR module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramMetadata {
+    /// Symbol name of the module (`module @name`), if any.
+    pub module_name: Option<String>,
+    /// Module attribute dictionary entries as `(key, raw value)` pairs.
+    pub module_attributes: Vec<(String, String)>,
+    /// Name of the entry function, without the leading `@`.
+    pub entry_function: String,
+    /// Arguments of the entry function.
+    pub arguments: Vec<ValueInfo>,
+    /// Results of the entry function.
+    pub results: Vec<ValueInfo>,
+}
+
+impl ProgramMetadata {
+    /// Returns the raw text of module attribute `key`.
+    pub fn module_attribute(&self, key: &str) -> Option<&str> {
+        find_attribute(&self.module_attributes, key)
+    }
+
+    /// Returns module attribute `key` decoded as a string literal.
+    pub fn module_string_attribute(&self, key: &str) -> Option<String> {
+        self.module_attribute(key).and_then(parse_string_literal)
+    }
+}
+
+/// An argument or result of the entry function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueInfo {
+    /// SSA name without the leading `%` (arguments only).
+    pub name: Option<String>,
+    /// The type as written, e.g. `tensor<2x3xf32>`.
+    pub ty: String,
+    /// Attribute dictionary entries as `(key, raw value)` pairs.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl ValueInfo {
+    /// Returns the raw text of attribute `key`.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        find_attribute(&self.attributes, key)
+    }
+
+    /// Returns attribute `key` decoded as a string literal.
+    pub fn string_attribute(&self, key: &str) -> Option<String> {
+        self.attribute(key).and_then(parse_string_literal)
+    }
+
+    /// Decodes the type if it is a ranked `tensor<...>` type.
+    pub fn tensor_type(&self) -> Option<TensorType> {
+        TensorType::parse(&self.ty)
+    }
+
+    /// Returns the sharding annotation, preferring `sdy.sharding`.
+    pub fn sharding(&self) -> Option<ShardingAnnotation> {
+        if let Some(sdy) = self.attribute("sdy.sharding") {
+            return Some(ShardingAnnotation::Sdy(sdy.to_string()));
+        }
+        self.string_attribute("mhlo.sharding")
+            .map(ShardingAnnotation::Mhlo)
+    }
+}
+
+/// A sharding annotation attached to an argument or result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ShardingAnnotation {
+    /// The string value of `mhlo.sharding`, e.g. `{devices=[2,1]0,1}`.
+    Mhlo(String),
+    /// The raw `#sdy.sharding<...>` attribute of `sdy.sharding`.
+    Sdy(String),
+}
+
+/// A decoded ranked tensor type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TensorType {
+    /// Dimension sizes; dynamic dimensions (`?`) are `-1`.
+    pub dims: Vec<i64>,
+    /// Element type.
+    pub primitive_type: PrimitiveType,
+}
+
+impl TensorType {
+    /// Parses a type such as `tensor<2x?xbf16>` or `tensor<f32>`.
+    ///
+    /// Returns `None` for unranked tensors, non-tensor types and element types
+    /// without a `PrimitiveType` equivalent.
+    pub fn parse(ty: &str) -> Option<Self> {
+        let inner = ty.trim().strip_prefix("tensor<")?.strip_suffix('>')?;
+        // Drop the encoding, e.g. `#stablehlo.bounds<...>`.
+        let shape = split_top_level(inner, ',').first()?.trim();
+        // Take dimensions from the left, as element types such as
+        // `complex<f32>` may contain an `x` themselves.
+        let mut dims = vec![];
+        let mut element = shape;
+        while let Some((dim, rest)) = element.split_once('x') {
+            let dim = match dim {
+                "?" => -1,
+                dim => match dim.parse() {
+                    Ok(dim) => dim,
+                    Err(_) => break,
+                },
+            };
+            dims.push(dim);
+            element = rest;
+        }
+        Some(Self {
+            dims,
+            primitive_type: primitive_type_from_mlir(element)?,
+        })
+    }
+}
+
+/// Maps an MLIR element type to a `PrimitiveType`.
+fn primitive_type_from_mlir(element: &str) -> Option<PrimitiveType> {
+    let ty = match element {
+        "i1" => PrimitiveType::Pred,
+        "i2" => PrimitiveType::S2,
+        "i4" => PrimitiveType::S4,
+        "i8" => PrimitiveType::S8,
+        "i16" => PrimitiveType::S16,
+        "i32" => PrimitiveType::S32,
+        "i64" => PrimitiveType::S64,
+        "ui2" => PrimitiveType::U2,
+        "ui4" => PrimitiveType::U4,
+        "ui8" => PrimitiveType::U8,
+        "ui16" => PrimitiveType::U16,
+        "ui32" => PrimitiveType::U32,
+        "ui64" => PrimitiveType::U64,
+        "f16" => PrimitiveType::F16,
+        "bf16" => PrimitiveType::BF16,
+        "f32" => PrimitiveType::F32,
+        "f64" => PrimitiveType::F64,
+        "complex<f32>" => PrimitiveType::C64,
+        "complex<f64>" => PrimitiveType::C128,
+        "f8E5M2" => PrimitiveType::F8E5M2,
+        "f8E4M3FN" => PrimitiveType::F8E4M3FN,
+        "f8E4M3B11FNUZ" => PrimitiveType::F8E4M3B11FNUZ,
+        "f8E5M2FNUZ" => PrimitiveType::F8E5M2FNUZ,
+        "f8E4M3FNUZ" => PrimitiveType::F8E4M3FNUZ,
+        "f8E4M3" => PrimitiveType::F8E4M3,
+        "f8E3M4" => PrimitiveType::F8E3M4,
+        "f8E8M0FNU" => PrimitiveType::F8E8M0FNU,
+        "f4E2M1FN" => PrimitiveType::F4E2M1FN,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// Returns `true` if `code` starts with the MLIR bytecode magic number.
+pub(crate) fn is_bytecode(code: &[u8]) -> bool {
+    code.starts_with(BYTECODE_MAGIC)
+}
+
+/// Reads the producer string from an MLIR bytecode header.
+///
+/// The header is the magic number, the bytecode version as a prefix varint
+/// and a NUL-terminated producer string.
+pub(crate) fn bytecode_producer(code: &[u8]) -> Option<String> {
+    let rest = code.strip_prefix(BYTECODE_MAGIC)?;
+    let first = *rest.first()?;
+    // The number of trailing zeros of the first byte encodes the varint length.
+    let len = if first == 0 {
+        9
+    } else {
+        first.trailing_zeros() as usize + 1
+    };
+    let rest = rest.get(len..)?;
+    let end = rest.iter().position(|&b| b == 0)?;
+    String::from_utf8(rest[..end].to_vec()).ok()
+}
+
+/// Parses the module header and entry function signature of textual MLIR.
+pub(crate) fn parse_metadata(text: &str) -> Result<ProgramMetadata> {
+    let (module_name, module_attributes) = parse_module_header(text)?;
+    let (entry_function, open) = find_entry_function(text)?;
+    let close = matching_close(text, open).ok_or_else(|| invalid("unbalanced argument list"))?;
+    let arguments = split_top_level(&text[open + 1..close], ',')
+        .into_iter()
+        .map(parse_argument)
+        .collect::<Result<Vec<_>>>()?;
+
+    let rest = text[close + 1..].trim_start();
+    let results = match rest.strip_prefix("->") {
+        Some(rest) => {
+            let rest = rest.trim_start();
+            if rest.starts_with('(') {
+                let close =
+                    matching_close(rest, 0).ok_or_else(|| invalid("unbalanced result list"))?;
+                split_top_level(&rest[1..close], ',')
+                    .into_iter()
+                    .map(|s| parse_value(None, s))
+                    .collect::<Result<Vec<_>>>()?
+            } else {
+                let end = find_top_level_whitespace(rest).unwrap_or(rest.len());
+                vec![parse_value(None, &rest[..end])?]
+            }
+        }
+        None => vec![],
+    };
+
+    Ok(ProgramMetadata {
+        module_name,
+        module_attributes,
+        entry_function,
+        arguments,
+        results,
+    })
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidProgramFormat(format!("mlir: {msg}"))
+}
+
+fn find_attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Attribute names and their raw values, in source order.
+type Attributes = Vec<(String, String)>;
+
+/// Parses `module @name attributes {...}` if the text has an explicit module.
+fn parse_module_header(text: &str) -> Result<(Option<String>, Attributes)> {
+    let Some(start) = text.match_indices("module").map(|(i, _)| i).find(|&i| {
+        let line_start = text[..i].rfind('\n').map_or(0, |n| n + 1);
+        let next = text[i + "module".len()..].chars().next();
+        text[line_start..i].trim().is_empty()
+            && matches!(next, Some(c) if c.is_whitespace() || c == '@' || c == '{')
+    }) else {
+        return Ok((None, vec![]));
+    };
+    let mut rest = text[start + "module".len()..].trim_start();
+    let mut name = None;
+    if let Some(after) = rest.strip_prefix('@') {
+        let end = after
+            .find(|c: char| c.is_whitespace() || c == '{')
+            .unwrap_or(after.len());
+        name = Some(after[..end].to_string());
+        rest = after[end..].trim_start();
+    }
+    let mut attributes = vec![];
+    if let Some(after) = rest.strip_prefix("attributes") {
+        let after = after.trim_start();
+        if after.starts_with('{') {
+            let close =
+                matching_close(after, 0).ok_or_else(|| invalid("unbalanced module attributes"))?;
+            attributes = parse_attribute_dict(&after[1..close]);
+        }
+    }
+    Ok((name, attributes))
+}
+
+/// Finds the entry function and the index of its argument list's `(`.
+///
+/// `@main` is preferred; otherwise the only public function is used.
+fn find_entry_function(text: &str) -> Result<(String, usize)> {
+    let mut public = vec![];
+    for (i, _) in text.match_indices("func.func") {
+        let mut rest = &text[i + "func.func".len()..];
+        let mut is_public = true;
+        for visibility in ["public", "private", "nested"] {
+            if let Some(after) = rest.trim_start().strip_prefix(visibility) {
+                is_public = visibility == "public";
+                rest = after;
+            }
+        }
+        let Some(after) = rest.trim_start().strip_prefix('@') else {
+            continue;
+        };
+        let Some(end) = after.find('(') else {
+            continue;
+        };
+        let name = after[..end].trim();
+        let open = text.len() - after.len() + end;
+        if name == "main" {
+            return Ok((name.to_string(), open));
+        }
+        if is_public {
+            public.push((name.to_string(), open));
+        }
+    }
+    match public.len() {
+        1 => Ok(public.remove(0)),
+        _ => Err(invalid("no `func.func @main` found")),
+    }
+}
+
+fn parse_argument(s: &str) -> Result<ValueInfo> {
+    let s = s.trim();
+    let (name, rest) = s
+        .strip_prefix('%')
+        .and_then(|s| s.split_once(':'))
+        .ok_or_else(|| invalid(&format!("malformed argument `{s}`")))?;
+    parse_value(Some(name.trim().to_string()), rest)
+}
+
+fn parse_value(name: Option<String>, s: &str) -> Result<ValueInfo> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(invalid("empty type"));
+    }
+    let end = find_top_level_whitespace(s).unwrap_or(s.len());
+    let ty = s[..end].to_string();
+    let rest = s[end..].trim_start();
+    let attributes = if rest.starts_with('{') {
+        let close = matching_close(rest, 0).ok_or_else(|| invalid("unbalanced attributes"))?;
+        parse_attribute_dict(&rest[1..close])
+    } else {
+        vec![]
+    };
+    Ok(ValueInfo {
+        name,
+        ty,
+        attributes,
+    })
+}
+
+/// Parses the inside of an attribute dictionary (without the braces).
+fn parse_attribute_dict(s: &str) -> Attributes {
+    split_top_level(s, ',')
+        .into_iter()
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((key, value)) => (unquote_key(key.trim()), value.trim().to_string()),
+            None => (unquote_key(entry.trim()), "unit".to_string()),
+        })
+        .collect()
+}
+
+fn unquote_key(key: &str) -> String {
+    parse_string_literal(key).unwrap_or_else(|| key.to_string())
+}
+
+/// Decodes an MLIR string literal such as `"{replicated}"`.
+fn parse_string_literal(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = Vec::with_capacity(inner.len());
+    let mut bytes = inner.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next()? {
+            b'n' => out.push(b'\n'),
+            b't' => out.push(b'\t'),
+            b'"' => out.push(b'"'),
+            b'\\' => out.push(b'\\'),
+            hi => {
+                let lo = bytes.next()?;
+                let hex = [hi, lo];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Walks `s`, calling `f(index, byte, depth)` for every byte outside string
+/// literals. Returning `true` from `f` stops the walk.
+fn scan(s: &str, mut f: impl FnMut(usize, u8, usize) -> bool) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if in_string {
+            match b {
+                b'\\' => i += 1,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'(' | b'[' | b'{' | b'<' => {
+                if f(i, b, depth) {
+                    return Some(i);
+                }
+                depth += 1;
+            }
+            // `->` inside function types is not a closing bracket.
+            b'>' if i > 0 && bytes[i - 1] == b'-' => {
+                if f(i, b, depth) {
+                    return Some(i);
+                }
+            }
+            b')' | b']' | b'}' | b'>' => {
+                depth = depth.saturating_sub(1);
+                if f(i, b, depth) {
+                    return Some(i);
+                }
+            }
+            _ => {
+                if f(i, b, depth) {
+                    return Some(i);
+                }
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Returns the index of the bracket closing the one at `open`.
+fn matching_close(s: &str, open: usize) -> Option<usize> {
+    let mut base = None;
+    scan(s, |i, b, depth| {
+        if i == open {
+            base = Some(depth);
+            return false;
+        }
+        matches!(b, b')' | b']' | b'}' | b'>') && base == Some(depth) && i > open
+    })
+}
+
+fn find_top_level_whitespace(s: &str) -> Option<usize> {
+    scan(s, |_, b, depth| depth == 0 && b.is_ascii_whitespace())
+}
+
+/// Splits `s` at occurrences of `sep` that are not nested in brackets or strings.
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let sep = sep as u8;
+    let mut parts = vec![];
+    let mut last = 0;
+    scan(s, |i, b, depth| {
+        if depth == 0 && b == sep {
+            parts.push(&s[last..i]);
+            last = i + 1;
+        }
+        false
+    });
+    let tail = &s[last..];
+    if !tail.trim().is_empty() || !parts.is_empty() {
+        parts.push(tail);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JAX_EXPORT: &str = r#"
+module @jit_f attributes {mhlo.num_partitions = 1 : i32, mhlo.num_replicas = 1 : i32} {
+  func.func public @main(%arg0: tensor<2x3xf32> {jax.arg_info = "x", mhlo.sharding = "{replicated}"}, %arg1: tensor<f32> {jax.arg_info = "scale"}) -> (tensor<2x3xf32> {jax.result_info = "out"}, tensor<i32>) {
+    %0 = stablehlo.multiply %arg0, %arg0 : tensor<2x3xf32>
+    %c = stablehlo.constant dense<1> : tensor<i32>
+    return %0, %c : tensor<2x3xf32>, tensor<i32>
+  }
+}
+"#;
+
+    #[test]
+    fn test_parse_metadata_jax_export() {
+        let metadata = parse_metadata(JAX_EXPORT).unwrap();
+        assert_eq!(metadata.module_name.as_deref(), Some("jit_f"));
+        assert_eq!(
+            metadata.module_attribute("mhlo.num_partitions"),
+            Some("1 : i32")
+        );
+        assert_eq!(metadata.entry_function, "main");
+
+        let args = &metadata.arguments;
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0].name.as_deref(), Some("arg0"));
+        assert_eq!(args[0].ty, "tensor<2x3xf32>");
+        assert_eq!(
+            args[0].string_attribute("jax.arg_info").as_deref(),
+            Some("x")
+        );
+        assert_eq!(
+            args[0].sharding(),
+            Some(ShardingAnnotation::Mhlo("{replicated}".to_string()))
+        );
+        assert_eq!(args[1].tensor_type().unwrap().dims, Vec::<i64>::new());
+        assert_eq!(args[1].sharding(), None);
+
+        let results = &metadata.results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].string_attribute("jax.result_info").as_deref(),
+            Some("out")
+        );
+        assert_eq!(
+            results[1].tensor_type().unwrap().primitive_type,
+            PrimitiveType::S32
+        );
+        assert!(results[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_metadata_single_result() {
+        let text = "func.func @main(%arg0: tensor<4xf32>) -> tensor<4xf32> { return %arg0 }";
+        let metadata = parse_metadata(text).unwrap();
+        assert_eq!(metadata.module_name, None);
+        assert_eq!(metadata.arguments.len(), 1);
+        assert_eq!(metadata.results.len(), 1);
+        assert_eq!(metadata.results[0].ty, "tensor<4xf32>");
+    }
+
+    #[test]
+    fn test_parse_metadata_no_args() {
+        let metadata = parse_metadata("module {\n  func.func @main() { return }\n}").unwrap();
+        assert!(metadata.arguments.is_empty());
+        assert!(metadata.results.is_empty());
+    }
+
+    #[test]
+    fn test_parse_metadata_skips_calls() {
+        let text = "func.func private @helper() { call @main() : () -> () }\n\
+                    func.func @main(%arg0: tensor<f32>) -> tensor<f32> { return %arg0 }";
+        let metadata = parse_metadata(text).unwrap();
+        assert_eq!(metadata.arguments.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_metadata_single_public_function() {
+        let text = "func.func public @forward(%arg0: tensor<f32>) -> tensor<f32> { return %arg0 }";
+        let metadata = parse_metadata(text).unwrap();
+        assert_eq!(metadata.entry_function, "forward");
+    }
+
+    #[test]
+    fn test_parse_metadata_sdy_sharding() {
+        let text = r#"func.func @main(%arg0: tensor<8x4xf32> {sdy.sharding = #sdy.sharding<@mesh, [{"x"}, {}]>}) -> tensor<8x4xf32> { return %arg0 }"#;
+        let metadata = parse_metadata(text).unwrap();
+        assert_eq!(
+            metadata.arguments[0].sharding(),
+            Some(ShardingAnnotation::Sdy(
+                r#"#sdy.sharding<@mesh, [{"x"}, {}]>"#.to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_missing() {
+        let err = parse_metadata("module {}").unwrap_err();
+        assert!(matches!(err, Error::InvalidProgramFormat(_)));
+    }
+
+    #[test]
+    fn test_tensor_type_parse() {
+        let t = TensorType::parse("tensor<2x?xbf16>").unwrap();
+        assert_eq!(t.dims, vec![2, -1]);
+        assert_eq!(t.primitive_type, PrimitiveType::BF16);
+
+        let t = TensorType::parse("tensor<3xcomplex<f32>>").unwrap();
+        assert_eq!(t.dims, vec![3]);
+        assert_eq!(t.primitive_type, PrimitiveType::C64);
+
+        let t = TensorType::parse("tensor<2x?xcomplex<f64>>").unwrap();
+        assert_eq!(t.dims, vec![2, -1]);
+        assert_eq!(t.primitive_type, PrimitiveType::C128);
+
+        let t = TensorType::parse("tensor<complex<f32>>").unwrap();
+        assert!(t.dims.is_empty());
+        assert_eq!(t.primitive_type, PrimitiveType::C64);
+
+        let t = TensorType::parse("tensor<?xi1, #stablehlo.bounds<4>>").unwrap();
+        assert_eq!(t.dims, vec![-1]);
+        assert_eq!(t.primitive_type, PrimitiveType::Pred);
+
+        assert_eq!(TensorType::parse("tensor<*xf32>"), None);
+        assert_eq!(TensorType::parse("!stablehlo.token"), None);
+    }
+
+    #[test]
+    fn test_bytecode_producer() {
+        let mut code = BYTECODE_MAGIC.to_vec();
+        code.push((6 << 1) | 1); // version 6 as a single-byte varint
+        code.extend_from_slice(b"StableHLO_v1.7.3\0rest");
+        assert!(is_bytecode(&code));
+        assert_eq!(
+            bytecode_producer(&code).as_deref(),
+            Some("StableHLO_v1.7.3")
+        );
+        assert!(!is_bytecode(b"module {}"));
+        assert_eq!(bytecode_producer(b"module {}"), None);
+    }
+
+    #[test]
+    fn test_parse_string_literal_escapes() {
+        assert_eq!(parse_string_literal(r#""a\"b""#).as_deref(), Some("a\"b"));
+        assert_eq!(parse_string_literal(r#""\0A""#).as_deref(), Some("\n"));
+        assert_eq!(parse_string_literal("bare"), None);
+    }
+
+    #[test]
+    fn test_split_top_level_nested() {
+        let parts = split_top_level(r#"a = tensor<2x3xf32>, b = "x,y", c = {d, e}"#, ',');
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[1].trim(), r#"b = "x,y""#);
+    }
+}
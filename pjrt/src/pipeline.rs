@@ -0,0 +1,324 @@
+//! Pipelined Execution
+//!
+//! This module provides `Pipeline`, which overlaps the host-to-device upload
+//! of upcoming batches with the execution of earlier ones. Each submitted
+//! batch is uploaded and launched without waiting for completion; the
+//! runtime orders the execution after its input transfers through buffer
+//! definition events. Up to `depth` batches are kept in flight, and outputs
+//! are copied back to the host in submission order.
+//!
+//! Submitting to a full pipeline first waits for the oldest batch and returns
+//! its outputs, which bounds device memory use and applies backpressure to
+//! the producer. `Pipeline::run` drives the pipeline from a `Stream` of
+//! batches, so the producer may itself be asynchronous.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let mut pipeline = loaded_executable.pipeline()?.depth(2);
+//!
+//! for batch in batches {
+//!     if let Some(outputs) = pipeline.submit(batch).await? {
+//!         consume(outputs);
+//!     }
+//! }
+//! while let Some(outputs) = pipeline.next_output().await? {
+//!     consume(outputs);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::pin::pin;
+
+use futures_core::Stream;
+
+use crate::{Buffer, Device, Error, Event, ExecuteOptions, HostBuffer, LoadedExecutable, Result};
+
+/// The default number of batches kept in flight, i.e. double buffering.
+const DEFAULT_DEPTH: usize = 2;
+
+/// Runs a `LoadedExecutable` over a sequence of host batches, keeping several
+/// batches in flight.
+///
+/// Created by [`LoadedExecutable::pipeline`]. Each batch is a list of host
+/// arguments for a single device, and yields the executable's outputs copied
+/// back to the host.
+///
+/// # Thread Safety
+///
+/// `Pipeline` is `!Send + !Sync` because it borrows a [`LoadedExecutable`]
+/// and holds device buffers.
+pub struct Pipeline<'a> {
+    loaded_executable: &'a LoadedExecutable,
+    device: Device,
+    /// Whether `device` was chosen explicitly, which launches through
+    /// `call_execute_on_device`.
+    on_device: bool,
+    options: ExecuteOptions<'a>,
+    depth: usize,
+    in_flight: VecDeque<Batch>,
+}
+
+/// A batch that has been uploaded and launched.
+struct Batch {
+    /// Host arguments, kept alive until the runtime is done reading them.
+    _inputs: Vec<HostBuffer>,
+    /// Pending `done_with_host_buffer` events of the uploads.
+    uploads: Vec<Event>,
+    events: Vec<Event>,
+    outputs: Vec<Buffer>,
+}
+
+impl Batch {
+    async fn release_inputs(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        // Events are only removed once ready, so that dropping this future
+        // leaves the remaining ones for `Drop`.
+        while let Some(event) = self.uploads.last_mut() {
+            let event_result = event.await;
+            self.uploads.pop();
+            if result.is_ok() {
+                result = event_result;
+            }
+        }
+        result
+    }
+
+    fn release_inputs_sync(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        while let Some(event) = self.uploads.pop() {
+            let event_result = event.wait();
+            if result.is_ok() {
+                result = event_result;
+            }
+        }
+        result
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        // The runtime may still be reading the host arguments.
+        let _ = self.release_inputs_sync();
+    }
+}
+
+impl<'a> Pipeline<'a> {
+    /// Creates a pipeline on the executable's first addressable device.
+    pub fn new(loaded_executable: &'a LoadedExecutable) -> Result<Self> {
+        let device = loaded_executable
+            .addressable_devices()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                Error::InvalidArgument("executable has no addressable devices".to_string())
+            })?;
+        Ok(Self {
+            loaded_executable,
+            device,
+            on_device: false,
+            options: ExecuteOptions::new(),
+            depth: DEFAULT_DEPTH,
+            in_flight: VecDeque::new(),
+        })
+    }
+
+    /// Sets the maximum number of batches in flight.
+    ///
+    /// The default of 2 overlaps the upload of one batch with the execution
+    /// of the previous one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is zero.
+    pub fn depth(mut self, depth: usize) -> Self {
+        assert!(depth > 0, "pipeline depth must be at least 1");
+        self.depth = depth;
+        self
+    }
+
+    /// Uploads to and launches on `device` instead of the first addressable
+    /// device.
+    ///
+    /// See [`LoadedExecutable::call_execute_on_device`].
+    pub fn device(mut self, device: Device) -> Self {
+        self.device = device;
+        self.on_device = true;
+        self
+    }
+
+    /// Sets the options used for every launch.
+    pub fn options(mut self, options: ExecuteOptions<'a>) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the maximum number of batches in flight.
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the device batches are uploaded to.
+    pub fn get_device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Returns the number of batches submitted but not yet returned.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Uploads and launches `inputs`.
+    ///
+    /// If `depth` batches are already in flight, first waits for the oldest
+    /// one and returns its outputs. An error leaves `inputs` unsubmitted; the
+    /// remaining batches can still be drained with
+    /// [`Pipeline::next_output`].
+    pub async fn submit(&mut self, inputs: Vec<HostBuffer>) -> Result<Option<Vec<HostBuffer>>> {
+        let outputs = if self.in_flight.len() >= self.depth {
+            self.next_output().await?
+        } else {
+            None
+        };
+        self.launch(inputs)?;
+        Ok(outputs)
+    }
+
+    /// Blocking version of [`Pipeline::submit`].
+    pub fn submit_sync(&mut self, inputs: Vec<HostBuffer>) -> Result<Option<Vec<HostBuffer>>> {
+        let outputs = if self.in_flight.len() >= self.depth {
+            self.next_output_sync()?
+        } else {
+            None
+        };
+        self.launch(inputs)?;
+        Ok(outputs)
+    }
+
+    /// Waits for the oldest batch in flight and returns its outputs.
+    ///
+    /// Returns `None` once the pipeline is empty.
+    pub async fn next_output(&mut self) -> Result<Option<Vec<HostBuffer>>> {
+        let Some(mut batch) = self.in_flight.pop_front() else {
+            return Ok(None);
+        };
+        batch.release_inputs().await?;
+        let device = self.on_device.then_some(&self.device);
+        self.loaded_executable
+            .wait_for_completion(std::mem::take(&mut batch.events), &self.options, device)
+            .await?;
+        let mut outputs = Vec::with_capacity(batch.outputs.len());
+        for buffer in &batch.outputs {
+            outputs.push(buffer.to_host(None).await?);
+        }
+        Ok(Some(outputs))
+    }
+
+    /// Blocking version of [`Pipeline::next_output`].
+    pub fn next_output_sync(&mut self) -> Result<Option<Vec<HostBuffer>>> {
+        let Some(mut batch) = self.in_flight.pop_front() else {
+            return Ok(None);
+        };
+        batch.release_inputs_sync()?;
+        let device = self.on_device.then_some(&self.device);
+        self.loaded_executable.wait_for_completion_sync(
+            std::mem::take(&mut batch.events),
+            &self.options,
+            device,
+        )?;
+        let mut outputs = Vec::with_capacity(batch.outputs.len());
+        for buffer in &batch.outputs {
+            outputs.push(buffer.to_host_sync(None)?);
+        }
+        Ok(Some(outputs))
+    }
+
+    /// Runs every batch of `inputs` through the pipeline, passing outputs to
+    /// `on_output` in order.
+    ///
+    /// Batches are submitted as the stream yields them, so inputs produced
+    /// asynchronously, e.g. read from disk or received over a channel, are
+    /// uploaded while earlier batches execute. Batches already in memory can
+    /// be passed with `futures::stream::iter`.
+    ///
+    /// Stops at the first error, from the pipeline or from `on_output`.
+    pub async fn run<S, F>(&mut self, inputs: S, mut on_output: F) -> Result<()>
+    where
+        S: Stream<Item = Vec<HostBuffer>>,
+        F: FnMut(Vec<HostBuffer>) -> Result<()>,
+    {
+        let mut inputs = pin!(inputs);
+        while let Some(batch) = poll_fn(|cx| inputs.as_mut().poll_next(cx)).await {
+            if let Some(outputs) = self.submit(batch).await? {
+                on_output(outputs)?;
+            }
+        }
+        while let Some(outputs) = self.next_output().await? {
+            on_output(outputs)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking version of [`Pipeline::run`], over an iterator of batches.
+    pub fn run_sync<I, F>(&mut self, inputs: I, mut on_output: F) -> Result<()>
+    where
+        I: IntoIterator<Item = Vec<HostBuffer>>,
+        F: FnMut(Vec<HostBuffer>) -> Result<()>,
+    {
+        for batch in inputs {
+            if let Some(outputs) = self.submit_sync(batch)? {
+                on_output(outputs)?;
+            }
+        }
+        while let Some(outputs) = self.next_output_sync()? {
+            on_output(outputs)?;
+        }
+        Ok(())
+    }
+
+    /// Starts the uploads of `inputs` and launches the executable on them
+    /// without waiting for either.
+    fn launch(&mut self, inputs: Vec<HostBuffer>) -> Result<()> {
+        let client = self.device.client();
+        let mut uploads = Vec::with_capacity(inputs.len());
+        let mut buffers = Vec::with_capacity(inputs.len());
+        for input in &inputs {
+            let args = input.call_copy_to(&self.device, None, None, None)?;
+            uploads.push(Event::wrap(client.api(), args.done_with_host_buffer));
+            buffers.push(Buffer::wrap(client, args.buffer));
+        }
+        let mut batch = Batch {
+            _inputs: inputs,
+            uploads,
+            events: vec![],
+            outputs: vec![],
+        };
+        if self.on_device {
+            let (event, outputs) = self.loaded_executable.call_execute_on_device(
+                buffers,
+                &self.options,
+                &self.device,
+            )?;
+            batch.events = vec![event];
+            batch.outputs = outputs;
+        } else {
+            let (events, outputs) = self
+                .loaded_executable
+                .call_execute(buffers, &self.options)?;
+            batch.events = events;
+            batch.outputs = outputs.into_iter().next().unwrap_or_default();
+        }
+        self.in_flight.push_back(batch);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Pipeline<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("depth", &self.depth)
+            .field("in_flight", &self.in_flight.len())
+            .finish()
+    }
+}
@@ -8,6 +8,9 @@
 //! - Load programs from files or strings
 //! - Convert between program formats
 //! - Serialize/deserialize programs
+//! - Detect the format of raw program bytes (MLIR text, MLIR bytecode or a
+//!   serialized `HloModuleProto`)
+//! - Inspect textual MLIR modules through `Program::metadata`
 //!
 //! # Examples
 //!
@@ -36,10 +39,10 @@
 //! use pjrt::{Program, ProgramFormat};
 //!
 //! // Load MLIR from file
-//! let program = Program::from_file("model.mlir", ProgramFormat::MLIR)?;
+//! let program = Program::from_mlir("model.mlir")?;
 //!
-//! // Or use auto-detection based on extension
-//! let program = Program::from_file_auto("model.mlir")?;
+//! // Or detect the format from the file contents
+//! let program = Program::from_file_auto("model.mlirbc")?;
 //! ```
 //!
 //! ## Program Formats
@@ -63,9 +66,11 @@
 use std::fs;
 use std::path::Path;
 
+use pjrt_sys::protos::xla::HloModuleProto;
 use pjrt_sys::PJRT_Program;
+use prost::Message;
 
-use crate::{Error, Result};
+use crate::{mlir, Error, ProgramMetadata, Result, StablehloVersion};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ProgramFormat {
@@ -87,6 +92,28 @@ impl ProgramFormat {
             ProgramFormat::HLO => b"hlo",
         }
     }
+
+    /// Detects the format of raw program bytes.
+    ///
+    /// MLIR bytecode is recognized by its magic number and textual MLIR by a
+    /// `module` or `func.func` definition. Anything else must decode as an
+    /// `HloModuleProto` with at least one computation.
+    pub fn detect(code: &[u8]) -> Result<Self> {
+        if mlir::is_bytecode(code) {
+            return Ok(ProgramFormat::MLIR);
+        }
+        if let Ok(text) = std::str::from_utf8(code) {
+            if text.contains("func.func") || text.trim_start().starts_with("module") {
+                return Ok(ProgramFormat::MLIR);
+            }
+        }
+        match HloModuleProto::decode(code) {
+            Ok(module) if !module.computations.is_empty() => Ok(ProgramFormat::HLO),
+            _ => Err(Error::InvalidProgramFormat(
+                "unable to detect program format".to_string(),
+            )),
+        }
+    }
 }
 
 impl TryFrom<&str> for ProgramFormat {
@@ -157,6 +184,65 @@ impl Program {
         let code = fs::read(path)?;
         Ok(Program::new(ProgramFormat::HLO, code))
     }
+
+    /// Creates a program, detecting the format with `ProgramFormat::detect`.
+    pub fn from_bytes(code: impl Into<Vec<u8>>) -> Result<Self> {
+        let code = code.into();
+        let format = ProgramFormat::detect(&code)?;
+        Ok(Program::new(format, code))
+    }
+
+    /// Loads a program from a file, detecting the format from its contents.
+    pub fn from_file_auto<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Returns `true` if this is an MLIR program in bytecode form.
+    pub fn is_mlir_bytecode(&self) -> bool {
+        self.format == ProgramFormat::MLIR && mlir::is_bytecode(&self.code)
+    }
+
+    /// Parses the module header and entry function signature.
+    ///
+    /// Only textual MLIR is supported; bytecode and HLO programs return
+    /// `Error::InvalidProgramFormat`.
+    pub fn metadata(&self) -> Result<ProgramMetadata> {
+        if self.format != ProgramFormat::MLIR || self.is_mlir_bytecode() {
+            return Err(Error::InvalidProgramFormat(
+                "metadata requires a textual MLIR program".to_string(),
+            ));
+        }
+        let text = std::str::from_utf8(&self.code).map_err(|_| {
+            Error::InvalidProgramFormat("mlir: program is not valid UTF-8".to_string())
+        })?;
+        mlir::parse_metadata(text)
+    }
+
+    /// Returns the StableHLO version the program was serialized with.
+    ///
+    /// Portable bytecode artifacts name their producer `StableHLO_v<version>`.
+    /// Returns `None` for textual MLIR, HLO programs and bytecode from other
+    /// producers.
+    pub fn stablehlo_version(&self) -> Option<StablehloVersion> {
+        if !self.is_mlir_bytecode() {
+            return None;
+        }
+        let producer = mlir::bytecode_producer(&self.code)?;
+        producer.strip_prefix("StableHLO_v")?.parse().ok()
+    }
+
+    /// Returns the version in the `stablehlo.version` module attribute of a
+    /// textual MLIR program.
+    ///
+    /// The attribute is an annotation added by some producers, not a
+    /// serialization version, so it is not checked when compiling.
+    pub fn stablehlo_version_attribute(&self) -> Option<StablehloVersion> {
+        self.metadata()
+            .ok()?
+            .module_string_attribute("stablehlo.version")?
+            .parse()
+            .ok()
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +321,68 @@ mod tests {
         assert_eq!(program.prog.code_size, code.len());
     }
 
+    #[test]
+    fn test_program_format_detect() {
+        let text = b"module @m {\n  func.func @main() { return }\n}";
+        assert_eq!(ProgramFormat::detect(text).unwrap(), ProgramFormat::MLIR);
+
+        let bytecode = b"ML\xEFR\x0dStableHLO_v1.0.0\0";
+        assert_eq!(
+            ProgramFormat::detect(bytecode).unwrap(),
+            ProgramFormat::MLIR
+        );
+
+        let module = HloModuleProto {
+            name: "m".to_string(),
+            computations: vec![Default::default()],
+            ..Default::default()
+        };
+        let hlo = module.encode_to_vec();
+        assert_eq!(ProgramFormat::detect(&hlo).unwrap(), ProgramFormat::HLO);
+
+        assert!(ProgramFormat::detect(b"\xff\xff\xff").is_err());
+    }
+
+    #[test]
+    fn test_program_from_bytes_bytecode() {
+        let program = Program::from_bytes(b"ML\xEFR\x0dStableHLO_v1.7.3\0".to_vec()).unwrap();
+        assert_eq!(program.format(), ProgramFormat::MLIR);
+        assert!(program.is_mlir_bytecode());
+        assert_eq!(
+            program.stablehlo_version(),
+            Some(StablehloVersion::new(1, 7, 3))
+        );
+        assert!(program.metadata().is_err());
+    }
+
+    #[test]
+    fn test_program_metadata_text() {
+        let code = "func.func @main(%arg0: tensor<2x3xf32>) -> tensor<2x3xf32> { return %arg0 }";
+        let program = Program::new(ProgramFormat::MLIR, code);
+        assert!(!program.is_mlir_bytecode());
+        assert_eq!(program.stablehlo_version(), None);
+        let metadata = program.metadata().unwrap();
+        assert_eq!(
+            metadata.arguments[0].tensor_type().unwrap().dims,
+            vec![2, 3]
+        );
+    }
+
+    #[test]
+    fn test_program_stablehlo_version_attribute() {
+        let code = r#"module @jit_f attributes {stablehlo.version = "1.9.5"} {
+  func.func public @main(%arg0: tensor<f32>) -> tensor<f32> {
+    return %arg0 : tensor<f32>
+  }
+}"#;
+        let program = Program::new(ProgramFormat::MLIR, code);
+        assert_eq!(program.stablehlo_version(), None);
+        assert_eq!(
+            program.stablehlo_version_attribute(),
+            Some(StablehloVersion::new(1, 9, 5))
+        );
+    }
+
     #[test]
     fn test_program_format_clone() {
         let format = ProgramFormat::MLIR;
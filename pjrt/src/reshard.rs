@@ -0,0 +1,526 @@
+//! Resharding
+//!
+//! This module moves a [`ShardedArray`] from one [`Sharding`] to another, e.g.
+//! from data-parallel to model-parallel, without gathering the whole array on
+//! the host.
+//!
+//! [`ShardedArray::reshard_plan`] compares the slices held by the source shards
+//! with the slices the target sharding assigns to each addressable device and
+//! produces a [`ReshardPlan`] with one [`ShardTransfer`] per target shard:
+//!
+//! - When a source shard holds exactly the target slice, it is copied
+//!   device-to-device, preferring a source on the target device itself.
+//! - Otherwise the target slice is assembled on the host from the source
+//!   shards overlapping it and uploaded. Each source shard is downloaded at
+//!   most once, however many target shards it contributes to.
+//!
+//! [`ShardedArray::reshard`] executes the plan. Device-to-device copies that
+//! the plugin does not support fall back to a copy through the host.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let data_parallel = ShardedArray::from_host_sync(&client, &host, rows)?;
+//! let plan = data_parallel.reshard_plan(columns.as_ref())?;
+//! println!("{} host transfers", plan.num_host_transfers());
+//!
+//! let model_parallel = data_parallel.reshard_sync(columns).copy()?;
+//! // Or place the new shards in a specific memory of each device.
+//! let offloaded = model_parallel.reshard_sync(replicated).memory_kind("pinned_host").copy()?;
+//! ```
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use bon::bon;
+
+use crate::sharded_array::{extract_slice, insert_slice, shard_dims, volume};
+use crate::{
+    utils, Buffer, Client, Device, Error, ErrorCode, GlobalDeviceId, HostBuffer, Memory,
+    MemoryLayout, Result, ShardedArray, Sharding,
+};
+
+/// How one target shard of a reshard is produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShardTransfer {
+    /// Copies the source shard of device `from`, which holds the same slice,
+    /// to device `to`.
+    Device {
+        from: GlobalDeviceId,
+        to: GlobalDeviceId,
+    },
+    /// Assembles the target shard of device `to` on the host from the source
+    /// shards of the devices in `from`, then uploads it.
+    Host {
+        from: Vec<GlobalDeviceId>,
+        to: GlobalDeviceId,
+    },
+}
+
+impl ShardTransfer {
+    /// Returns the device receiving the target shard.
+    pub fn target(&self) -> GlobalDeviceId {
+        match self {
+            Self::Device { to, .. } | Self::Host { to, .. } => *to,
+        }
+    }
+}
+
+/// The transfers moving an array between two shardings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReshardPlan {
+    source: Vec<(GlobalDeviceId, Vec<Range<i64>>)>,
+    target: Vec<(GlobalDeviceId, Vec<Range<i64>>)>,
+    transfers: Vec<ShardTransfer>,
+}
+
+impl ReshardPlan {
+    /// Plans the transfers from the `source` shards to the `target` shards,
+    /// both given as `(device, slice)` pairs.
+    ///
+    /// Fails if the source shards do not cover some target slice.
+    pub fn new(
+        source: Vec<(GlobalDeviceId, Vec<Range<i64>>)>,
+        target: Vec<(GlobalDeviceId, Vec<Range<i64>>)>,
+    ) -> Result<Self> {
+        let transfers = target
+            .iter()
+            .map(|(to, index)| plan_transfer(&source, *to, index))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            source,
+            target,
+            transfers,
+        })
+    }
+
+    /// Returns the transfers, one per target shard.
+    pub fn transfers(&self) -> &[ShardTransfer] {
+        &self.transfers
+    }
+
+    /// Returns the number of target shards copied device-to-device.
+    pub fn num_device_copies(&self) -> usize {
+        self.transfers
+            .iter()
+            .filter(|t| matches!(t, ShardTransfer::Device { .. }))
+            .count()
+    }
+
+    /// Returns the number of target shards assembled on the host.
+    pub fn num_host_transfers(&self) -> usize {
+        self.transfers.len() - self.num_device_copies()
+    }
+
+    /// Returns the source shards downloaded to the host, each once.
+    pub fn host_downloads(&self) -> Vec<GlobalDeviceId> {
+        let mut downloads = vec![];
+        for transfer in &self.transfers {
+            if let ShardTransfer::Host { from, .. } = transfer {
+                for id in from {
+                    if !downloads.contains(id) {
+                        downloads.push(*id);
+                    }
+                }
+            }
+        }
+        downloads
+    }
+
+    fn source_index(&self, device_id: GlobalDeviceId) -> &[Range<i64>] {
+        self.source
+            .iter()
+            .find(|(id, _)| *id == device_id)
+            .map(|(_, index)| index.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+fn plan_transfer(
+    source: &[(GlobalDeviceId, Vec<Range<i64>>)],
+    to: GlobalDeviceId,
+    index: &[Range<i64>],
+) -> Result<ShardTransfer> {
+    let exact = source
+        .iter()
+        .find(|(id, i)| *id == to && i == index)
+        .or_else(|| source.iter().find(|(_, i)| i == index));
+    if let Some((from, _)) = exact {
+        return Ok(ShardTransfer::Device { from: *from, to });
+    }
+
+    // Distinct source slices are disjoint tiles, so one shard per slice is
+    // enough.
+    let mut from = vec![];
+    let mut seen: Vec<&[Range<i64>]> = vec![];
+    let mut covered = 0;
+    for (id, i) in source {
+        if seen.contains(&i.as_slice()) {
+            continue;
+        }
+        if let Some(overlap) = intersect(i, index) {
+            seen.push(i);
+            from.push(*id);
+            covered += volume(&overlap);
+        }
+    }
+    if covered != volume(index) {
+        return Err(Error::InvalidArgument(format!(
+            "the addressable source shards do not cover the target shard of device {to}"
+        )));
+    }
+    Ok(ShardTransfer::Host { from, to })
+}
+
+/// Returns the non-empty intersection of two slices.
+fn intersect(a: &[Range<i64>], b: &[Range<i64>]) -> Option<Vec<Range<i64>>> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let r = a.start.max(b.start)..a.end.min(b.end);
+            (r.start < r.end).then_some(r)
+        })
+        .collect()
+}
+
+/// Assembles the row-major slice `index` from row-major `sources` given as
+/// `(slice, bytes)` pairs.
+fn assemble(index: &[Range<i64>], sources: &[(&[Range<i64>], &[u8])], elem_size: usize) -> Vec<u8> {
+    let dims = shard_dims(index);
+    let mut bytes = vec![0; volume(index) as usize * elem_size];
+    for (source_index, source) in sources {
+        let Some(overlap) = intersect(source_index, index) else {
+            continue;
+        };
+        let in_source = translate(&overlap, source_index);
+        let region = extract_slice(source, &shard_dims(source_index), &in_source, elem_size);
+        insert_slice(
+            &mut bytes,
+            &dims,
+            &translate(&overlap, index),
+            &region,
+            elem_size,
+        );
+    }
+    bytes
+}
+
+/// Expresses `region` relative to the start of `origin`.
+fn translate(region: &[Range<i64>], origin: &[Range<i64>]) -> Vec<Range<i64>> {
+    region
+        .iter()
+        .zip(origin)
+        .map(|(r, o)| r.start - o.start..r.end - o.start)
+        .collect()
+}
+
+/// Returns whether `err` means the plugin cannot copy between devices.
+fn is_unsupported(err: &Error) -> bool {
+    matches!(err, Error::NullFunctionPointer(_)) || err.code() == ErrorCode::Unimplemented
+}
+
+/// Where a target shard is placed.
+enum Destination {
+    Device(Device),
+    Memory(Memory),
+}
+
+impl Destination {
+    fn new(client: &Client, device_id: GlobalDeviceId, memory_kind: Option<&str>) -> Result<Self> {
+        let device = client.lookup_device(device_id)?;
+        let Some(kind) = memory_kind else {
+            return Ok(Self::Device(device));
+        };
+        for memory in device.addressable_memories()? {
+            if memory.kind()? == kind {
+                return Ok(Self::Memory(memory));
+            }
+        }
+        Err(Error::InvalidArgument(format!(
+            "device {device_id} has no memory of kind {kind}"
+        )))
+    }
+
+    async fn copy(&self, buffer: &Buffer) -> Result<Buffer> {
+        match self {
+            Self::Device(device) => buffer.to_device(device).copy().await,
+            Self::Memory(memory) => buffer.to_memory(memory).copy().await,
+        }
+    }
+
+    fn copy_sync(&self, buffer: &Buffer) -> Result<Buffer> {
+        match self {
+            Self::Device(device) => buffer.to_device_sync(device).copy(),
+            Self::Memory(memory) => buffer.to_memory_sync().memory(memory).copy(),
+        }
+    }
+
+    async fn upload(&self, host: &HostBuffer) -> Result<Buffer> {
+        match self {
+            Self::Device(device) => host.to(device).copy().await,
+            Self::Memory(memory) => host.to(memory).copy().await,
+        }
+    }
+
+    fn upload_sync(&self, host: &HostBuffer) -> Result<Buffer> {
+        match self {
+            Self::Device(device) => host.to_sync(device).copy(),
+            Self::Memory(memory) => host.to_sync(memory).copy(),
+        }
+    }
+}
+
+#[bon]
+impl ShardedArray {
+    /// Plans how to move this array to `sharding`.
+    pub fn reshard_plan(&self, sharding: &dyn Sharding) -> Result<ReshardPlan> {
+        let client = self.client();
+        let source = self
+            .shards()
+            .iter()
+            .map(|s| (s.device_id(), s.index().to_vec()))
+            .collect();
+        let mut target = vec![];
+        for (device_id, index) in sharding.shard_indices(self.global_shape())? {
+            if client.lookup_device(device_id)?.is_addressable()? {
+                target.push((device_id, index));
+            }
+        }
+        ReshardPlan::new(source, target)
+    }
+
+    /// Moves this array to `sharding`, copying shards device-to-device where
+    /// possible.
+    ///
+    /// With `memory_kind`, the new shards are placed in the memory of that kind
+    /// of each device instead of its default memory.
+    #[builder(finish_fn = copy, derive(IntoFuture(Box, ?Send)))]
+    pub async fn reshard(
+        &self,
+        #[builder(start_fn)] sharding: Arc<dyn Sharding>,
+        #[builder(into)] memory_kind: Option<String>,
+    ) -> Result<ShardedArray> {
+        let plan = self.reshard_plan(sharding.as_ref())?;
+        let mut downloads = HashMap::new();
+        let mut buffers = Vec::with_capacity(plan.transfers.len());
+        for (transfer, (to, index)) in plan.transfers.iter().zip(&plan.target) {
+            let dest = Destination::new(self.client(), *to, memory_kind.as_deref())?;
+            let from = match transfer {
+                ShardTransfer::Device { from, .. } => {
+                    let source = self.shard_buffer(*from)?;
+                    match dest.copy(source).await {
+                        Ok(buffer) => {
+                            buffers.push(buffer);
+                            continue;
+                        }
+                        Err(err) if is_unsupported(&err) => std::slice::from_ref(from),
+                        Err(err) => return Err(err),
+                    }
+                }
+                ShardTransfer::Host { from, .. } => from.as_slice(),
+            };
+            for id in from {
+                if !downloads.contains_key(id) {
+                    let host = self
+                        .shard_buffer(*id)?
+                        .to_host(Some(self.row_major(plan.source_index(*id))?))
+                        .await?;
+                    downloads.insert(*id, host);
+                }
+            }
+            let host = self.assemble_shard(&plan, from, &downloads, index)?;
+            buffers.push(dest.upload(&host).await?);
+        }
+        ShardedArray::new(self.global_shape().to_vec(), sharding, buffers)
+    }
+
+    /// Synchronous version of [`ShardedArray::reshard`].
+    #[builder(finish_fn = copy)]
+    pub fn reshard_sync(
+        &self,
+        #[builder(start_fn)] sharding: Arc<dyn Sharding>,
+        #[builder(into)] memory_kind: Option<String>,
+    ) -> Result<ShardedArray> {
+        let plan = self.reshard_plan(sharding.as_ref())?;
+        let mut downloads = HashMap::new();
+        let mut buffers = Vec::with_capacity(plan.transfers.len());
+        for (transfer, (to, index)) in plan.transfers.iter().zip(&plan.target) {
+            let dest = Destination::new(self.client(), *to, memory_kind.as_deref())?;
+            let from = match transfer {
+                ShardTransfer::Device { from, .. } => {
+                    match dest.copy_sync(self.shard_buffer(*from)?) {
+                        Ok(buffer) => {
+                            buffers.push(buffer);
+                            continue;
+                        }
+                        Err(err) if is_unsupported(&err) => std::slice::from_ref(from),
+                        Err(err) => return Err(err),
+                    }
+                }
+                ShardTransfer::Host { from, .. } => from.as_slice(),
+            };
+            for id in from {
+                if !downloads.contains_key(id) {
+                    let host = self
+                        .shard_buffer(*id)?
+                        .to_host_sync(Some(self.row_major(plan.source_index(*id))?))?;
+                    downloads.insert(*id, host);
+                }
+            }
+            let host = self.assemble_shard(&plan, from, &downloads, index)?;
+            buffers.push(dest.upload_sync(&host)?);
+        }
+        ShardedArray::new(self.global_shape().to_vec(), sharding, buffers)
+    }
+
+    fn shard_buffer(&self, device_id: GlobalDeviceId) -> Result<&Buffer> {
+        self.shard(device_id).map(|s| s.buffer()).ok_or_else(|| {
+            Error::InvalidArgument(format!("device {device_id} holds no source shard"))
+        })
+    }
+
+    fn row_major(&self, index: &[Range<i64>]) -> Result<MemoryLayout> {
+        let elem_size = self.primitive_type().try_into_dtype()?.size();
+        Ok(MemoryLayout::from_strides(utils::byte_strides(
+            &shard_dims(index),
+            elem_size,
+        )))
+    }
+
+    fn assemble_shard(
+        &self,
+        plan: &ReshardPlan,
+        from: &[GlobalDeviceId],
+        downloads: &HashMap<GlobalDeviceId, HostBuffer>,
+        index: &[Range<i64>],
+    ) -> Result<HostBuffer> {
+        let ty = self.primitive_type();
+        let sources: Vec<_> = from
+            .iter()
+            .map(|id| (plan.source_index(*id), downloads[id].as_bytes()))
+            .collect();
+        let bytes = assemble(index, &sources, ty.try_into_dtype()?.size());
+        HostBuffer::from_bytes(bytes, ty, Some(shard_dims(index)), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<(GlobalDeviceId, Vec<Range<i64>>)> {
+        vec![(0, vec![0..2, 0..4]), (1, vec![2..4, 0..4])]
+    }
+
+    fn columns() -> Vec<(GlobalDeviceId, Vec<Range<i64>>)> {
+        vec![(0, vec![0..4, 0..2]), (1, vec![0..4, 2..4])]
+    }
+
+    #[test]
+    fn test_plan_same_sharding() {
+        let plan = ReshardPlan::new(rows(), rows()).unwrap();
+        assert_eq!(
+            plan.transfers(),
+            [
+                ShardTransfer::Device { from: 0, to: 0 },
+                ShardTransfer::Device { from: 1, to: 1 },
+            ]
+        );
+        assert_eq!(plan.num_host_transfers(), 0);
+        assert!(plan.host_downloads().is_empty());
+    }
+
+    #[test]
+    fn test_plan_moves_between_devices() {
+        let swapped = vec![(0, vec![2..4, 0..4]), (1, vec![0..2, 0..4])];
+        let plan = ReshardPlan::new(rows(), swapped).unwrap();
+        assert_eq!(
+            plan.transfers(),
+            [
+                ShardTransfer::Device { from: 1, to: 0 },
+                ShardTransfer::Device { from: 0, to: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_prefers_local_replica() {
+        let replicated = vec![(0, vec![0..4, 0..4]), (1, vec![0..4, 0..4])];
+        let plan = ReshardPlan::new(replicated.clone(), replicated).unwrap();
+        assert_eq!(
+            plan.transfers()[1],
+            ShardTransfer::Device { from: 1, to: 1 }
+        );
+    }
+
+    #[test]
+    fn test_plan_rows_to_columns() {
+        let plan = ReshardPlan::new(rows(), columns()).unwrap();
+        assert_eq!(
+            plan.transfers(),
+            [
+                ShardTransfer::Host {
+                    from: vec![0, 1],
+                    to: 0
+                },
+                ShardTransfer::Host {
+                    from: vec![0, 1],
+                    to: 1
+                },
+            ]
+        );
+        assert_eq!(plan.num_device_copies(), 0);
+        assert_eq!(plan.host_downloads(), [0, 1]);
+    }
+
+    #[test]
+    fn test_plan_skips_replicated_sources() {
+        let source = vec![
+            (0, vec![0..2, 0..4]),
+            (1, vec![0..2, 0..4]),
+            (2, vec![2..4, 0..4]),
+            (3, vec![2..4, 0..4]),
+        ];
+        let target = vec![(0, vec![0..4, 0..4])];
+        let plan = ReshardPlan::new(source, target).unwrap();
+        assert_eq!(
+            plan.transfers(),
+            [ShardTransfer::Host {
+                from: vec![0, 2],
+                to: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_uncovered_target() {
+        let source = vec![(0, vec![0..2, 0..4])];
+        let err = ReshardPlan::new(source, columns()).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn test_intersect() {
+        assert_eq!(
+            intersect(&[0..4, 2..6], &[2..8, 0..3]),
+            Some(vec![2..4, 2..3])
+        );
+        assert_eq!(intersect(&[0..2, 0..4], &[2..4, 0..4]), None);
+        assert_eq!(intersect(&[], &[]), Some(vec![]));
+    }
+
+    #[test]
+    fn test_assemble() {
+        // The 4x4 array [[0, 1, 2, 3], [4, 5, 6, 7], ...] split by rows.
+        let array: Vec<u8> = (0..16).collect();
+        let top = &array[..8];
+        let bottom = &array[8..];
+        let sources: [(&[Range<i64>], &[u8]); 2] = [(&[0..2, 0..4], top), (&[2..4, 0..4], bottom)];
+        assert_eq!(
+            assemble(&[0..4, 2..4], &sources, 1),
+            [2, 3, 6, 7, 10, 11, 14, 15]
+        );
+        assert_eq!(assemble(&[1..3, 1..2], &sources, 1), [5, 9]);
+    }
+}
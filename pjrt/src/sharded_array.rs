@@ -0,0 +1,520 @@
+//! Sharded Arrays
+//!
+//! This module provides [`ShardedArray`], a global array split over devices
+//! according to a [`Sharding`]. It owns one [`Buffer`] per addressable device
+//! of the sharding together with the global shape, so callers no longer have
+//! to remember which device holds which shard:
+//!
+//! - [`ShardedArray::from_host`] slices a full [`HostBuffer`] and uploads each
+//!   shard to its device.
+//! - [`ShardedArray::to_host`] gathers the shards back into a single
+//!   [`HostBuffer`], downloading replicated shards only once.
+//! - [`ShardedArray::execution_inputs`] orders the shards of several arrays by
+//!   the addressable devices of a multi-partition [`LoadedExecutable`], ready
+//!   to be passed to [`LoadedExecutable::execution`].
+//! - [`ShardedArray::from_outputs`] wraps the per-device outputs of an
+//!   execution.
+//!
+//! Host buffers are sliced and assembled in dense row-major order.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let mesh = Mesh::from_client(&client, &[("data", 4)])?;
+//! let sharding: Arc<dyn Sharding> =
+//!     Arc::new(NamedSharding::new(mesh, PartitionSpec::new().sharded("data"))?);
+//!
+//! let x = ShardedArray::from_host_sync(&client, &host, sharding.clone())?;
+//! let outputs = exe
+//!     .execution(ShardedArray::execution_inputs(&exe, &[&x])?)
+//!     .run_sync()?;
+//! let y = ShardedArray::from_outputs(outputs, [(vec![8, 3], sharding)])?;
+//! let result = y[0].to_host_sync()?;
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::mesh::unravel_index;
+use crate::{
+    utils, Buffer, Client, Device, Error, GlobalDeviceId, HostBuffer, LoadedExecutable,
+    MemoryLayout, PrimitiveType, Result, Sharding,
+};
+
+/// A global array whose shards live on the devices of a [`Sharding`].
+///
+/// # Thread Safety
+///
+/// `ShardedArray` is `!Send + !Sync` because it owns [`Buffer`]s.
+pub struct ShardedArray {
+    global_shape: Vec<i64>,
+    primitive_type: PrimitiveType,
+    sharding: Arc<dyn Sharding>,
+    /// Shards of the addressable devices, in shard order.
+    shards: Vec<Shard>,
+}
+
+/// The part of a [`ShardedArray`] held by one device.
+pub struct Shard {
+    device_id: GlobalDeviceId,
+    index: Vec<Range<i64>>,
+    buffer: Buffer,
+}
+
+impl Shard {
+    /// Returns the global id of the device holding this shard.
+    pub fn device_id(&self) -> GlobalDeviceId {
+        self.device_id
+    }
+
+    /// Returns the slice of the global array this shard holds.
+    pub fn index(&self) -> &[Range<i64>] {
+        &self.index
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn into_buffer(self) -> Buffer {
+        self.buffer
+    }
+}
+
+impl fmt::Debug for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shard")
+            .field("device_id", &self.device_id)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl fmt::Debug for ShardedArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedArray")
+            .field("global_shape", &self.global_shape)
+            .field("primitive_type", &self.primitive_type)
+            .field("sharding", &self.sharding)
+            .field("shards", &self.shards)
+            .finish()
+    }
+}
+
+impl ShardedArray {
+    /// Creates a sharded array from the buffers of its addressable shards.
+    ///
+    /// The buffers may be given in any order. Each must live on a distinct
+    /// device of `sharding` and have the shape of that device's shard, and
+    /// every addressable device of `sharding` must hold one.
+    pub fn new(
+        global_shape: Vec<i64>,
+        sharding: Arc<dyn Sharding>,
+        buffers: Vec<Buffer>,
+    ) -> Result<Self> {
+        let Some(first) = buffers.first() else {
+            return Err(Error::InvalidArgument(
+                "a sharded array needs at least one shard".to_string(),
+            ));
+        };
+        let client = first.client().clone();
+        let primitive_type = first.primitive_type()?;
+        let indices = sharding.shard_indices(&global_shape)?;
+
+        let mut by_device = HashMap::with_capacity(buffers.len());
+        for buffer in buffers {
+            let device_id = buffer.device()?.description()?.id()?;
+            let Some((_, index)) = indices.iter().find(|(id, _)| *id == device_id) else {
+                return Err(Error::InvalidArgument(format!(
+                    "device {device_id} is not part of the sharding"
+                )));
+            };
+            let ty = buffer.primitive_type()?;
+            if ty != primitive_type {
+                return Err(Error::InvalidArgument(format!(
+                    "shard on device {device_id} has type {ty:?}, expected {primitive_type:?}"
+                )));
+            }
+            let dims = buffer.dims()?;
+            let expected = shard_dims(index);
+            if dims != expected {
+                return Err(Error::InvalidArgument(format!(
+                    "shard on device {device_id} has dimensions {dims:?}, expected {expected:?}"
+                )));
+            }
+            if by_device.insert(device_id, buffer).is_some() {
+                return Err(Error::InvalidArgument(format!(
+                    "device {device_id} holds more than one shard"
+                )));
+            }
+        }
+
+        let mut shards = Vec::with_capacity(by_device.len());
+        for (device_id, index) in indices {
+            match by_device.remove(&device_id) {
+                Some(buffer) => shards.push(Shard {
+                    device_id,
+                    index,
+                    buffer,
+                }),
+                None if client.lookup_device(device_id)?.is_addressable()? => {
+                    return Err(Error::InvalidArgument(format!(
+                        "missing shard for addressable device {device_id}"
+                    )));
+                }
+                None => {}
+            }
+        }
+        Ok(Self {
+            global_shape,
+            primitive_type,
+            sharding,
+            shards,
+        })
+    }
+
+    /// Slices `host` by `sharding` and uploads each shard to its addressable
+    /// device.
+    ///
+    /// `host` must have a dense row-major layout.
+    pub async fn from_host(
+        client: &Client,
+        host: &HostBuffer,
+        sharding: Arc<dyn Sharding>,
+    ) -> Result<Self> {
+        let mut buffers = vec![];
+        for (device, shard) in host_shards(client, host, sharding.as_ref())? {
+            buffers.push(shard.to(&device).copy().await?);
+        }
+        Self::new(host.dims().to_vec(), sharding, buffers)
+    }
+
+    /// Synchronous version of [`ShardedArray::from_host`].
+    pub fn from_host_sync(
+        client: &Client,
+        host: &HostBuffer,
+        sharding: Arc<dyn Sharding>,
+    ) -> Result<Self> {
+        let buffers = host_shards(client, host, sharding.as_ref())?
+            .into_iter()
+            .map(|(device, shard)| shard.to_sync(&device).copy())
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(host.dims().to_vec(), sharding, buffers)
+    }
+
+    /// Wraps the outputs of a multi-device execution.
+    ///
+    /// `outputs` holds the output buffers per device, as returned by
+    /// [`Execution::run`](crate::Execution::run), and `specs` gives the global
+    /// shape and sharding of each output.
+    pub fn from_outputs(
+        outputs: Vec<Vec<Buffer>>,
+        specs: impl IntoIterator<Item = (Vec<i64>, Arc<dyn Sharding>)>,
+    ) -> Result<Vec<Self>> {
+        let specs: Vec<_> = specs.into_iter().collect();
+        let mut per_output: Vec<Vec<Buffer>> = specs.iter().map(|_| vec![]).collect();
+        for (device, buffers) in outputs.into_iter().enumerate() {
+            if buffers.len() != specs.len() {
+                return Err(Error::InvalidArgument(format!(
+                    "device {device} has {} outputs, expected {}",
+                    buffers.len(),
+                    specs.len()
+                )));
+            }
+            for (output, buffer) in per_output.iter_mut().zip(buffers) {
+                output.push(buffer);
+            }
+        }
+        specs
+            .into_iter()
+            .zip(per_output)
+            .map(|((global_shape, sharding), buffers)| Self::new(global_shape, sharding, buffers))
+            .collect()
+    }
+
+    /// Arranges the shards of `arrays` as the per-device arguments of a
+    /// multi-partition execution of `executable`.
+    ///
+    /// The outer vector follows the order of
+    /// [`LoadedExecutable::addressable_devices`], and each inner vector holds
+    /// one shard per array. The shards are borrowed, so they are not donated
+    /// unless requested with [`Execution::donate`](crate::Execution::donate).
+    pub fn execution_inputs<'a>(
+        executable: &LoadedExecutable,
+        arrays: &[&'a ShardedArray],
+    ) -> Result<Vec<Vec<&'a Buffer>>> {
+        executable
+            .addressable_devices()?
+            .iter()
+            .map(|device| {
+                let device_id = device.description()?.id()?;
+                arrays
+                    .iter()
+                    .enumerate()
+                    .map(|(arg, array)| {
+                        array.shard(device_id).map(Shard::buffer).ok_or_else(|| {
+                            Error::InvalidArgument(format!(
+                                "argument {arg} has no shard on device {device_id}"
+                            ))
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn client(&self) -> &Client {
+        self.shards[0].buffer.client()
+    }
+
+    pub fn global_shape(&self) -> &[i64] {
+        &self.global_shape
+    }
+
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.primitive_type
+    }
+
+    pub fn sharding(&self) -> &Arc<dyn Sharding> {
+        &self.sharding
+    }
+
+    /// Returns the shards of the addressable devices, in shard order.
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    /// Returns the shard held by the device `device_id`, if it is addressable.
+    pub fn shard(&self, device_id: GlobalDeviceId) -> Option<&Shard> {
+        self.shards.iter().find(|s| s.device_id == device_id)
+    }
+
+    pub fn into_shards(self) -> Vec<Shard> {
+        self.shards
+    }
+
+    /// Gathers the shards into a single row-major [`HostBuffer`].
+    ///
+    /// Fails if the addressable shards do not cover the whole array.
+    pub async fn to_host(&self) -> Result<HostBuffer> {
+        let elem_size = self.primitive_type.try_into_dtype()?.size();
+        let mut bytes = vec![0; self.byte_size(elem_size)];
+        for shard in self.distinct_shards()? {
+            let dims = shard_dims(&shard.index);
+            let layout = MemoryLayout::from_strides(utils::byte_strides(&dims, elem_size));
+            let host = shard.buffer.to_host(Some(layout)).await?;
+            insert_slice(
+                &mut bytes,
+                &self.global_shape,
+                &shard.index,
+                host.as_bytes(),
+                elem_size,
+            );
+        }
+        HostBuffer::from_bytes(
+            bytes,
+            self.primitive_type,
+            Some(self.global_shape.clone()),
+            None,
+        )
+    }
+
+    /// Synchronous version of [`ShardedArray::to_host`].
+    pub fn to_host_sync(&self) -> Result<HostBuffer> {
+        let elem_size = self.primitive_type.try_into_dtype()?.size();
+        let mut bytes = vec![0; self.byte_size(elem_size)];
+        for shard in self.distinct_shards()? {
+            let dims = shard_dims(&shard.index);
+            let layout = MemoryLayout::from_strides(utils::byte_strides(&dims, elem_size));
+            let host = shard.buffer.to_host_sync(Some(layout))?;
+            insert_slice(
+                &mut bytes,
+                &self.global_shape,
+                &shard.index,
+                host.as_bytes(),
+                elem_size,
+            );
+        }
+        HostBuffer::from_bytes(
+            bytes,
+            self.primitive_type,
+            Some(self.global_shape.clone()),
+            None,
+        )
+    }
+
+    fn byte_size(&self, elem_size: usize) -> usize {
+        self.global_shape.iter().product::<i64>() as usize * elem_size
+    }
+
+    /// Returns one shard per distinct slice, checking that together they
+    /// cover the whole array.
+    fn distinct_shards(&self) -> Result<Vec<&Shard>> {
+        let mut shards: Vec<&Shard> = vec![];
+        for shard in &self.shards {
+            if !shards.iter().any(|s| s.index == shard.index) {
+                shards.push(shard);
+            }
+        }
+        let covered: i64 = shards.iter().map(|s| volume(&s.index)).sum();
+        if covered != self.global_shape.iter().product::<i64>() {
+            return Err(Error::InvalidArgument(
+                "the addressable shards do not cover the whole array".to_string(),
+            ));
+        }
+        Ok(shards)
+    }
+}
+
+/// Slices `host` into the shards of the addressable devices of `sharding`.
+fn host_shards(
+    client: &Client,
+    host: &HostBuffer,
+    sharding: &dyn Sharding,
+) -> Result<Vec<(Device, HostBuffer)>> {
+    let ty = host.primitive_type();
+    let elem_size = ty.try_into_dtype()?.size();
+    let shape = host.dims();
+    if !utils::is_row_major(host.layout(), shape, elem_size) {
+        return Err(Error::InvalidArgument(
+            "host buffer must have a dense row-major layout to be sharded".to_string(),
+        ));
+    }
+    let mut shards = vec![];
+    for (device_id, index) in sharding.shard_indices(shape)? {
+        let device = client.lookup_device(device_id)?;
+        if !device.is_addressable()? {
+            continue;
+        }
+        let bytes = extract_slice(host.as_bytes(), shape, &index, elem_size);
+        let shard = HostBuffer::from_bytes(bytes, ty, Some(shard_dims(&index)), None)?;
+        shards.push((device, shard));
+    }
+    Ok(shards)
+}
+
+pub(crate) fn shard_dims(index: &[Range<i64>]) -> Vec<i64> {
+    index.iter().map(|r| r.end - r.start).collect()
+}
+
+pub(crate) fn volume(index: &[Range<i64>]) -> i64 {
+    index.iter().map(|r| r.end - r.start).product()
+}
+
+/// Calls `f(offset, slice_offset, len)` with the byte offsets in the array and
+/// in the slice of every contiguous run of the slice `index` of a row-major
+/// array of shape `shape`.
+fn for_each_run(
+    shape: &[i64],
+    index: &[Range<i64>],
+    elem_size: usize,
+    mut f: impl FnMut(usize, usize, usize),
+) {
+    let extents: Vec<usize> = shard_dims(index).iter().map(|d| *d as usize).collect();
+    if extents.contains(&0) {
+        return;
+    }
+    let strides = utils::byte_strides(shape, elem_size);
+    let (outer, run) = match extents.split_last() {
+        Some((last, outer)) => (outer, last * elem_size),
+        None => (&extents[..], elem_size),
+    };
+    for n in 0..outer.iter().product() {
+        let coords = unravel_index(n, outer);
+        let offset: i64 = index
+            .iter()
+            .zip(&strides)
+            .enumerate()
+            .map(|(d, (r, stride))| (r.start + coords.get(d).map_or(0, |c| *c as i64)) * stride)
+            .sum();
+        f(offset as usize, n * run, run);
+    }
+}
+
+/// Copies the slice `index` out of the row-major array `src` of shape
+/// `shape`.
+pub(crate) fn extract_slice(
+    src: &[u8],
+    shape: &[i64],
+    index: &[Range<i64>],
+    elem_size: usize,
+) -> Vec<u8> {
+    let mut dst = vec![0; volume(index) as usize * elem_size];
+    for_each_run(shape, index, elem_size, |offset, slice_offset, len| {
+        dst[slice_offset..slice_offset + len].copy_from_slice(&src[offset..offset + len]);
+    });
+    dst
+}
+
+/// Writes the row-major slice `src` to the slice `index` of the row-major
+/// array `dst` of shape `shape`.
+pub(crate) fn insert_slice(
+    dst: &mut [u8],
+    shape: &[i64],
+    index: &[Range<i64>],
+    src: &[u8],
+    elem_size: usize,
+) {
+    for_each_run(shape, index, elem_size, |offset, slice_offset, len| {
+        dst[offset..offset + len].copy_from_slice(&src[slice_offset..slice_offset + len]);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iota(n: u8) -> Vec<u8> {
+        (0..n).collect()
+    }
+
+    #[test]
+    fn test_extract_slice() {
+        // [[0, 1, 2], [3, 4, 5]]
+        let src = iota(6);
+        assert_eq!(extract_slice(&src, &[2, 3], &[0..2, 1..3], 1), [1, 2, 4, 5]);
+        assert_eq!(extract_slice(&src, &[2, 3], &[1..2, 0..3], 1), [3, 4, 5]);
+        assert!(extract_slice(&src, &[2, 3], &[2..2, 0..3], 1).is_empty());
+    }
+
+    #[test]
+    fn test_extract_slice_elem_size() {
+        let src = iota(16);
+        assert_eq!(
+            extract_slice(&src, &[2, 2], &[0..2, 1..2], 4),
+            [4, 5, 6, 7, 12, 13, 14, 15]
+        );
+    }
+
+    #[test]
+    fn test_extract_slice_scalar() {
+        assert_eq!(extract_slice(&[7, 8], &[], &[], 2), [7, 8]);
+    }
+
+    #[test]
+    fn test_insert_slice_round_trip() {
+        let shape = [4, 3, 2];
+        let src = iota(24);
+        let slices = [
+            vec![0..2, 0..3, 0..1],
+            vec![0..2, 0..3, 1..2],
+            vec![2..4, 0..2, 0..2],
+            vec![2..4, 2..3, 0..2],
+        ];
+        let mut dst = vec![0; src.len()];
+        for index in &slices {
+            let shard = extract_slice(&src, &shape, index, 1);
+            assert_eq!(shard.len() as i64, volume(index));
+            insert_slice(&mut dst, &shape, index, &shard, 1);
+        }
+        assert_eq!(dst, src);
+    }
+
+    #[test]
+    fn test_shard_dims() {
+        assert_eq!(shard_dims(&[0..4, 2..3]), [4, 1]);
+        assert!(shard_dims(&[]).is_empty());
+    }
+}
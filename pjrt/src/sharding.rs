@@ -0,0 +1,763 @@
+//! Array Shardings
+//!
+//! This module describes how an array is partitioned over devices:
+//!
+//! - [`NamedSharding`]: a [`Mesh`] and a [`PartitionSpec`] naming, for each
+//!   array dimension, the mesh axes it is split over. Mesh axes that no
+//!   dimension uses replicate the array.
+//! - [`Replicated`]: a full copy of the array on every device.
+//! - [`PositionalSharding`]: a grid of devices with one dimension per array
+//!   dimension, like `jax.sharding.PositionalSharding`.
+//!
+//! All of them implement [`Sharding`], which converts them to `xla::OpSharding`
+//! protos, to the textual `mhlo.sharding` and `sdy.sharding` attributes, and
+//! computes which slice of a global shape each device holds.
+//!
+//! Dimensions that do not divide evenly are split like XLA does: every shard
+//! but the last has `ceil(dim / num_shards)` elements, and trailing shards may
+//! be empty.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use pjrt::{Mesh, NamedSharding, PartitionSpec, Sharding};
+//!
+//! let mesh = Mesh::new(&[("data", 2), ("model", 2)], vec![0, 1, 2, 3]).unwrap();
+//! let sharding = NamedSharding::new(mesh, PartitionSpec::new().sharded("data")).unwrap();
+//!
+//! assert_eq!(
+//!     sharding.to_mhlo(2).unwrap(),
+//!     "{devices=[2,1,2]0,1,2,3 last_tile_dim_replicate}"
+//! );
+//! assert_eq!(
+//!     sharding.to_sdy(2, "mesh").unwrap(),
+//!     r#"#sdy.sharding<@mesh, [{"data"}, {}]>"#
+//! );
+//!
+//! // Devices 0 and 1 hold the first 4 rows, devices 2 and 3 the last 4.
+//! let indices = sharding.shard_indices(&[8, 3]).unwrap();
+//! assert_eq!(indices[1], (1, vec![0..4, 0..3]));
+//! assert_eq!(indices[2], (2, vec![4..8, 0..3]));
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use pjrt_sys::protos::xla::{op_sharding, OpSharding};
+
+use crate::mesh::unravel_index;
+use crate::{Error, GlobalDeviceId, Mesh, Result, ShardingAnnotation};
+
+/// The mesh axes each dimension of an array is split over.
+///
+/// Dimensions past the end of the spec are not split. A dimension split
+/// over several axes is split over the first axis, then each part over the
+/// next, and so on.
+///
+/// # Examples
+///
+/// ```rust
+/// use pjrt::PartitionSpec;
+///
+/// let spec = PartitionSpec::new()
+///     .sharded("data")
+///     .unsharded()
+///     .sharded_over(&["x", "y"]);
+/// assert_eq!(spec.to_string(), "PartitionSpec('data', None, ('x', 'y'))");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PartitionSpec {
+    dims: Vec<Vec<String>>,
+}
+
+impl PartitionSpec {
+    /// Creates a spec that splits no dimension.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves the next dimension unsplit.
+    pub fn unsharded(self) -> Self {
+        self.sharded_over(&[])
+    }
+
+    /// Splits the next dimension over mesh axis `axis`.
+    pub fn sharded(self, axis: &str) -> Self {
+        self.sharded_over(&[axis])
+    }
+
+    /// Splits the next dimension over mesh axes `axes`, major to minor.
+    pub fn sharded_over(mut self, axes: &[&str]) -> Self {
+        self.dims
+            .push(axes.iter().map(|axis| axis.to_string()).collect());
+        self
+    }
+
+    /// Returns the mesh axes of each dimension.
+    pub fn dims(&self) -> &[Vec<String>] {
+        &self.dims
+    }
+
+    pub fn len(&self) -> usize {
+        self.dims.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dims.is_empty()
+    }
+}
+
+impl Display for PartitionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PartitionSpec(")?;
+        for (i, axes) in self.dims.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match axes.as_slice() {
+                [] => write!(f, "None")?,
+                [axis] => write!(f, "'{axis}'")?,
+                axes => {
+                    write!(f, "(")?;
+                    for (j, axis) in axes.iter().enumerate() {
+                        if j > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "'{axis}'")?;
+                    }
+                    write!(f, ")")?;
+                }
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// A description of how an array is partitioned over devices.
+///
+/// Implementors only provide [`Sharding::to_named_sharding`]; every other
+/// method is derived from the equivalent [`NamedSharding`].
+pub trait Sharding: fmt::Debug + Send + Sync {
+    /// Returns the equivalent named sharding.
+    fn to_named_sharding(&self) -> Cow<'_, NamedSharding>;
+
+    /// Returns the devices holding a shard, in shard order.
+    fn device_ids(&self) -> Vec<GlobalDeviceId> {
+        self.to_named_sharding().mesh.device_ids().to_vec()
+    }
+
+    /// Returns whether every device holds the whole array.
+    fn is_fully_replicated(&self) -> bool {
+        let named = self.to_named_sharding();
+        named
+            .partition_spec
+            .dims
+            .iter()
+            .flatten()
+            .all(|axis| matches!(named.mesh.axis_size(axis), Ok(1)))
+    }
+
+    /// Converts the sharding of an array of rank `rank` to an OpSharding.
+    ///
+    /// The tile assignment lists logical device ids: positions in the device
+    /// assignment returned by `mesh.to_device_assignment(&[])`, which lists
+    /// the devices in mesh order, not global device ids.
+    fn to_op_sharding(&self, rank: usize) -> Result<OpSharding> {
+        let named = self.to_named_sharding();
+        named.check_rank(rank)?;
+        let mesh = &named.mesh;
+        if self.is_fully_replicated() {
+            return Ok(OpSharding {
+                r#type: op_sharding::Type::Replicated as i32,
+                ..Default::default()
+            });
+        }
+
+        let mut tiles = vec![1; rank];
+        let mut order = vec![];
+        for (tile, axes) in tiles.iter_mut().zip(&named.partition_spec.dims) {
+            for axis in axes {
+                let index = mesh.axis_index(axis)?;
+                *tile *= mesh.shape()[index];
+                order.push(index);
+            }
+        }
+        let replicated_axes: Vec<usize> = (0..mesh.ndim()).filter(|i| !order.contains(i)).collect();
+        let num_replicas: usize = replicated_axes.iter().map(|i| mesh.shape()[*i]).product();
+        order.extend(replicated_axes);
+
+        let mut dims: Vec<i64> = tiles.iter().map(|t| *t as i64).collect();
+        if num_replicas > 1 {
+            dims.push(num_replicas as i64);
+        }
+        Ok(OpSharding {
+            r#type: op_sharding::Type::Other as i32,
+            tile_assignment_dimensions: dims,
+            tile_assignment_devices: mesh
+                .transposed_positions(&order)
+                .into_iter()
+                .map(|position| position as i64)
+                .collect(),
+            replicate_on_last_tile_dim: num_replicas > 1,
+            ..Default::default()
+        })
+    }
+
+    /// Converts the sharding of an array of rank `rank` to the value of an
+    /// `mhlo.sharding` attribute, e.g. `{devices=[2,1]0,1}`.
+    fn to_mhlo(&self, rank: usize) -> Result<String> {
+        format_mhlo(&self.to_op_sharding(rank)?)
+    }
+
+    /// Converts the sharding of an array of rank `rank` to the value of an
+    /// `sdy.sharding` attribute on the mesh named `mesh_name`, e.g.
+    /// `#sdy.sharding<@mesh, [{"data"}, {}]>`.
+    ///
+    /// The module must declare the mesh returned by [`Sharding::sdy_mesh`].
+    fn to_sdy(&self, rank: usize, mesh_name: &str) -> Result<String> {
+        let named = self.to_named_sharding();
+        named.check_rank(rank)?;
+        let mut dims = Vec::with_capacity(rank);
+        for i in 0..rank {
+            let axes = named
+                .partition_spec
+                .dims
+                .get(i)
+                .map_or(&[][..], Vec::as_slice);
+            let axes: Vec<String> = axes.iter().map(|axis| format!("\"{axis}\"")).collect();
+            dims.push(format!("{{{}}}", axes.join(", ")));
+        }
+        Ok(format!(
+            "#sdy.sharding<@{mesh_name}, [{}]>",
+            dims.join(", ")
+        ))
+    }
+
+    /// Returns the declaration of the mesh referenced by [`Sharding::to_sdy`],
+    /// e.g. `sdy.mesh @mesh = <["data"=2, "model"=2]>`.
+    ///
+    /// The mesh is over logical device ids in mesh order, so it never lists
+    /// explicit `device_ids`; compile with the device assignment returned by
+    /// `mesh.to_device_assignment(&[])` to place it on the mesh's devices.
+    fn sdy_mesh(&self, mesh_name: &str) -> String {
+        let named = self.to_named_sharding();
+        let mesh = &named.mesh;
+        let axes: Vec<String> = mesh
+            .axis_names()
+            .iter()
+            .zip(mesh.shape())
+            .map(|(name, size)| format!("\"{name}\"={size}"))
+            .collect();
+        format!("sdy.mesh @{mesh_name} = <[{}]>", axes.join(", "))
+    }
+
+    /// Returns the `mhlo.sharding` annotation for an array of rank `rank`.
+    fn to_annotation(&self, rank: usize) -> Result<ShardingAnnotation> {
+        self.to_mhlo(rank).map(ShardingAnnotation::Mhlo)
+    }
+
+    /// Returns the slice of an array of shape `shape` held by each device,
+    /// in shard order.
+    fn shard_indices(&self, shape: &[i64]) -> Result<Vec<(GlobalDeviceId, Vec<Range<i64>>)>> {
+        let named = self.to_named_sharding();
+        named.check_rank(shape.len())?;
+        if let Some(dim) = shape.iter().find(|dim| **dim < 0) {
+            return Err(Error::InvalidArgument(format!(
+                "cannot shard dynamic or negative dimension {dim}"
+            )));
+        }
+        let mesh = &named.mesh;
+        let axes = named
+            .partition_spec
+            .dims
+            .iter()
+            .map(|axes| {
+                axes.iter()
+                    .map(|axis| mesh.axis_index(axis))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(mesh
+            .device_ids()
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                let coords = unravel_index(index, mesh.shape());
+                let slices = shape
+                    .iter()
+                    .enumerate()
+                    .map(|(d, dim)| {
+                        let (mut shard, mut num_shards) = (0, 1);
+                        for axis in axes.get(d).into_iter().flatten() {
+                            shard = shard * mesh.shape()[*axis] + coords[*axis];
+                            num_shards *= mesh.shape()[*axis];
+                        }
+                        shard_range(*dim, shard as i64, num_shards as i64)
+                    })
+                    .collect();
+                (*id, slices)
+            })
+            .collect())
+    }
+}
+
+/// Returns the range of shard `shard` of `num_shards` of a dimension.
+fn shard_range(dim: i64, shard: i64, num_shards: i64) -> Range<i64> {
+    let size = (dim + num_shards - 1) / num_shards;
+    let start = (shard * size).min(dim);
+    start..(start + size).min(dim)
+}
+
+/// Formats an OpSharding as the value of an `mhlo.sharding` attribute.
+fn format_mhlo(op: &OpSharding) -> Result<String> {
+    match op.r#type() {
+        op_sharding::Type::Replicated => Ok("{replicated}".to_string()),
+        op_sharding::Type::Maximal => match op.tile_assignment_devices.as_slice() {
+            [device] => Ok(format!("{{maximal device={device}}}")),
+            _ => Err(Error::InvalidArgument(
+                "maximal sharding needs exactly one device".to_string(),
+            )),
+        },
+        op_sharding::Type::Other => {
+            let join = |values: &[i64]| {
+                values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            let mut text = format!(
+                "{{devices=[{}]{}",
+                join(&op.tile_assignment_dimensions),
+                join(&op.tile_assignment_devices)
+            );
+            if op.replicate_on_last_tile_dim {
+                text.push_str(" last_tile_dim_replicate");
+            }
+            text.push('}');
+            Ok(text)
+        }
+        ty => Err(Error::InvalidArgument(format!(
+            "unsupported sharding type {ty:?}"
+        ))),
+    }
+}
+
+/// An array partitioned over the axes of a mesh.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NamedSharding {
+    mesh: Mesh,
+    partition_spec: PartitionSpec,
+}
+
+impl NamedSharding {
+    /// Creates a sharding of arrays over `mesh` as described by
+    /// `partition_spec`, whose axes must be in `mesh` and used at most once.
+    pub fn new(mesh: Mesh, partition_spec: PartitionSpec) -> Result<Self> {
+        let mut used: Vec<&str> = vec![];
+        for axis in partition_spec.dims.iter().flatten() {
+            mesh.axis_index(axis)?;
+            if used.contains(&axis.as_str()) {
+                return Err(Error::InvalidArgument(format!(
+                    "mesh axis {axis} used twice in {partition_spec}"
+                )));
+            }
+            used.push(axis);
+        }
+        Ok(Self {
+            mesh,
+            partition_spec,
+        })
+    }
+
+    /// Creates a sharding that replicates arrays over every device of `mesh`.
+    pub fn replicated(mesh: Mesh) -> Self {
+        Self {
+            mesh,
+            partition_spec: PartitionSpec::new(),
+        }
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    pub fn partition_spec(&self) -> &PartitionSpec {
+        &self.partition_spec
+    }
+
+    fn check_rank(&self, rank: usize) -> Result<()> {
+        if self.partition_spec.len() > rank {
+            return Err(Error::InvalidArgument(format!(
+                "{} has more dimensions than an array of rank {rank}",
+                self.partition_spec
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Sharding for NamedSharding {
+    fn to_named_sharding(&self) -> Cow<'_, NamedSharding> {
+        Cow::Borrowed(self)
+    }
+}
+
+/// An array with a full copy on every device.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Replicated {
+    device_ids: Vec<GlobalDeviceId>,
+}
+
+impl Replicated {
+    /// Creates a sharding that replicates arrays over `device_ids`.
+    pub fn new(device_ids: Vec<GlobalDeviceId>) -> Result<Self> {
+        if device_ids.is_empty() {
+            return Err(Error::InvalidArgument(
+                "replicated sharding needs at least one device".to_string(),
+            ));
+        }
+        Mesh::new(&[("replica", device_ids.len())], device_ids.clone())?;
+        Ok(Self { device_ids })
+    }
+
+    /// Creates a sharding that replicates arrays over every device of
+    /// `mesh`.
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        Self {
+            device_ids: mesh.device_ids().to_vec(),
+        }
+    }
+}
+
+impl Sharding for Replicated {
+    fn to_named_sharding(&self) -> Cow<'_, NamedSharding> {
+        let mesh = Mesh::new(
+            &[("replica", self.device_ids.len())],
+            self.device_ids.clone(),
+        )
+        .expect("device ids are validated on construction");
+        Cow::Owned(NamedSharding::replicated(mesh))
+    }
+}
+
+/// An array partitioned over a grid of devices with one dimension per array
+/// dimension.
+///
+/// Each device grid dimension splits the array dimension at the same
+/// position. Replicated dimensions have size 1 in the grid and their devices
+/// hold copies of the same shards.
+///
+/// # Examples
+///
+/// ```rust
+/// use pjrt::{PositionalSharding, Sharding};
+///
+/// // Split rows over 2 devices and columns over 2.
+/// let sharding = PositionalSharding::new(&[2, 2], vec![0, 1, 2, 3]).unwrap();
+/// assert_eq!(sharding.to_mhlo(2).unwrap(), "{devices=[2,2]0,1,2,3}");
+///
+/// // Only split rows, replicating over the column devices.
+/// let rows = sharding.replicate(1).unwrap();
+/// assert_eq!(rows.shape(), [2, 1]);
+/// assert_eq!(
+///     rows.to_mhlo(2).unwrap(),
+///     "{devices=[2,1,2]0,1,2,3 last_tile_dim_replicate}"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PositionalSharding {
+    shape: Vec<usize>,
+    num_replicas: usize,
+    /// Row-major over `shape` followed by the replicas.
+    device_ids: Vec<GlobalDeviceId>,
+    named: NamedSharding,
+}
+
+impl PositionalSharding {
+    /// Creates a sharding over a device grid of shape `shape`, listing
+    /// `device_ids` in row-major order.
+    pub fn new(shape: &[usize], device_ids: Vec<GlobalDeviceId>) -> Result<Self> {
+        Self::with_replicas(shape.to_vec(), 1, device_ids)
+    }
+
+    fn with_replicas(
+        shape: Vec<usize>,
+        num_replicas: usize,
+        device_ids: Vec<GlobalDeviceId>,
+    ) -> Result<Self> {
+        let names: Vec<String> = (0..shape.len()).map(|i| format!("axis_{i}")).collect();
+        let mut axes: Vec<(&str, usize)> = names
+            .iter()
+            .map(String::as_str)
+            .zip(shape.iter().copied())
+            .collect();
+        axes.push(("replica", num_replicas));
+        let mesh = Mesh::new(&axes, device_ids.clone())?;
+
+        let mut spec = PartitionSpec::new();
+        for (name, size) in &axes[..shape.len()] {
+            spec = if *size > 1 {
+                spec.sharded(name)
+            } else {
+                spec.unsharded()
+            };
+        }
+        Ok(Self {
+            named: NamedSharding::new(mesh, spec)?,
+            shape,
+            num_replicas,
+            device_ids,
+        })
+    }
+
+    /// Returns the device grid shape, one dimension per array dimension.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Returns how many devices hold each shard.
+    pub fn num_replicas(&self) -> usize {
+        self.num_replicas
+    }
+
+    /// Returns the sharding with dimension `dim` replicated instead of split.
+    pub fn replicate(&self, dim: usize) -> Result<Self> {
+        if dim >= self.shape.len() {
+            return Err(Error::InvalidArgument(format!(
+                "dimension {dim} out of range for device grid {:?}",
+                self.shape
+            )));
+        }
+        // Move `dim` next to the replicas, which vary fastest.
+        let mesh = &self.named.mesh;
+        let mut order: Vec<usize> = (0..self.shape.len()).filter(|d| *d != dim).collect();
+        order.extend([dim, self.shape.len()]);
+        let device_ids = mesh.transposed_device_ids(&order);
+
+        let mut shape = self.shape.clone();
+        shape[dim] = 1;
+        Self::with_replicas(shape, self.num_replicas * self.shape[dim], device_ids)
+    }
+}
+
+impl Sharding for PositionalSharding {
+    fn to_named_sharding(&self) -> Cow<'_, NamedSharding> {
+        Cow::Borrowed(&self.named)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh_2x2() -> Mesh {
+        Mesh::new(&[("data", 2), ("model", 2)], vec![0, 1, 2, 3]).unwrap()
+    }
+
+    #[test]
+    fn test_partition_spec() {
+        let spec = PartitionSpec::new()
+            .sharded("a")
+            .unsharded()
+            .sharded_over(&["b", "c"]);
+        assert_eq!(spec.len(), 3);
+        assert_eq!(spec.dims()[2], ["b", "c"]);
+        assert_eq!(spec.to_string(), "PartitionSpec('a', None, ('b', 'c'))");
+        assert!(PartitionSpec::new().is_empty());
+        assert_eq!(PartitionSpec::new().to_string(), "PartitionSpec()");
+    }
+
+    #[test]
+    fn test_named_sharding_validates() {
+        let spec = PartitionSpec::new().sharded("pipeline");
+        assert!(NamedSharding::new(mesh_2x2(), spec).is_err());
+        let spec = PartitionSpec::new().sharded("data").sharded("data");
+        assert!(NamedSharding::new(mesh_2x2(), spec).is_err());
+
+        let spec = PartitionSpec::new().sharded("data").sharded("model");
+        let sharding = NamedSharding::new(mesh_2x2(), spec).unwrap();
+        assert!(sharding.to_op_sharding(1).is_err());
+        assert!(sharding.shard_indices(&[4]).is_err());
+        assert!(sharding.shard_indices(&[-1, 4]).is_err());
+    }
+
+    #[test]
+    fn test_named_sharding_tiled() {
+        let spec = PartitionSpec::new().sharded("model").sharded("data");
+        let sharding = NamedSharding::new(mesh_2x2(), spec).unwrap();
+        assert!(!sharding.is_fully_replicated());
+
+        let op = sharding.to_op_sharding(2).unwrap();
+        assert_eq!(op.r#type(), op_sharding::Type::Other);
+        assert_eq!(op.tile_assignment_dimensions, vec![2, 2]);
+        assert_eq!(op.tile_assignment_devices, vec![0, 2, 1, 3]);
+        assert!(!op.replicate_on_last_tile_dim);
+        assert_eq!(sharding.to_mhlo(2).unwrap(), "{devices=[2,2]0,2,1,3}");
+        assert_eq!(
+            sharding.to_sdy(3, "mesh").unwrap(),
+            r#"#sdy.sharding<@mesh, [{"model"}, {"data"}, {}]>"#
+        );
+
+        let indices = sharding.shard_indices(&[4, 6]).unwrap();
+        assert_eq!(
+            indices,
+            vec![
+                (0, vec![0..2, 0..3]),
+                (1, vec![2..4, 0..3]),
+                (2, vec![0..2, 3..6]),
+                (3, vec![2..4, 3..6]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_named_sharding_multiple_axes() {
+        let mesh = Mesh::new(&[("x", 2), ("y", 2), ("z", 2)], (0..8).collect()).unwrap();
+        let spec = PartitionSpec::new().sharded_over(&["z", "x"]);
+        let sharding = NamedSharding::new(mesh, spec).unwrap();
+
+        assert_eq!(
+            sharding.to_mhlo(1).unwrap(),
+            "{devices=[4,2]0,2,4,6,1,3,5,7 last_tile_dim_replicate}"
+        );
+        assert_eq!(
+            sharding.to_sdy(1, "m").unwrap(),
+            r#"#sdy.sharding<@m, [{"z", "x"}]>"#
+        );
+
+        // Device 5 is at (x=1, y=0, z=1): shard 1 * 2 + 1 = 3 of 4.
+        let indices = sharding.shard_indices(&[8, 1]).unwrap();
+        assert_eq!(indices[5], (5, vec![6..8, 0..1]));
+        assert_eq!(indices[2], (2, vec![0..2, 0..1]));
+    }
+
+    #[test]
+    fn test_replicated() {
+        let sharding = NamedSharding::replicated(mesh_2x2());
+        assert!(sharding.is_fully_replicated());
+        assert_eq!(
+            sharding.to_op_sharding(2).unwrap().r#type(),
+            op_sharding::Type::Replicated
+        );
+        assert_eq!(sharding.to_mhlo(0).unwrap(), "{replicated}");
+        assert_eq!(
+            sharding.to_annotation(2).unwrap(),
+            ShardingAnnotation::Mhlo("{replicated}".to_string())
+        );
+
+        let replicated = Replicated::new(vec![3, 1]).unwrap();
+        assert!(replicated.is_fully_replicated());
+        assert_eq!(replicated.device_ids(), vec![3, 1]);
+        assert_eq!(replicated.to_mhlo(1).unwrap(), "{replicated}");
+        assert_eq!(
+            replicated.to_sdy(2, "mesh").unwrap(),
+            "#sdy.sharding<@mesh, [{}, {}]>"
+        );
+        assert_eq!(
+            replicated.shard_indices(&[3, 2]).unwrap(),
+            vec![(3, vec![0..3, 0..2]), (1, vec![0..3, 0..2])]
+        );
+        assert_eq!(
+            Replicated::from_mesh(&mesh_2x2()).device_ids(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(Replicated::new(vec![]).is_err());
+        assert!(Replicated::new(vec![1, 1]).is_err());
+
+        // Splitting over an axis of size 1 does not split.
+        let mesh = Mesh::new(&[("x", 1), ("y", 2)], vec![0, 1]).unwrap();
+        let sharding = NamedSharding::new(mesh, PartitionSpec::new().sharded("x")).unwrap();
+        assert!(sharding.is_fully_replicated());
+    }
+
+    #[test]
+    fn test_sdy_mesh() {
+        assert_eq!(
+            NamedSharding::replicated(mesh_2x2()).sdy_mesh("mesh"),
+            r#"sdy.mesh @mesh = <["data"=2, "model"=2]>"#
+        );
+        let mesh = Mesh::new(&[("x", 2)], vec![1, 0]).unwrap();
+        assert_eq!(
+            NamedSharding::replicated(mesh).sdy_mesh("m"),
+            r#"sdy.mesh @m = <["x"=2]>"#
+        );
+    }
+
+    #[test]
+    fn test_positional_sharding() {
+        let sharding = PositionalSharding::new(&[2, 2], vec![3, 2, 1, 0]).unwrap();
+        assert_eq!(sharding.shape(), [2, 2]);
+        assert_eq!(sharding.num_replicas(), 1);
+        assert_eq!(sharding.to_mhlo(2).unwrap(), "{devices=[2,2]0,1,2,3}");
+        assert_eq!(
+            sharding.shard_indices(&[2, 2]).unwrap()[1],
+            (2, vec![0..1, 1..2])
+        );
+
+        let rows = sharding.replicate(1).unwrap();
+        assert_eq!(rows.shape(), [2, 1]);
+        assert_eq!(rows.num_replicas(), 2);
+        assert_eq!(
+            rows.to_mhlo(2).unwrap(),
+            "{devices=[2,1,2]0,1,2,3 last_tile_dim_replicate}"
+        );
+
+        let columns = sharding.replicate(0).unwrap();
+        assert_eq!(
+            columns.to_mhlo(2).unwrap(),
+            "{devices=[1,2,2]0,1,2,3 last_tile_dim_replicate}"
+        );
+        assert_eq!(
+            columns.shard_indices(&[4, 4]).unwrap(),
+            vec![
+                (3, vec![0..4, 0..2]),
+                (1, vec![0..4, 0..2]),
+                (2, vec![0..4, 2..4]),
+                (0, vec![0..4, 2..4]),
+            ]
+        );
+
+        let all = columns.replicate(1).unwrap();
+        assert!(all.is_fully_replicated());
+        assert_eq!(all.num_replicas(), 4);
+        assert!(sharding.replicate(2).is_err());
+        assert!(PositionalSharding::new(&[2, 2], vec![0, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_uneven_shards() {
+        assert_eq!(shard_range(10, 0, 4), 0..3);
+        assert_eq!(shard_range(10, 3, 4), 9..10);
+        assert_eq!(shard_range(2, 2, 4), 2..2);
+        assert_eq!(shard_range(2, 3, 4), 2..2);
+        assert_eq!(shard_range(0, 1, 2), 0..0);
+    }
+
+    #[test]
+    fn test_format_mhlo() {
+        let op = OpSharding {
+            r#type: op_sharding::Type::Maximal as i32,
+            tile_assignment_dimensions: vec![1],
+            tile_assignment_devices: vec![3],
+            ..Default::default()
+        };
+        assert_eq!(format_mhlo(&op).unwrap(), "{maximal device=3}");
+        let op = OpSharding {
+            r#type: op_sharding::Type::Manual as i32,
+            ..Default::default()
+        };
+        assert!(format_mhlo(&op).is_err());
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<NamedSharding>();
+        assert_send_sync::<Replicated>();
+        assert_send_sync::<PositionalSharding>();
+        assert_send_sync::<Box<dyn Sharding>>();
+    }
+}
@@ -0,0 +1,449 @@
+//! PJRT Executable Signature
+//!
+//! This module provides the `ExecutableSignature` type, which describes the
+//! parameters and outputs of a compiled executable. The signature is derived
+//! from the optimized `HloModuleProto` returned by `Executable::optimize`:
+//!
+//! - Parameter and output shapes come from the entry computation layout
+//! - Input/output aliasing comes from `input_output_alias`
+//! - Donation candidates come from `buffer_donor`
+//! - Output memory kinds are reported by the plugin; PJRT has no query for
+//!   parameter memory kinds, so a parameter takes the kind of an output in
+//!   the same memory space
+//!
+//! Tupled parameters and tuple results are flattened one level, matching the
+//! buffers PJRT expects as arguments and returns as outputs.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let signature = executable.signature()?;
+//! for (i, param) in signature.parameters().iter().enumerate() {
+//!     println!("arg {}: {:?} {:?}", i, param.shape.primitive_type, param.shape.dims);
+//! }
+//! ```
+
+use pjrt_sys::protos::xla::{HloModuleProto, Kind, ProgramShapeProto, ShapeProto};
+
+use crate::{Error, PrimitiveType, Result};
+
+// `xla.PrimitiveType.TUPLE`
+const XLA_TUPLE: i32 = 13;
+
+/// Shape, element type and layout of a single array parameter or output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeSignature {
+    /// Element type of the array.
+    pub primitive_type: PrimitiveType,
+    /// Dimension sizes (upper bounds for dynamic dimensions).
+    pub dims: Vec<i64>,
+    /// Whether each dimension is dynamic.
+    pub dynamic_dims: Vec<bool>,
+    /// Layout as minor-to-major dimension order, if the compiler assigned one.
+    pub minor_to_major: Option<Vec<i64>>,
+    /// Memory space of the layout (0 is the default device memory).
+    pub memory_space: i64,
+}
+
+impl ShapeSignature {
+    fn from_proto(shape: &ShapeProto) -> Result<Self> {
+        let primitive_type = PrimitiveType::from_xla_proto(shape.element_type)?;
+        let (minor_to_major, memory_space) = match &shape.layout {
+            Some(layout) => (Some(layout.minor_to_major.clone()), layout.memory_space),
+            None => (None, 0),
+        };
+        Ok(Self {
+            primitive_type,
+            dims: shape.dimensions.clone(),
+            dynamic_dims: shape.is_dynamic_dimension.clone(),
+            minor_to_major,
+            memory_space,
+        })
+    }
+}
+
+/// The kind of aliasing between a parameter and an output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AliasKind {
+    /// The buffers may or may not alias at runtime.
+    MayAlias,
+    /// The buffers must alias at runtime; the input must be donated.
+    MustAlias,
+}
+
+/// How a parameter's buffer may be reused by the executable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Donation {
+    /// The parameter buffer is never reused.
+    None,
+    /// The parameter may donate its buffer to any output.
+    Donor,
+    /// The parameter buffer is aliased with the given output.
+    Aliased {
+        /// Index of the aliased output.
+        output: usize,
+        /// Kind of aliasing.
+        kind: AliasKind,
+    },
+}
+
+/// Description of one executable parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterSignature {
+    /// Parameter name, if the module records one.
+    pub name: Option<String>,
+    /// Shape, element type and layout.
+    pub shape: ShapeSignature,
+    /// Donation and aliasing information.
+    pub donation: Donation,
+    /// Memory kind, if an output in the same memory space reports one.
+    ///
+    /// PJRT only reports the memory kinds of outputs; parameters in a memory
+    /// space no output uses have no kind.
+    pub memory_kind: Option<String>,
+}
+
+/// Description of one executable output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSignature {
+    /// Shape, element type and layout.
+    pub shape: ShapeSignature,
+    /// Memory kind reported by the executable, if available.
+    pub memory_kind: Option<String>,
+    /// Index of the parameter whose buffer this output aliases, if any.
+    pub aliased_parameter: Option<usize>,
+}
+
+/// Parameters and outputs of a compiled executable.
+///
+/// Obtained through `Executable::signature`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutableSignature {
+    parameters: Vec<ParameterSignature>,
+    outputs: Vec<OutputSignature>,
+}
+
+impl ExecutableSignature {
+    /// Builds a signature from an optimized HLO module.
+    ///
+    /// `output_memory_kinds` is attached to outputs by position, and to
+    /// parameters through the memory space of the outputs; pass an empty
+    /// slice when the plugin does not report memory kinds.
+    pub(crate) fn from_hlo_module(
+        module: &HloModuleProto,
+        output_memory_kinds: &[String],
+    ) -> Result<Self> {
+        let program_shape = entry_program_shape(module)?;
+
+        let tupled_params = program_shape.parameters.len() == 1
+            && program_shape.parameters[0].element_type == XLA_TUPLE;
+        let param_shapes: &[ShapeProto] = if tupled_params {
+            &program_shape.parameters[0].tuple_shapes
+        } else {
+            &program_shape.parameters
+        };
+        let use_names = !tupled_params && program_shape.parameter_names.len() == param_shapes.len();
+        let mut parameters = param_shapes
+            .iter()
+            .enumerate()
+            .map(|(i, shape)| {
+                Ok(ParameterSignature {
+                    name: use_names
+                        .then(|| program_shape.parameter_names[i].clone())
+                        .filter(|name| !name.is_empty()),
+                    shape: ShapeSignature::from_proto(shape)?,
+                    donation: Donation::None,
+                    memory_kind: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let result = program_shape
+            .result
+            .as_ref()
+            .ok_or_else(|| Error::InvalidProgramFormat("HLO module has no result shape".into()))?;
+        let tupled_result = result.element_type == XLA_TUPLE;
+        let output_shapes = if tupled_result {
+            result.tuple_shapes.as_slice()
+        } else {
+            std::slice::from_ref(result)
+        };
+        let mut outputs = output_shapes
+            .iter()
+            .enumerate()
+            .map(|(i, shape)| {
+                Ok(OutputSignature {
+                    shape: ShapeSignature::from_proto(shape)?,
+                    memory_kind: output_memory_kinds.get(i).cloned(),
+                    aliased_parameter: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for param in &mut parameters {
+            param.memory_kind = outputs
+                .iter()
+                .filter(|o| o.shape.memory_space == param.shape.memory_space)
+                .find_map(|o| o.memory_kind.clone());
+        }
+
+        let flat_param = |number: i64, index: &[i64]| -> Option<usize> {
+            let flat = if tupled_params {
+                *index.first()?
+            } else {
+                number
+            };
+            usize::try_from(flat).ok()
+        };
+        let flat_output = |index: &[i64]| -> Option<usize> {
+            let flat = if tupled_result { *index.first()? } else { 0 };
+            usize::try_from(flat).ok()
+        };
+
+        if let Some(donors) = &module.buffer_donor {
+            for entry in &donors.entries {
+                let param = flat_param(entry.parameter_number, &entry.parameter_shape_index);
+                if let Some(param) = param.and_then(|p| parameters.get_mut(p)) {
+                    param.donation = Donation::Donor;
+                }
+            }
+        }
+
+        if let Some(aliases) = &module.input_output_alias {
+            for entry in &aliases.entries {
+                let kind = match entry.kind() {
+                    Kind::MustAlias => AliasKind::MustAlias,
+                    _ => AliasKind::MayAlias,
+                };
+                let param = flat_param(entry.parameter_number, &entry.parameter_shape_index);
+                let output = flat_output(&entry.output_shape_index);
+                let (Some(param), Some(output)) = (param, output) else {
+                    continue;
+                };
+                if param >= parameters.len() || output >= outputs.len() {
+                    continue;
+                }
+                parameters[param].donation = Donation::Aliased { output, kind };
+                outputs[output].aliased_parameter = Some(param);
+            }
+        }
+
+        Ok(Self {
+            parameters,
+            outputs,
+        })
+    }
+
+    /// Returns the parameters, in argument order.
+    pub fn parameters(&self) -> &[ParameterSignature] {
+        &self.parameters
+    }
+
+    /// Returns the outputs, in output order.
+    pub fn outputs(&self) -> &[OutputSignature] {
+        &self.outputs
+    }
+
+    /// Returns the number of parameters.
+    pub fn num_parameters(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// Returns the number of outputs.
+    pub fn num_outputs(&self) -> usize {
+        self.outputs.len()
+    }
+}
+
+fn entry_program_shape(module: &HloModuleProto) -> Result<&ProgramShapeProto> {
+    if let Some(shape) = &module.host_program_shape {
+        return Ok(shape);
+    }
+    module
+        .computations
+        .iter()
+        .find(|c| c.id == module.entry_computation_id || c.name == module.entry_computation_name)
+        .and_then(|c| c.program_shape.as_ref())
+        .ok_or_else(|| Error::InvalidProgramFormat("HLO module has no entry computation".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use pjrt_sys::protos::xla::hlo_buffer_donor_proto::BufferDonorEntryProto;
+    use pjrt_sys::protos::xla::hlo_input_output_alias_proto::AliasEntryProto;
+    use pjrt_sys::protos::xla::{
+        HloBufferDonorProto, HloComputationProto, HloInputOutputAliasProto, LayoutProto,
+    };
+
+    use super::*;
+
+    // xla.PrimitiveType values
+    const F32: i32 = 11;
+    const S32: i32 = 4;
+
+    fn array(element_type: i32, dims: &[i64]) -> ShapeProto {
+        ShapeProto {
+            element_type,
+            dimensions: dims.to_vec(),
+            is_dynamic_dimension: vec![false; dims.len()],
+            layout: Some(LayoutProto {
+                minor_to_major: (0..dims.len() as i64).rev().collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn tuple(elements: Vec<ShapeProto>) -> ShapeProto {
+        ShapeProto {
+            element_type: XLA_TUPLE,
+            tuple_shapes: elements,
+            ..Default::default()
+        }
+    }
+
+    fn module(parameters: Vec<ShapeProto>, result: ShapeProto) -> HloModuleProto {
+        HloModuleProto {
+            host_program_shape: Some(ProgramShapeProto {
+                parameter_names: (0..parameters.len()).map(|i| format!("p{i}")).collect(),
+                parameters,
+                result: Some(result),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_signature_simple() {
+        let m = module(
+            vec![array(F32, &[2, 3]), array(S32, &[])],
+            array(F32, &[2, 3]),
+        );
+        let sig = ExecutableSignature::from_hlo_module(&m, &["device".to_string()]).unwrap();
+
+        assert_eq!(sig.num_parameters(), 2);
+        assert_eq!(sig.num_outputs(), 1);
+        let p0 = &sig.parameters()[0];
+        assert_eq!(p0.name.as_deref(), Some("p0"));
+        assert_eq!(p0.shape.primitive_type, PrimitiveType::F32);
+        assert_eq!(p0.shape.dims, vec![2, 3]);
+        assert_eq!(p0.shape.minor_to_major, Some(vec![1, 0]));
+        assert_eq!(p0.donation, Donation::None);
+        assert_eq!(sig.parameters()[1].shape.primitive_type, PrimitiveType::S32);
+        assert_eq!(sig.outputs()[0].memory_kind.as_deref(), Some("device"));
+        assert_eq!(sig.outputs()[0].aliased_parameter, None);
+    }
+
+    #[test]
+    fn test_signature_flattens_tuple_result() {
+        let m = module(
+            vec![array(F32, &[4])],
+            tuple(vec![array(F32, &[4]), array(S32, &[1])]),
+        );
+        let sig = ExecutableSignature::from_hlo_module(&m, &[]).unwrap();
+
+        assert_eq!(sig.num_outputs(), 2);
+        assert_eq!(sig.outputs()[1].shape.primitive_type, PrimitiveType::S32);
+        assert_eq!(sig.outputs()[1].memory_kind, None);
+    }
+
+    #[test]
+    fn test_signature_parameter_memory_kinds() {
+        let in_space = |memory_space: i64| {
+            let mut shape = array(F32, &[2]);
+            shape.layout.as_mut().unwrap().memory_space = memory_space;
+            shape
+        };
+        let m = module(
+            vec![in_space(5), in_space(0), in_space(1)],
+            tuple(vec![in_space(0), in_space(5)]),
+        );
+        let kinds = ["device".to_string(), "pinned_host".to_string()];
+        let sig = ExecutableSignature::from_hlo_module(&m, &kinds).unwrap();
+
+        let params = sig.parameters();
+        assert_eq!(params[0].memory_kind.as_deref(), Some("pinned_host"));
+        assert_eq!(params[1].memory_kind.as_deref(), Some("device"));
+        assert_eq!(params[2].memory_kind, None);
+
+        let sig = ExecutableSignature::from_hlo_module(&m, &[]).unwrap();
+        assert_eq!(sig.parameters()[0].memory_kind, None);
+    }
+
+    #[test]
+    fn test_signature_flattens_tupled_parameters() {
+        let m = module(
+            vec![tuple(vec![array(F32, &[2]), array(F32, &[3])])],
+            array(F32, &[2]),
+        );
+        let sig = ExecutableSignature::from_hlo_module(&m, &[]).unwrap();
+
+        assert_eq!(sig.num_parameters(), 2);
+        assert_eq!(sig.parameters()[1].shape.dims, vec![3]);
+        assert_eq!(sig.parameters()[1].name, None);
+    }
+
+    #[test]
+    fn test_signature_aliasing_and_donors() {
+        let mut m = module(
+            vec![array(F32, &[4]), array(F32, &[4]), array(F32, &[4])],
+            tuple(vec![array(F32, &[4]), array(F32, &[4])]),
+        );
+        m.input_output_alias = Some(HloInputOutputAliasProto {
+            entries: vec![AliasEntryProto {
+                output_shape_index: vec![1],
+                parameter_number: 0,
+                parameter_shape_index: vec![],
+                kind: Kind::MustAlias as i32,
+            }],
+        });
+        m.buffer_donor = Some(HloBufferDonorProto {
+            entries: vec![BufferDonorEntryProto {
+                parameter_number: 2,
+                parameter_shape_index: vec![],
+            }],
+        });
+        let sig = ExecutableSignature::from_hlo_module(&m, &[]).unwrap();
+
+        assert_eq!(
+            sig.parameters()[0].donation,
+            Donation::Aliased {
+                output: 1,
+                kind: AliasKind::MustAlias
+            }
+        );
+        assert_eq!(sig.parameters()[1].donation, Donation::None);
+        assert_eq!(sig.parameters()[2].donation, Donation::Donor);
+        assert_eq!(sig.outputs()[1].aliased_parameter, Some(0));
+        assert_eq!(sig.outputs()[0].aliased_parameter, None);
+    }
+
+    #[test]
+    fn test_signature_from_entry_computation() {
+        let m = HloModuleProto {
+            entry_computation_name: "main".to_string(),
+            entry_computation_id: 7,
+            computations: vec![HloComputationProto {
+                name: "main".to_string(),
+                id: 7,
+                program_shape: Some(ProgramShapeProto {
+                    parameters: vec![array(F32, &[8])],
+                    result: Some(array(F32, &[8])),
+                    parameter_names: vec![],
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let sig = ExecutableSignature::from_hlo_module(&m, &[]).unwrap();
+        assert_eq!(sig.num_parameters(), 1);
+        assert_eq!(sig.parameters()[0].name, None);
+    }
+
+    #[test]
+    fn test_signature_missing_entry_computation() {
+        let m = HloModuleProto::default();
+        let err = ExecutableSignature::from_hlo_module(&m, &[]).unwrap_err();
+        assert!(matches!(err, Error::InvalidProgramFormat(_)));
+    }
+}
@@ -0,0 +1,181 @@
+//! StableHLO Version Compatibility
+//!
+//! Portable StableHLO artifacts record the StableHLO version they were
+//! serialized with as the bytecode producer (`StableHLO_v1.7.3`). Plugins
+//! advertise the range of versions they can deserialize through the
+//! `stablehlo_minimum_version` and `stablehlo_current_version` plugin
+//! attributes.
+//!
+//! `Client::compile` and `Api::compile` compare the two before handing the
+//! program to the plugin and fail with `Error::IncompatibleStablehloVersion`
+//! instead of an opaque deserialization error. The check is skipped when
+//! either side does not record a version.
+//!
+//! Textual MLIR records no serialization version. Some producers annotate it
+//! with a `stablehlo.version` module attribute, which can be checked on
+//! request with `Api::check_stablehlo_attribute_compatibility`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use pjrt::StablehloVersion;
+//!
+//! let version: StablehloVersion = "1.7.3".parse().unwrap();
+//! assert_eq!(version, StablehloVersion::new(1, 7, 3));
+//! assert!(version < StablehloVersion::new(1, 10, 0));
+//! assert_eq!(version.to_string(), "1.7.3");
+//! ```
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::named_value::Value;
+use crate::{Error, NamedValueMap, Result};
+
+/// A StableHLO version as `major.minor.patch`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StablehloVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl StablehloVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Decodes a plugin attribute value, given as `[major, minor, patch]` or
+    /// as a version string.
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::I64List(parts) => match parts.as_slice() {
+                [major, minor, patch] => Some(Self::new(
+                    u32::try_from(*major).ok()?,
+                    u32::try_from(*minor).ok()?,
+                    u32::try_from(*patch).ok()?,
+                )),
+                _ => None,
+            },
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StablehloVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for StablehloVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidArgument(format!("invalid StableHLO version `{s}`"));
+        let mut parts = s.trim().split('.');
+        let mut next = || -> Result<u32> {
+            parts
+                .next()
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let version = Self::new(next()?, next()?, next()?);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+/// Reads the supported version range from plugin attributes.
+///
+/// A missing `stablehlo_minimum_version` is treated as `0.0.0`.
+pub(crate) fn supported_range(
+    attributes: &NamedValueMap,
+) -> Option<RangeInclusive<StablehloVersion>> {
+    let current = StablehloVersion::from_value(attributes.get("stablehlo_current_version")?)?;
+    let minimum = attributes
+        .get("stablehlo_minimum_version")
+        .and_then(StablehloVersion::from_value)
+        .unwrap_or_default();
+    Some(minimum..=current)
+}
+
+/// Checks a program's StableHLO version against a supported range.
+pub(crate) fn check_version(
+    program: StablehloVersion,
+    supported: &RangeInclusive<StablehloVersion>,
+) -> Result<()> {
+    if supported.contains(&program) {
+        return Ok(());
+    }
+    Err(Error::IncompatibleStablehloVersion {
+        program,
+        minimum: *supported.start(),
+        current: *supported.end(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NamedValue;
+
+    #[test]
+    fn test_stablehlo_version_parse() {
+        let version: StablehloVersion = "1.7.3".parse().unwrap();
+        assert_eq!(version, StablehloVersion::new(1, 7, 3));
+        assert_eq!(version.to_string(), "1.7.3");
+        assert!("1.7".parse::<StablehloVersion>().is_err());
+        assert!("1.7.3.1".parse::<StablehloVersion>().is_err());
+        assert!("1.x.3".parse::<StablehloVersion>().is_err());
+    }
+
+    #[test]
+    fn test_stablehlo_version_ordering() {
+        assert!(StablehloVersion::new(1, 7, 3) < StablehloVersion::new(1, 10, 0));
+        assert!(StablehloVersion::new(2, 0, 0) > StablehloVersion::new(1, 99, 99));
+    }
+
+    #[test]
+    fn test_supported_range() {
+        let attributes = NamedValueMap::from([
+            NamedValue::i64_list("stablehlo_current_version", vec![1, 9, 5]),
+            NamedValue::i64_list("stablehlo_minimum_version", vec![0, 9, 0]),
+        ]);
+        let range = supported_range(&attributes).unwrap();
+        assert_eq!(*range.start(), StablehloVersion::new(0, 9, 0));
+        assert_eq!(*range.end(), StablehloVersion::new(1, 9, 5));
+
+        let attributes =
+            NamedValueMap::from([NamedValue::string("stablehlo_current_version", "1.8.0")]);
+        let range = supported_range(&attributes).unwrap();
+        assert_eq!(*range.start(), StablehloVersion::default());
+        assert_eq!(*range.end(), StablehloVersion::new(1, 8, 0));
+
+        assert!(supported_range(&NamedValueMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_check_version() {
+        let supported = StablehloVersion::new(0, 9, 0)..=StablehloVersion::new(1, 9, 5);
+        assert!(check_version(StablehloVersion::new(1, 7, 3), &supported).is_ok());
+
+        let err = check_version(StablehloVersion::new(1, 10, 0), &supported).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::IncompatibleStablehloVersion { program, .. }
+                if program == StablehloVersion::new(1, 10, 0)
+        ));
+        let message = err.to_string();
+        assert!(message.contains("1.10.0"));
+        assert!(message.contains("1.9.5"));
+    }
+}
@@ -61,6 +61,8 @@ mod error_tests {
             Error::InvalidErrorCode(0),
             Error::InvalidMemoryLayoutType(0),
             Error::DeviceNotInDeviceAssignment(0),
+            Error::DeviceNotAddressable(0),
+            Error::MissingDeviceEvent(0),
             Error::InvalidProgramFormat("test".to_string()),
             Error::NotSupportedType(PrimitiveType::F32),
             Error::NullPointer,
@@ -122,6 +124,7 @@ mod error_tests {
             Error::InvalidErrorCode(0),
             Error::InvalidMemoryLayoutType(0),
             Error::DeviceNotInDeviceAssignment(0),
+            Error::MissingDeviceEvent(0),
             Error::InvalidProgramFormat("test".to_string()),
             Error::NotSupportedType(PrimitiveType::F32),
             Error::NullPointer,
@@ -674,6 +677,15 @@ mod host_buffer_tests {
         assert_eq!(buffer.primitive_type(), PrimitiveType::F32);
     }
 
+    #[test]
+    fn test_host_buffer_as_bytes_round_trip() {
+        let buffer = HostBuffer::from_data(vec![1i32, -2, 3], None, None);
+        let bytes = buffer.as_bytes().to_vec();
+        assert_eq!(bytes.len(), 12);
+        let copy = HostBuffer::from_bytes(bytes, PrimitiveType::S32, Some(vec![3]), None).unwrap();
+        assert_eq!(copy.as_bytes(), buffer.as_bytes());
+    }
+
     #[test]
     fn test_host_buffer_from_bytes_unsupported_type() {
         let bytes = vec![0u8; 4];
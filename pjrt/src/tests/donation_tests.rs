@@ -0,0 +1,127 @@
+//! Buffer Donation Tests
+//!
+//! Tests that every execute entry point donates inputs according to the
+//! executable's aliasing and the donate/keep options:
+//! - Borrowed inputs are kept unless donated
+//! - Donated borrowed inputs fail later use with `Error::BufferDonated`
+//!
+//! These tests require the `integration-tests` feature and the `PJRT_PLUGIN_PATH`
+//! environment variable to be set to a valid PJRT plugin path.
+
+#[cfg(all(test, feature = "integration-tests"))]
+mod integration_tests {
+    use crate::ProgramFormat::MLIR;
+    use crate::{
+        plugin, Buffer, Client, Error, ExecuteOptions, HostBuffer, LoadedExecutable, Program,
+    };
+
+    /// Doubles its argument, which it aliases with its output.
+    const ALIASED: &str = r#"
+module @aliased {
+  func.func @main(%arg0: tensor<4xf32> {tf.aliasing_output = 0 : i32}) -> tensor<4xf32> {
+    %0 = stablehlo.add %arg0, %arg0 : tensor<4xf32>
+    return %0 : tensor<4xf32>
+  }
+}
+"#;
+
+    fn setup_test_client() -> Option<Client> {
+        let plugin_path = match std::env::var("PJRT_PLUGIN_PATH") {
+            Ok(path) => path,
+            Err(_) => {
+                eprintln!("Skipping test: PJRT_PLUGIN_PATH environment variable not set");
+                return None;
+            }
+        };
+        match plugin(&plugin_path).load() {
+            Ok(api) => match Client::builder(&api).build() {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    eprintln!("Skipping test: Failed to create client: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                eprintln!("Skipping test: Failed to load plugin: {}", e);
+                None
+            }
+        }
+    }
+
+    fn setup(client: &Client) -> (LoadedExecutable, Buffer) {
+        let program = Program::new(MLIR, ALIASED.as_bytes());
+        let executable = LoadedExecutable::builder(client, &program).build().unwrap();
+        let input = HostBuffer::from_data(vec![1.0f32, 2.0, 3.0, 4.0], None, None)
+            .to_sync(client)
+            .copy()
+            .unwrap();
+        (executable, input)
+    }
+
+    fn read(buffer: &Buffer) -> crate::Result<Vec<f32>> {
+        Ok(buffer.to_host_sync(None)?.read_f32()?.to_vec())
+    }
+
+    #[test]
+    fn test_execute_keeps_borrowed_inputs() {
+        let Some(client) = setup_test_client() else {
+            return;
+        };
+        let (executable, input) = setup(&client);
+
+        let options = ExecuteOptions::new();
+        let outputs = executable.execute_sync(&input, &options).unwrap();
+        assert_eq!(read(&outputs[0][0]).unwrap(), [2.0, 4.0, 6.0, 8.0]);
+        assert!(!input.is_donated());
+        assert_eq!(read(&input).unwrap(), [1.0, 2.0, 3.0, 4.0]);
+
+        // Keeping an input explicitly has the same effect.
+        let outputs = executable
+            .execution(vec![&input])
+            .keep(0)
+            .run_sync()
+            .unwrap();
+        assert_eq!(read(&outputs[0][0]).unwrap(), [2.0, 4.0, 6.0, 8.0]);
+        assert!(!input.is_donated());
+    }
+
+    #[test]
+    fn test_execute_donates_borrowed_inputs() {
+        let Some(client) = setup_test_client() else {
+            return;
+        };
+        let (executable, input) = setup(&client);
+
+        let options = ExecuteOptions::new().donate(0);
+        let outputs = executable.execute_sync(&input, &options).unwrap();
+        assert_eq!(read(&outputs[0][0]).unwrap(), [2.0, 4.0, 6.0, 8.0]);
+        assert!(input.is_donated());
+
+        // Using the donated buffer fails clearly, including as an input.
+        assert!(matches!(read(&input), Err(Error::BufferDonated)));
+        assert!(matches!(
+            executable.execute_sync(&input, &ExecuteOptions::new()),
+            Err(Error::BufferDonated)
+        ));
+    }
+
+    #[test]
+    fn test_execution_donates_borrowed_inputs() {
+        let Some(client) = setup_test_client() else {
+            return;
+        };
+        let (executable, input) = setup(&client);
+
+        let outputs = executable
+            .execution(vec![&input])
+            .donate(0)
+            .run_sync()
+            .unwrap();
+        assert_eq!(read(&outputs[0][0]).unwrap(), [2.0, 4.0, 6.0, 8.0]);
+        assert!(input.is_donated());
+        assert!(matches!(
+            executable.execution(vec![&input]).run_sync(),
+            Err(Error::BufferDonated)
+        ));
+    }
+}
@@ -23,6 +23,36 @@ mod trait_tests {
     }
 }
 
+#[cfg(test)]
+mod combinator_tests {
+    use crate::{Api, Error, Event};
+
+    #[test]
+    fn test_join_all_empty() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(Event::join_all(Vec::new()));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "select requires at least one event")]
+    fn test_select_empty_panics() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(Event::select(Vec::new()));
+    }
+
+    #[test]
+    fn test_from_future_requires_runtime() {
+        let api = unsafe { Api::empty_for_testing() };
+        let result = Event::from_future(&api, async { Ok(()) });
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+}
+
 #[cfg(test)]
 mod error_code_tests {
     use crate::ErrorCode;
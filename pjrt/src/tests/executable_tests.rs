@@ -229,6 +229,15 @@ mod loaded_executable_types_tests {
         assert_eq!(raw.num_non_donatable_input_indices, 4);
         assert!(!raw.non_donatable_input_indices.is_null());
     }
+
+    #[test]
+    fn test_execute_options_validate_inputs_default() {
+        let options = ExecuteOptions::new();
+        assert!(options.get_validate_inputs());
+
+        let options = options.validate_inputs(false);
+        assert!(!options.get_validate_inputs());
+    }
 }
 
 #[cfg(test)]
@@ -245,6 +254,14 @@ mod execution_inputs_tests {
         assert!(ptrs[0].is_empty());
     }
 
+    #[test]
+    fn test_execution_inputs_empty_tuple_buffers() {
+        let inputs: () = ();
+        let buffers = inputs.buffers().expect("() exposes its buffers");
+        assert_eq!(buffers.len(), 1);
+        assert!(buffers[0].is_empty());
+    }
+
     #[test]
     fn test_execution_inputs_empty_non_donatable() {
         let inputs: () = ();
@@ -0,0 +1,136 @@
+//! Execute Context Tests
+//!
+//! Tests that executions share their `ExecuteContext`:
+//! - User data attached to the context can be read back from an `Execution`
+//! - Launches hold the context until they complete
+//!
+//! These tests require the `integration-tests` feature and the `PJRT_PLUGIN_PATH`
+//! environment variable to be set to a valid PJRT plugin path.
+
+#[cfg(all(test, feature = "integration-tests"))]
+mod integration_tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::ProgramFormat::MLIR;
+    use crate::{
+        plugin, Client, ExecuteOptions, FfiExt, FfiTypeInfo, HostBuffer, LoadedExecutable, Program,
+    };
+
+    const ADD_ONE: &str = r#"
+module @add_one {
+  func.func @main(%arg0: tensor<f32>) -> tensor<f32> {
+    %0 = stablehlo.constant dense<1.000000e+00> : tensor<f32>
+    %1 = stablehlo.add %arg0, %0 : tensor<f32>
+    return %1 : tensor<f32>
+  }
+}
+"#;
+
+    #[derive(Debug, PartialEq)]
+    struct State {
+        scale: f32,
+    }
+
+    fn setup_test_client() -> Option<Client> {
+        let plugin_path = match std::env::var("PJRT_PLUGIN_PATH") {
+            Ok(path) => path,
+            Err(_) => {
+                eprintln!("Skipping test: PJRT_PLUGIN_PATH environment variable not set");
+                return None;
+            }
+        };
+        match plugin(&plugin_path).load() {
+            Ok(api) => match Client::builder(&api).build() {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    eprintln!("Skipping test: Failed to create client: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                eprintln!("Skipping test: Failed to load plugin: {}", e);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_execution_context_user_data() {
+        let Some(client) = setup_test_client() else {
+            return;
+        };
+        let api = client.api();
+        let Some(ffi) = api.ffi_extension() else {
+            eprintln!("Skipping test: plugin lacks the FFI extension");
+            return;
+        };
+        let type_info = FfiTypeInfo {
+            deleter: None,
+            _serialize: Default::default(),
+            _deserialize: Default::default(),
+        };
+        let type_id = ffi
+            .register_type("pjrt_rs_test_state", &type_info, 0)
+            .unwrap();
+        let mut context = api.create_execute_context().unwrap();
+        context
+            .insert_user_data(type_id, State { scale: 2.0 })
+            .unwrap();
+        let context = Arc::new(context);
+
+        let program = Program::new(MLIR, ADD_ONE.as_bytes());
+        let executable = LoadedExecutable::builder(&client, &program)
+            .build()
+            .unwrap();
+        let input = HostBuffer::from_scalar(1.0f32)
+            .to_sync(&client)
+            .copy()
+            .unwrap();
+
+        let execution = executable
+            .execution(vec![&input])
+            .context(Arc::clone(&context));
+        let attached = execution.options.get_context().unwrap();
+        assert!(Arc::ptr_eq(attached, &context));
+        assert_eq!(attached.user_data::<State>(), Some(&State { scale: 2.0 }));
+        assert_eq!(attached.user_data::<u32>(), None);
+        let outputs = execution.run_sync().unwrap();
+        let output = outputs[0][0].to_host_sync(None).unwrap();
+        assert_eq!(output.read_f32().unwrap(), [2.0]);
+
+        // The launch releases the context once it completes.
+        let start = Instant::now();
+        while Arc::strong_count(&context) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_launch_outlives_options_context() {
+        let Some(client) = setup_test_client() else {
+            return;
+        };
+        let context = Arc::new(client.api().create_execute_context().unwrap());
+        let program = Program::new(MLIR, ADD_ONE.as_bytes());
+        let executable = LoadedExecutable::builder(&client, &program)
+            .build()
+            .unwrap();
+        let input = HostBuffer::from_scalar(1.0f32)
+            .to_sync(&client)
+            .copy()
+            .unwrap();
+
+        // Neither the options nor the caller's reference are needed for the
+        // launch to complete.
+        let options = ExecuteOptions::new().context(context);
+        let (events, outputs) = executable.call_execute(&input, &options).unwrap();
+        drop(options);
+        for event in events {
+            event.wait().unwrap();
+        }
+        let output = outputs[0][0].to_host_sync(None).unwrap();
+        assert_eq!(output.read_f32().unwrap(), [2.0]);
+    }
+}
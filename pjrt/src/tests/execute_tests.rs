@@ -91,6 +91,48 @@ mod execute_options_tests {
         assert!(options.get_call_location().is_some());
     }
 
+    #[test]
+    fn test_execute_options_donate_and_keep() {
+        let options = ExecuteOptions::new()
+            .non_donatable_input_indices(vec![0, 1])
+            .donate(1)
+            .donate(2)
+            .donate(2)
+            .keep(3);
+        assert_eq!(options.get_non_donatable_input_indices(), &[0, 3]);
+        assert_eq!(options.get_donated_input_indices(), &[1, 2]);
+
+        let options = options.keep(2).donate(0);
+        assert_eq!(options.get_non_donatable_input_indices(), &[3, 2]);
+        assert_eq!(options.get_donated_input_indices(), &[1, 0]);
+    }
+
+    #[test]
+    fn test_execute_options_deadline_assigns_launch_id() {
+        use std::time::{Duration, Instant};
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let options = ExecuteOptions::new().deadline(deadline);
+        assert_eq!(options.get_deadline(), Some(deadline));
+        assert_ne!(options.get_launch_id(), 0);
+
+        let other = ExecuteOptions::new().deadline(deadline);
+        assert_ne!(other.get_launch_id(), options.get_launch_id());
+    }
+
+    #[test]
+    fn test_execute_options_cancellation_token_keeps_launch_id() {
+        use crate::CancellationToken;
+
+        let token = CancellationToken::new();
+        let options = ExecuteOptions::new()
+            .launch_id(7)
+            .cancellation_token(token.clone());
+        assert_eq!(options.get_launch_id(), 7);
+        token.cancel();
+        assert!(options.get_cancellation_token().unwrap().is_cancelled());
+    }
+
     #[test]
     fn test_execute_options_conversion_to_raw() {
         use pjrt_sys::PJRT_ExecuteOptions;
@@ -102,6 +144,7 @@ mod execute_options_tests {
         let raw: PJRT_ExecuteOptions = (&options).into();
         assert_eq!(raw.launch_id, 123);
         assert_eq!(raw.num_non_donatable_input_indices, 3);
+        assert!(raw.context.is_null());
     }
 
     #[test]
@@ -508,7 +551,7 @@ mod execute_options_raw_tests {
         let options = ExecuteOptions::new();
         let mut raw = PJRT_ExecuteOptions::new();
 
-        let _raw_holder = ExecuteOptionsRaw::new(&options, &mut raw);
+        let _raw_holder = ExecuteOptionsRaw::for_launch(&options, &[], None, &mut raw);
 
         assert_eq!(raw.launch_id, 0);
         assert_eq!(raw.num_non_donatable_input_indices, 0);
@@ -521,7 +564,7 @@ mod execute_options_raw_tests {
         let options = ExecuteOptions::new().launch_id(42);
         let mut raw = PJRT_ExecuteOptions::new();
 
-        let _raw_holder = ExecuteOptionsRaw::new(&options, &mut raw);
+        let _raw_holder = ExecuteOptionsRaw::for_launch(&options, &[], None, &mut raw);
 
         assert_eq!(raw.launch_id, 42);
     }
@@ -531,7 +574,12 @@ mod execute_options_raw_tests {
         let options = ExecuteOptions::new().non_donatable_input_indices(vec![1, 3, 5]);
         let mut raw = PJRT_ExecuteOptions::new();
 
-        let _raw_holder = ExecuteOptionsRaw::new(&options, &mut raw);
+        let _raw_holder = ExecuteOptionsRaw::for_launch(
+            &options,
+            options.get_non_donatable_input_indices(),
+            None,
+            &mut raw,
+        );
 
         assert_eq!(raw.num_non_donatable_input_indices, 3);
         assert!(!raw.non_donatable_input_indices.is_null());
@@ -545,7 +593,7 @@ mod execute_options_raw_tests {
         let options = ExecuteOptions::new().call_location(location);
         let mut raw = PJRT_ExecuteOptions::new();
 
-        let _raw_holder = ExecuteOptionsRaw::new(&options, &mut raw);
+        let _raw_holder = ExecuteOptionsRaw::for_launch(&options, &[], None, &mut raw);
 
         assert!(!raw.call_location.is_null());
     }
@@ -555,13 +603,62 @@ mod execute_options_raw_tests {
         let options = ExecuteOptions::new().task_incarnation_ids(vec![0, 1], vec![10i64, 11]);
         let mut raw = PJRT_ExecuteOptions::new();
 
-        let _raw_holder = ExecuteOptionsRaw::new(&options, &mut raw);
+        let _raw_holder = ExecuteOptionsRaw::for_launch(&options, &[], None, &mut raw);
 
         assert_eq!(raw.num_tasks, 2);
         assert!(!raw.task_ids.is_null());
         assert!(!raw.incarnation_ids.is_null());
     }
 
+    #[test]
+    fn test_execute_options_raw_host_callbacks() {
+        use crate::Api;
+
+        let api = unsafe { Api::empty_for_testing() };
+        let options = ExecuteOptions::new()
+            .on_send(1, |_, _| Ok(()))
+            .on_send(2, |_, _| Ok(()))
+            .on_recv(3, |_, _| Ok(()));
+        let mut raw = PJRT_ExecuteOptions::new();
+
+        let callbacks = options.launch_callbacks(&api, 2);
+        let _raw_holder =
+            ExecuteOptionsRaw::for_launch(&options, &[], callbacks.as_deref(), &mut raw);
+
+        assert_eq!(raw.num_send_ops, 2);
+        assert_eq!(raw.num_recv_ops, 1);
+        unsafe {
+            let device1_sends = *raw.send_callbacks.add(1);
+            assert_eq!((*device1_sends).channel_id, 1);
+            assert_eq!((*device1_sends.add(1)).channel_id, 2);
+            assert!((*device1_sends).send_callback.is_some());
+            assert_eq!((**raw.recv_callbacks).channel_id, 3);
+        }
+    }
+
+    #[test]
+    fn test_execute_options_raw_resolved_non_donatable_indices() {
+        let options = ExecuteOptions::new().non_donatable_input_indices(vec![7]);
+        let resolved = [0i64, 2];
+        let mut raw = PJRT_ExecuteOptions::new();
+
+        let _raw_holder = ExecuteOptionsRaw::for_launch(&options, &resolved, None, &mut raw);
+
+        assert_eq!(raw.num_non_donatable_input_indices, 2);
+        assert_eq!(raw.non_donatable_input_indices, resolved.as_ptr());
+    }
+
+    #[test]
+    fn test_execute_options_raw_without_launch_callbacks_ignores_host_callbacks() {
+        let options = ExecuteOptions::new().on_send(1, |_, _| Ok(()));
+        let mut raw = PJRT_ExecuteOptions::new();
+
+        let _raw_holder = ExecuteOptionsRaw::for_launch(&options, &[], None, &mut raw);
+
+        assert!(raw.send_callbacks.is_null());
+        assert_eq!(raw.num_send_ops, 0);
+    }
+
     #[test]
     fn test_execute_options_raw_lifetime() {
         // Test that data remains valid while raw_holder is alive
@@ -570,7 +667,12 @@ mod execute_options_raw_tests {
             .non_donatable_input_indices(vec![0, 2, 4]);
         let mut raw = PJRT_ExecuteOptions::new();
 
-        let _raw_holder = ExecuteOptionsRaw::new(&options, &mut raw);
+        let _raw_holder = ExecuteOptionsRaw::for_launch(
+            &options,
+            options.get_non_donatable_input_indices(),
+            None,
+            &mut raw,
+        );
 
         // Access raw fields while holder is alive - should be safe
         assert_eq!(raw.launch_id, 99);
@@ -590,7 +692,9 @@ mod execution_inputs_tests {
     // Note: ExecutionInputs trait implementations require Buffer which needs a plugin.
     // We test the trait definition and empty input case here.
 
-    use crate::ExecutionInputs;
+    use pjrt_sys::PJRT_Buffer;
+
+    use crate::{ExecuteOptions, ExecutionInputs};
 
     #[test]
     fn test_unit_execution_inputs() {
@@ -608,4 +712,76 @@ mod execution_inputs_tests {
         let indices = inputs.non_donatable_input_indices();
         assert!(indices.is_empty());
     }
+
+    /// Stands in for borrowed buffers, which keep every argument by default.
+    struct Borrowed(usize);
+
+    impl ExecutionInputs for Borrowed {
+        fn buffer_ptrs(&self) -> Vec<Vec<*mut PJRT_Buffer>> {
+            vec![vec![std::ptr::null_mut(); self.0]]
+        }
+
+        fn non_donatable_input_indices(&self) -> Vec<i64> {
+            (0..self.0 as i64).collect()
+        }
+    }
+
+    #[test]
+    fn test_resolve_keeps_borrowed_inputs() {
+        let options = ExecuteOptions::new();
+        assert_eq!(
+            options.resolve_non_donatable_input_indices(&Borrowed(3)),
+            vec![0, 1, 2]
+        );
+        // Keeping an already kept input changes nothing.
+        let options = ExecuteOptions::new().keep(1);
+        assert_eq!(
+            options.resolve_non_donatable_input_indices(&Borrowed(3)),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_resolve_donates_borrowed_inputs() {
+        let options = ExecuteOptions::new().donate(1);
+        assert_eq!(
+            options.resolve_non_donatable_input_indices(&Borrowed(3)),
+            vec![0, 2]
+        );
+        // Explicit indices add to the inputs' defaults, but not to donations.
+        let options = ExecuteOptions::new()
+            .non_donatable_input_indices(vec![5, 1])
+            .donate(0);
+        assert_eq!(
+            options.resolve_non_donatable_input_indices(&Borrowed(3)),
+            vec![1, 2, 5]
+        );
+        // The last of donate and keep wins.
+        let options = ExecuteOptions::new().donate(1).keep(1);
+        assert_eq!(
+            options.resolve_non_donatable_input_indices(&Borrowed(3)),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_resolve_owned_inputs() {
+        let options = ExecuteOptions::new();
+        assert!(options.resolve_non_donatable_input_indices(&()).is_empty());
+        let options = ExecuteOptions::new().keep(0).donate(1);
+        assert_eq!(options.resolve_non_donatable_input_indices(&()), vec![0]);
+    }
+
+    #[test]
+    fn test_borrowed_empty_execution_inputs() {
+        use crate::Buffer;
+
+        let inputs: &[Buffer] = &[];
+        assert_eq!(inputs.buffer_ptrs(), vec![Vec::new()]);
+        assert!(inputs.non_donatable_input_indices().is_empty());
+
+        let per_device: &[Vec<Buffer>] = &[];
+        assert!(per_device.buffer_ptrs().is_empty());
+        assert!(per_device.non_donatable_input_indices().is_empty());
+    }
 }
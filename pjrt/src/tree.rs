@@ -0,0 +1,949 @@
+//! Structured Inputs and Outputs
+//!
+//! This module provides pytree-style structured values for execution.
+//! Executables take and return flat, positional lists of buffers; `Tree`
+//! pairs such a list with a `TreeDef` describing the nesting it was flattened
+//! from, the same way JAX flattens function arguments before lowering them to
+//! StableHLO.
+//!
+//! Flattening follows JAX's order: tuple, list and struct children in order,
+//! and dict entries sorted by key. A StableHLO module exported from JAX can
+//! therefore be called with inputs of the same nested structure.
+//!
+//! Types implement [`PjrtTree`] to convert to and from trees. It is
+//! implemented for `Buffer` (a leaf), `Option`, tuples, arrays, `Vec`,
+//! `BTreeMap<String, _>` and `Tree<Buffer>`, and can be derived for structs
+//! with `#[derive(PjrtTree)]`.
+//!
+//! Tree definitions are serialized in the text form below, written with
+//! `Display` and parsed back with `FromStr`. There are no `serde`
+//! implementations; store the string, e.g. next to a serialized executable.
+//!
+//! | Node | Syntax |
+//! |------|--------|
+//! | Leaf | `*` |
+//! | None | `None` |
+//! | Tuple | `(*, *)`, `(*,)` |
+//! | List | `[*, *]` |
+//! | Dict | `{'a': *, 'b': *}` |
+//! | Struct | `Params{w: *, b: *}` |
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! use pjrt::{Buffer, PjrtTree};
+//!
+//! #[derive(PjrtTree)]
+//! struct Params {
+//!     w: Buffer,
+//!     b: Buffer,
+//! }
+//!
+//! #[derive(PjrtTree)]
+//! struct Output {
+//!     logits: Buffer,
+//!     state: (Buffer, Buffer),
+//! }
+//!
+//! let output: Output = loaded_executable
+//!     .execute_as((params, x), &ExecuteOptions::new())
+//!     .await?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+
+use crate::{Buffer, Error, Result};
+
+/// The structure of a tree, without its leaves.
+///
+/// The text form written by `Display` and parsed by `FromStr` is the
+/// serialization format of a `TreeDef`:
+///
+/// ```rust
+/// use pjrt::TreeDef;
+///
+/// let def: TreeDef = "(Params{w: *, b: *}, [*, None], {'x': *})".parse().unwrap();
+/// assert_eq!(def.num_leaves(), 4);
+/// assert_eq!(def.to_string().parse::<TreeDef>().unwrap(), def);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TreeDef {
+    /// A single leaf.
+    Leaf,
+    /// An absent value with no leaves.
+    None,
+    Tuple(Vec<TreeDef>),
+    List(Vec<TreeDef>),
+    /// String-keyed entries, flattened in key order.
+    Dict(BTreeMap<String, TreeDef>),
+    /// A named record, such as a struct deriving `PjrtTree`, flattened in
+    /// field order.
+    Struct {
+        name: String,
+        fields: Vec<(String, TreeDef)>,
+    },
+}
+
+impl TreeDef {
+    /// Returns the number of leaves in this tree.
+    pub fn num_leaves(&self) -> usize {
+        match self {
+            TreeDef::Leaf => 1,
+            TreeDef::None => 0,
+            TreeDef::Tuple(children) | TreeDef::List(children) => {
+                children.iter().map(TreeDef::num_leaves).sum()
+            }
+            TreeDef::Dict(entries) => entries.values().map(TreeDef::num_leaves).sum(),
+            TreeDef::Struct { fields, .. } => fields.iter().map(|(_, def)| def.num_leaves()).sum(),
+        }
+    }
+
+    /// Returns the direct children of this node, in flattening order.
+    pub fn children(&self) -> Vec<&TreeDef> {
+        match self {
+            TreeDef::Leaf | TreeDef::None => vec![],
+            TreeDef::Tuple(children) | TreeDef::List(children) => children.iter().collect(),
+            TreeDef::Dict(entries) => entries.values().collect(),
+            TreeDef::Struct { fields, .. } => fields.iter().map(|(_, def)| def).collect(),
+        }
+    }
+
+    /// Returns a tuple of `n` leaves.
+    pub fn leaves(n: usize) -> TreeDef {
+        TreeDef::Tuple(vec![TreeDef::Leaf; n])
+    }
+
+    /// Checks that this is a struct node with the given name and fields and
+    /// returns its fields. Used by `#[derive(PjrtTree)]`.
+    #[doc(hidden)]
+    pub fn struct_fields(&self, name: &str, fields: &[&str]) -> Result<&[(String, TreeDef)]> {
+        match self {
+            TreeDef::Struct {
+                name: actual,
+                fields: actual_fields,
+            } if actual == name
+                && actual_fields.len() == fields.len()
+                && actual_fields.iter().zip(fields).all(|((a, _), b)| a == b) =>
+            {
+                Ok(actual_fields)
+            }
+            _ => {
+                let mut expected = format!("{name}{{");
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        expected.push_str(", ");
+                    }
+                    let _ = write!(expected, "{field}: ..");
+                }
+                expected.push('}');
+                Err(mismatch(expected, self))
+            }
+        }
+    }
+}
+
+impl Display for TreeDef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fn write_seq<'a>(
+            f: &mut Formatter<'_>,
+            items: impl IntoIterator<Item = &'a TreeDef>,
+        ) -> fmt::Result {
+            for (i, item) in items.into_iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{item}")?;
+            }
+            Ok(())
+        }
+
+        match self {
+            TreeDef::Leaf => f.write_str("*"),
+            TreeDef::None => f.write_str("None"),
+            TreeDef::Tuple(children) => {
+                f.write_str("(")?;
+                write_seq(f, children)?;
+                if children.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            TreeDef::List(children) => {
+                f.write_str("[")?;
+                write_seq(f, children)?;
+                f.write_str("]")
+            }
+            TreeDef::Dict(entries) => {
+                f.write_str("{")?;
+                for (i, (key, def)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    f.write_char('\'')?;
+                    for c in key.chars() {
+                        if c == '\'' || c == '\\' {
+                            f.write_char('\\')?;
+                        }
+                        f.write_char(c)?;
+                    }
+                    write!(f, "': {def}")?;
+                }
+                f.write_str("}")
+            }
+            TreeDef::Struct { name, fields } => {
+                write!(f, "{name}{{")?;
+                for (i, (field, def)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{field}: {def}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl FromStr for TreeDef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let def = parser.parse_def()?;
+        parser.skip_whitespace();
+        if parser.pos != s.len() {
+            return Err(parser.error("end of input"));
+        }
+        Ok(def)
+    }
+}
+
+/// Recursive descent parser for the `Display` form of `TreeDef`.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{c}'")))
+        }
+    }
+
+    fn error(&self, expected: &str) -> Error {
+        Error::InvalidArgument(format!(
+            "invalid tree definition {:?}: expected {expected} at offset {}",
+            self.input, self.pos
+        ))
+    }
+
+    fn parse_def(&mut self) -> Result<TreeDef> {
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(TreeDef::Leaf)
+            }
+            Some('(') => {
+                self.pos += 1;
+                let (children, trailing_comma) = self.parse_seq(')')?;
+                if children.len() == 1 && !trailing_comma {
+                    // A parenthesized definition rather than a 1-tuple.
+                    return Ok(children.into_iter().next().unwrap());
+                }
+                Ok(TreeDef::Tuple(children))
+            }
+            Some('[') => {
+                self.pos += 1;
+                Ok(TreeDef::List(self.parse_seq(']')?.0))
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.parse_dict()
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.parse_ident();
+                if self.eat('{') {
+                    self.parse_struct(name)
+                } else if name == "None" {
+                    Ok(TreeDef::None)
+                } else {
+                    Err(self.error("'{'"))
+                }
+            }
+            _ => Err(self.error("a tree definition")),
+        }
+    }
+
+    /// Parses comma separated definitions up to `close`, returning whether
+    /// the last one was followed by a comma.
+    fn parse_seq(&mut self, close: char) -> Result<(Vec<TreeDef>, bool)> {
+        let mut children = vec![];
+        let mut trailing_comma = false;
+        while !self.eat(close) {
+            children.push(self.parse_def()?);
+            trailing_comma = self.eat(',');
+            if !trailing_comma {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok((children, trailing_comma))
+    }
+
+    fn parse_dict(&mut self) -> Result<TreeDef> {
+        let mut entries = BTreeMap::new();
+        while !self.eat('}') {
+            let key = self.parse_key()?;
+            self.expect(':')?;
+            let def = self.parse_def()?;
+            if entries.insert(key, def).is_some() {
+                return Err(self.error("unique dict keys"));
+            }
+            if !self.eat(',') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(TreeDef::Dict(entries))
+    }
+
+    fn parse_struct(&mut self, name: String) -> Result<TreeDef> {
+        let mut fields = vec![];
+        while !self.eat('}') {
+            self.skip_whitespace();
+            let field = self.parse_ident();
+            if field.is_empty() {
+                return Err(self.error("a field name"));
+            }
+            self.expect(':')?;
+            fields.push((field, self.parse_def()?));
+            if !self.eat(',') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(TreeDef::Struct { name, fields })
+    }
+
+    /// Parses an identifier or a tuple struct field index.
+    fn parse_ident(&mut self) -> String {
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        let ident = self.rest()[..len].to_string();
+        self.pos += len;
+        ident
+    }
+
+    fn parse_key(&mut self) -> Result<String> {
+        let quote = match self.peek() {
+            Some(c @ ('\'' | '"')) => c,
+            _ => return Err(self.error("a quoted key")),
+        };
+        self.pos += 1;
+        let mut key = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => key.push(escaped),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(key);
+                }
+                c => key.push(c),
+            }
+        }
+        self.pos = self.input.len();
+        Err(self.error("a closing quote"))
+    }
+}
+
+fn mismatch(expected: impl Into<String>, actual: &TreeDef) -> Error {
+    Error::TreeMismatch {
+        expected: expected.into(),
+        actual: actual.to_string(),
+    }
+}
+
+/// A tree definition together with its leaves in flattening order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree<T> {
+    def: TreeDef,
+    leaves: Vec<T>,
+}
+
+impl<T> Tree<T> {
+    /// Creates a tree, checking that `leaves` matches the number of leaves
+    /// of `def`.
+    pub fn new(def: TreeDef, leaves: Vec<T>) -> Result<Self> {
+        if def.num_leaves() != leaves.len() {
+            return Err(Error::TreeMismatch {
+                expected: format!("{def} with {} leaves", def.num_leaves()),
+                actual: format!("{} leaves", leaves.len()),
+            });
+        }
+        Ok(Self { def, leaves })
+    }
+
+    /// Creates a tree holding a single leaf.
+    pub fn leaf(leaf: T) -> Self {
+        Self {
+            def: TreeDef::Leaf,
+            leaves: vec![leaf],
+        }
+    }
+
+    pub fn def(&self) -> &TreeDef {
+        &self.def
+    }
+
+    pub fn leaves(&self) -> &[T] {
+        &self.leaves
+    }
+
+    pub fn into_leaves(self) -> Vec<T> {
+        self.leaves
+    }
+
+    pub fn into_parts(self) -> (TreeDef, Vec<T>) {
+        (self.def, self.leaves)
+    }
+
+    /// Applies `f` to every leaf, keeping the structure.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Tree<U> {
+        Tree {
+            def: self.def,
+            leaves: self.leaves.into_iter().map(f).collect(),
+        }
+    }
+
+    /// Applies the fallible `f` to every leaf, keeping the structure.
+    pub fn try_map<U>(self, f: impl FnMut(T) -> Result<U>) -> Result<Tree<U>> {
+        Ok(Tree {
+            def: self.def,
+            leaves: self.leaves.into_iter().map(f).collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl Tree<Buffer> {
+    /// Rebuilds a value of type `T` from this tree.
+    pub fn unflatten<T: PjrtTree>(self) -> Result<T> {
+        T::unflatten(&self.def, self.leaves)
+    }
+}
+
+/// Conversion between a value and a tree of buffers.
+///
+/// Derive it for structs with `#[derive(PjrtTree)]`.
+pub trait PjrtTree: Sized {
+    /// Returns the structure of this value.
+    fn tree_def(&self) -> TreeDef;
+
+    /// Appends the leaves of this value to `leaves` in flattening order.
+    fn flatten_into(self, leaves: &mut Vec<Buffer>);
+
+    /// Rebuilds a value with structure `def`, taking its leaves from
+    /// `leaves`.
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self>;
+
+    /// Returns the structure shared by every value of this type, if it is
+    /// fixed.
+    ///
+    /// This is `None` for types such as `Vec` whose structure depends on the
+    /// value.
+    fn static_tree_def() -> Option<TreeDef> {
+        None
+    }
+
+    /// Flattens this value into its leaves and structure.
+    fn flatten(self) -> (Vec<Buffer>, TreeDef) {
+        let def = self.tree_def();
+        let mut leaves = Vec::with_capacity(def.num_leaves());
+        self.flatten_into(&mut leaves);
+        (leaves, def)
+    }
+
+    /// Rebuilds a value with structure `def` from its leaves.
+    fn unflatten(def: &TreeDef, leaves: Vec<Buffer>) -> Result<Self> {
+        if def.num_leaves() != leaves.len() {
+            return Err(Error::TreeMismatch {
+                expected: format!("{def} with {} leaves", def.num_leaves()),
+                actual: format!("{} leaves", leaves.len()),
+            });
+        }
+        Self::unflatten_from(def, &mut leaves.into_iter())
+    }
+}
+
+impl PjrtTree for Buffer {
+    fn tree_def(&self) -> TreeDef {
+        TreeDef::Leaf
+    }
+
+    fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+        leaves.push(self);
+    }
+
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        match def {
+            TreeDef::Leaf => leaves.next().ok_or_else(|| Error::TreeMismatch {
+                expected: "a leaf".to_string(),
+                actual: "no remaining leaves".to_string(),
+            }),
+            _ => Err(mismatch("*", def)),
+        }
+    }
+
+    fn static_tree_def() -> Option<TreeDef> {
+        Some(TreeDef::Leaf)
+    }
+}
+
+impl<T: PjrtTree> PjrtTree for Option<T> {
+    fn tree_def(&self) -> TreeDef {
+        match self {
+            Some(value) => value.tree_def(),
+            None => TreeDef::None,
+        }
+    }
+
+    fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+        if let Some(value) = self {
+            value.flatten_into(leaves);
+        }
+    }
+
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        match def {
+            TreeDef::None => Ok(None),
+            def => T::unflatten_from(def, leaves).map(Some),
+        }
+    }
+}
+
+impl<T: PjrtTree> PjrtTree for Vec<T> {
+    fn tree_def(&self) -> TreeDef {
+        TreeDef::List(self.iter().map(T::tree_def).collect())
+    }
+
+    fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+        for value in self {
+            value.flatten_into(leaves);
+        }
+    }
+
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        match def {
+            TreeDef::List(children) => children
+                .iter()
+                .map(|child| T::unflatten_from(child, leaves))
+                .collect(),
+            _ => Err(mismatch("[..]", def)),
+        }
+    }
+}
+
+impl<T: PjrtTree, const N: usize> PjrtTree for [T; N] {
+    fn tree_def(&self) -> TreeDef {
+        TreeDef::List(self.iter().map(T::tree_def).collect())
+    }
+
+    fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+        for value in self {
+            value.flatten_into(leaves);
+        }
+    }
+
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        match def {
+            TreeDef::List(children) if children.len() == N => {
+                let values = children
+                    .iter()
+                    .map(|child| T::unflatten_from(child, leaves))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(values
+                    .try_into()
+                    .unwrap_or_else(|_| unreachable!("length checked above")))
+            }
+            _ => Err(mismatch(format!("a list of {N} elements"), def)),
+        }
+    }
+
+    fn static_tree_def() -> Option<TreeDef> {
+        let child = T::static_tree_def()?;
+        Some(TreeDef::List(vec![child; N]))
+    }
+}
+
+impl<T: PjrtTree> PjrtTree for BTreeMap<String, T> {
+    fn tree_def(&self) -> TreeDef {
+        TreeDef::Dict(
+            self.iter()
+                .map(|(key, value)| (key.clone(), value.tree_def()))
+                .collect(),
+        )
+    }
+
+    fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+        for value in self.into_values() {
+            value.flatten_into(leaves);
+        }
+    }
+
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        match def {
+            TreeDef::Dict(entries) => entries
+                .iter()
+                .map(|(key, child)| Ok((key.clone(), T::unflatten_from(child, leaves)?)))
+                .collect(),
+            _ => Err(mismatch("{..}", def)),
+        }
+    }
+}
+
+impl PjrtTree for Tree<Buffer> {
+    fn tree_def(&self) -> TreeDef {
+        self.def.clone()
+    }
+
+    fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+        leaves.extend(self.leaves);
+    }
+
+    fn unflatten_from(def: &TreeDef, leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        let n = def.num_leaves();
+        if leaves.len() < n {
+            return Err(Error::TreeMismatch {
+                expected: format!("{def} with {n} leaves"),
+                actual: format!("{} remaining leaves", leaves.len()),
+            });
+        }
+        Ok(Tree {
+            def: def.clone(),
+            leaves: leaves.take(n).collect(),
+        })
+    }
+}
+
+macro_rules! impl_tuple_tree {
+    ($len:literal => $($name:ident $index:tt),+) => {
+        impl<$($name: PjrtTree),+> PjrtTree for ($($name,)+) {
+            fn tree_def(&self) -> TreeDef {
+                TreeDef::Tuple(vec![$(self.$index.tree_def()),+])
+            }
+
+            fn flatten_into(self, leaves: &mut Vec<Buffer>) {
+                $(self.$index.flatten_into(leaves);)+
+            }
+
+            fn unflatten_from(
+                def: &TreeDef,
+                leaves: &mut std::vec::IntoIter<Buffer>,
+            ) -> Result<Self> {
+                match def {
+                    TreeDef::Tuple(children) if children.len() == $len => {
+                        Ok(($($name::unflatten_from(&children[$index], leaves)?,)+))
+                    }
+                    _ => Err(mismatch(format!("a tuple of {} elements", $len), def)),
+                }
+            }
+
+            fn static_tree_def() -> Option<TreeDef> {
+                Some(TreeDef::Tuple(vec![$($name::static_tree_def()?),+]))
+            }
+        }
+    };
+}
+
+impl PjrtTree for () {
+    fn tree_def(&self) -> TreeDef {
+        TreeDef::Tuple(vec![])
+    }
+
+    fn flatten_into(self, _leaves: &mut Vec<Buffer>) {}
+
+    fn unflatten_from(def: &TreeDef, _leaves: &mut std::vec::IntoIter<Buffer>) -> Result<Self> {
+        match def {
+            TreeDef::Tuple(children) if children.is_empty() => Ok(()),
+            _ => Err(mismatch("()", def)),
+        }
+    }
+
+    fn static_tree_def() -> Option<TreeDef> {
+        Some(TreeDef::Tuple(vec![]))
+    }
+}
+
+impl_tuple_tree!(1 => A 0);
+impl_tuple_tree!(2 => A 0, B 1);
+impl_tuple_tree!(3 => A 0, B 1, C 2);
+impl_tuple_tree!(4 => A 0, B 1, C 2, D 3);
+impl_tuple_tree!(5 => A 0, B 1, C 2, D 3, E 4);
+impl_tuple_tree!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple_tree!(7 => A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple_tree!(8 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> TreeDef {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_tree_def_display() {
+        let mut entries = BTreeMap::new();
+        entries.insert("b".to_string(), TreeDef::List(vec![TreeDef::Leaf]));
+        entries.insert("a".to_string(), TreeDef::None);
+        let def = TreeDef::Tuple(vec![
+            TreeDef::Leaf,
+            TreeDef::Dict(entries),
+            TreeDef::Tuple(vec![TreeDef::Leaf]),
+            TreeDef::Struct {
+                name: "Params".to_string(),
+                fields: vec![
+                    ("w".to_string(), TreeDef::Leaf),
+                    ("b".to_string(), TreeDef::Leaf),
+                ],
+            },
+        ]);
+        assert_eq!(
+            def.to_string(),
+            "(*, {'a': None, 'b': [*]}, (*,), Params{w: *, b: *})"
+        );
+    }
+
+    #[test]
+    fn test_tree_def_round_trip() {
+        for s in [
+            "*",
+            "None",
+            "()",
+            "(*,)",
+            "(*, *)",
+            "[]",
+            "[*, [*, *]]",
+            "{}",
+            "{'a': *, 'b': (*, None)}",
+            "{'it\\'s': *}",
+            "Empty{}",
+            "Pair{0: *, 1: *}",
+            "Layer{w: *, b: [*, *], extra: None}",
+        ] {
+            assert_eq!(parse(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_tree_def_parse_lenient() {
+        assert_eq!(parse(" ( * , * , ) "), TreeDef::leaves(2));
+        assert_eq!(parse("(*)"), TreeDef::Leaf);
+        assert_eq!(parse("[*,]"), TreeDef::List(vec![TreeDef::Leaf]));
+        assert_eq!(parse("{\"b\": *, 'a': *}").to_string(), "{'a': *, 'b': *}");
+    }
+
+    #[test]
+    fn test_tree_def_parse_errors() {
+        for s in [
+            "",
+            "(",
+            "(*",
+            "[*,*",
+            "*,",
+            "{a: *}",
+            "{'a' *}",
+            "{'a",
+            "Foo",
+            "Foo{: *}",
+            "{'a': *, 'a': *}",
+            "**",
+        ] {
+            let err = s.parse::<TreeDef>().unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidArgument(_)),
+                "{s:?} gave {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tree_def_num_leaves() {
+        assert_eq!(parse("*").num_leaves(), 1);
+        assert_eq!(parse("None").num_leaves(), 0);
+        assert_eq!(
+            parse("(*, [*, *], {'a': *}, S{x: *, y: None})").num_leaves(),
+            5
+        );
+        assert_eq!(TreeDef::leaves(3).num_leaves(), 3);
+    }
+
+    #[test]
+    fn test_tree_def_children() {
+        let def = parse("{'b': None, 'a': *}");
+        assert_eq!(def.children(), vec![&TreeDef::Leaf, &TreeDef::None]);
+        assert!(TreeDef::Leaf.children().is_empty());
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        let def = parse("Params{w: *, b: *}");
+        assert_eq!(def.struct_fields("Params", &["w", "b"]).unwrap().len(), 2);
+
+        let err = def.struct_fields("Params", &["w"]).unwrap_err();
+        match err {
+            Error::TreeMismatch { expected, actual } => {
+                assert_eq!(expected, "Params{w: ..}");
+                assert_eq!(actual, "Params{w: *, b: *}");
+            }
+            _ => panic!("Expected TreeMismatch variant"),
+        }
+        assert!(def.struct_fields("Other", &["w", "b"]).is_err());
+        assert!(TreeDef::Leaf.struct_fields("Params", &[]).is_err());
+    }
+
+    #[test]
+    fn test_tree_new_checks_leaf_count() {
+        let tree = Tree::new(parse("(*, [*])"), vec![1, 2]).unwrap();
+        assert_eq!(tree.leaves(), &[1, 2]);
+        assert!(matches!(
+            Tree::new(parse("(*, [*])"), vec![1]),
+            Err(Error::TreeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tree_map() {
+        let tree = Tree::new(parse("{'a': *, 'b': *}"), vec![1, 2]).unwrap();
+        let doubled = tree.clone().map(|x| x * 2);
+        assert_eq!(doubled.def(), tree.def());
+        assert_eq!(doubled.into_leaves(), vec![2, 4]);
+
+        let err = tree
+            .try_map(|x| {
+                if x == 2 {
+                    Err(Error::InvalidArgument("two".to_string()))
+                } else {
+                    Ok(x)
+                }
+            })
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)));
+
+        let (def, leaves) = Tree::leaf("x").into_parts();
+        assert_eq!(def, TreeDef::Leaf);
+        assert_eq!(leaves, vec!["x"]);
+    }
+
+    #[test]
+    fn test_static_tree_defs() {
+        assert_eq!(Buffer::static_tree_def(), Some(TreeDef::Leaf));
+        assert_eq!(<()>::static_tree_def(), Some(TreeDef::Tuple(vec![])));
+        assert_eq!(
+            <(Buffer, [Buffer; 2])>::static_tree_def()
+                .unwrap()
+                .to_string(),
+            "(*, [*, *])"
+        );
+        assert_eq!(<Vec<Buffer>>::static_tree_def(), None);
+        assert_eq!(<(Buffer, Option<Buffer>)>::static_tree_def(), None);
+        assert_eq!(<BTreeMap<String, Buffer>>::static_tree_def(), None);
+    }
+
+    #[allow(dead_code)]
+    #[derive(crate::PjrtTree)]
+    struct Params {
+        w: Buffer,
+        layers: [(Buffer, Buffer); 2],
+    }
+
+    #[allow(dead_code)]
+    #[derive(crate::PjrtTree)]
+    struct Wrapper<T>(T, Buffer);
+
+    #[derive(crate::PjrtTree)]
+    struct Empty;
+
+    #[test]
+    fn test_derived_static_tree_defs() {
+        assert_eq!(
+            Params::static_tree_def().unwrap().to_string(),
+            "Params{w: *, layers: [(*, *), (*, *)]}"
+        );
+        assert_eq!(
+            <Wrapper<(Buffer,)>>::static_tree_def().unwrap().to_string(),
+            "Wrapper{0: (*,), 1: *}"
+        );
+        assert_eq!(<Wrapper<Vec<Buffer>>>::static_tree_def(), None);
+        assert_eq!(Empty::static_tree_def().unwrap().to_string(), "Empty{}");
+    }
+
+    #[test]
+    fn test_unflatten_without_leaves() {
+        let empty = Empty::unflatten(&parse("Empty{}"), vec![]);
+        assert!(empty.is_ok());
+        assert!(<()>::unflatten(&parse("()"), vec![]).is_ok());
+        assert!(<Option<Buffer>>::unflatten(&TreeDef::None, vec![])
+            .unwrap()
+            .is_none());
+        let list = <Vec<Option<Buffer>>>::unflatten(&parse("[None, None]"), vec![]).unwrap();
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_unflatten_mismatch() {
+        assert!(matches!(
+            Empty::unflatten(&parse("Other{}"), vec![]),
+            Err(Error::TreeMismatch { .. })
+        ));
+        assert!(matches!(
+            <()>::unflatten(&parse("[]"), vec![]),
+            Err(Error::TreeMismatch { .. })
+        ));
+        assert!(matches!(
+            <Vec<()>>::unflatten(&parse("{'a': ()}"), vec![]),
+            Err(Error::TreeMismatch { .. })
+        ));
+        assert!(matches!(
+            <[(); 2]>::unflatten(&parse("[()]"), vec![]),
+            Err(Error::TreeMismatch { .. })
+        ));
+        assert!(matches!(
+            <Option<Buffer>>::unflatten(&TreeDef::Leaf, vec![]),
+            Err(Error::TreeMismatch { .. })
+        ));
+    }
+}
@@ -526,6 +526,48 @@ impl TryFrom<PJRT_Buffer_Type> for PrimitiveType {
     }
 }
 
+impl PrimitiveType {
+    /// Converts an `xla.PrimitiveType` proto enum value into a `PrimitiveType`.
+    ///
+    /// The XLA proto numbering differs from `PJRT_Buffer_Type`, so protos such
+    /// as `ShapeProto::element_type` must go through this conversion.
+    pub(crate) fn from_xla_proto(value: i32) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Invalid),
+            1 => Ok(Self::Pred),
+            2 => Ok(Self::S8),
+            3 => Ok(Self::S16),
+            4 => Ok(Self::S32),
+            5 => Ok(Self::S64),
+            6 => Ok(Self::U8),
+            7 => Ok(Self::U16),
+            8 => Ok(Self::U32),
+            9 => Ok(Self::U64),
+            10 => Ok(Self::F16),
+            11 => Ok(Self::F32),
+            12 => Ok(Self::F64),
+            15 => Ok(Self::C64),
+            16 => Ok(Self::BF16),
+            17 => Ok(Self::Token),
+            18 => Ok(Self::C128),
+            19 => Ok(Self::F8E5M2),
+            20 => Ok(Self::F8E4M3FN),
+            21 => Ok(Self::S4),
+            22 => Ok(Self::U4),
+            23 => Ok(Self::F8E4M3B11FNUZ),
+            24 => Ok(Self::F8E5M2FNUZ),
+            25 => Ok(Self::F8E4M3FNUZ),
+            26 => Ok(Self::S2),
+            27 => Ok(Self::U2),
+            28 => Ok(Self::F8E4M3),
+            29 => Ok(Self::F8E3M4),
+            32 => Ok(Self::F4E2M1FN),
+            33 => Ok(Self::F8E8M0FNU),
+            _ => Err(Error::InvalidPrimitiveType(value as u32)),
+        }
+    }
+}
+
 pub trait DType {
     fn name(&self) -> &'static str;
     fn primitive_type(&self) -> PrimitiveType;
@@ -912,4 +954,29 @@ mod tests {
         assert_eq!(<half::bf16 as ElemType>::Type::NAME, "bf16");
         assert_eq!(<half::bf16 as ElemType>::Type::SIZE, 2);
     }
+
+    #[test]
+    fn test_primitive_type_from_xla_proto() {
+        assert_eq!(
+            PrimitiveType::from_xla_proto(1).unwrap(),
+            PrimitiveType::Pred
+        );
+        assert_eq!(
+            PrimitiveType::from_xla_proto(11).unwrap(),
+            PrimitiveType::F32
+        );
+        assert_eq!(
+            PrimitiveType::from_xla_proto(16).unwrap(),
+            PrimitiveType::BF16
+        );
+        assert_eq!(
+            PrimitiveType::from_xla_proto(18).unwrap(),
+            PrimitiveType::C128
+        );
+        // TUPLE has no buffer type equivalent
+        assert!(matches!(
+            PrimitiveType::from_xla_proto(13),
+            Err(Error::InvalidPrimitiveType(13))
+        ));
+    }
 }
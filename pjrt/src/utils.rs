@@ -4,7 +4,7 @@ use std::slice;
 
 use pjrt_sys::PJRT_NamedValue;
 
-use crate::{NamedValueMap, Result};
+use crate::{MemoryLayout, NamedValueMap, Result};
 
 pub(crate) fn str_from_raw<'a>(ptr: *const c_char, size: usize) -> Cow<'a, str> {
     if ptr.is_null() {
@@ -52,6 +52,22 @@ pub(crate) fn byte_strides(shape: &[i64], elem_ty_size: usize) -> Vec<i64> {
     strides
 }
 
+/// Returns whether `layout` is the dense row-major layout of an array of
+/// shape `dims` with elements of `elem_size` bytes.
+pub(crate) fn is_row_major(layout: &MemoryLayout, dims: &[i64], elem_size: usize) -> bool {
+    match layout {
+        MemoryLayout::Strides(strides) => strides.byte_strides == byte_strides(dims, elem_size),
+        MemoryLayout::Tiled(tiled) => {
+            tiled.tile_dims.as_deref().unwrap_or_default().is_empty()
+                && tiled
+                    .minor_to_major
+                    .iter()
+                    .copied()
+                    .eq((0..dims.len() as i64).rev())
+        }
+    }
+}
+
 pub(super) fn to_named_value_map(
     values: *const PJRT_NamedValue,
     size: usize,
@@ -132,6 +148,26 @@ mod tests {
         assert_eq!(strides_f64, vec![24, 8]);
     }
 
+    #[test]
+    fn test_is_row_major() {
+        let dims = [2, 3];
+        assert!(is_row_major(
+            &MemoryLayout::from_strides(vec![12, 4]),
+            &dims,
+            4
+        ));
+        assert!(is_row_major(
+            &MemoryLayout::from_tiled(vec![1, 0]).build(),
+            &dims,
+            4
+        ));
+        assert!(!is_row_major(
+            &MemoryLayout::from_tiled(vec![0, 1]).build(),
+            &dims,
+            4
+        ));
+    }
+
     #[test]
     fn test_to_named_value_map_empty() {
         let map = to_named_value_map(std::ptr::null(), 0).unwrap();